@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[path = "build/dbc.rs"]
+mod dbc;
+
+use dbc::Dbc;
+
+/// The vehicle description used when `CAN_DBC` is not set, relative to this crate.
+const DEFAULT_DBC: &str = "dbc/vehicle.dbc";
+
+fn main() {
+    let dbc_path = env::var("CAN_DBC").unwrap_or_else(|_| DEFAULT_DBC.to_string());
+    println!("cargo:rerun-if-env-changed=CAN_DBC");
+    println!("cargo:rerun-if-changed={}", dbc_path);
+    println!("cargo:rerun-if-changed=build/dbc.rs");
+    let source = fs::read_to_string(&dbc_path)
+        .unwrap_or_else(|e| panic!("Can not read DBC file {}: {}", dbc_path, e));
+    let dbc = Dbc::parse(&source).unwrap_or_else(|e| panic!("{}: {}", dbc_path, e));
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("dbc.rs");
+    fs::write(&out, dbc.generate()).unwrap();
+}
+
@@ -0,0 +1,297 @@
+//! The DBC parser and code generator behind `build.rs`, in a file of its own so the
+//! host tests can include it as well.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Signal attribute naming the `CarState` field a signal feeds, e.g.
+/// `BA_ "CarStateField" SG_ 201 EngineSpeed "engine_rpm";`
+const CAR_STATE_ATTRIBUTE: &str = "CarStateField";
+/// The usual message attribute for how often a message is sent, in ms, e.g.
+/// `BA_ "GenMsgCycleTime" BO_ 201 20;`
+const CYCLE_TIME_ATTRIBUTE: &str = "GenMsgCycleTime";
+
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u16,
+    pub length: u8,
+    pub big_endian: bool,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    /// `None` for plain signals, `Some(None)` for the multiplexor itself and
+    /// `Some(Some(n))` for a signal only present when the multiplexor reads `n`.
+    pub multiplex: Option<Option<u32>>,
+}
+
+pub struct Message {
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    pub dlc: u8,
+    pub cycle_ms: Option<u32>,
+    pub signals: Vec<Signal>,
+}
+
+#[derive(Default)]
+pub struct Dbc {
+    /// Keyed on the raw DBC id (bit 31 marks extended frames) so the output is sorted.
+    pub messages: BTreeMap<u32, Message>,
+    /// (raw message id, signal name) -> CarState field name
+    pub bindings: BTreeMap<(u32, String), String>,
+}
+
+impl Dbc {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut dbc = Dbc::default();
+        let mut current: Option<u32> = None;
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            let context = |e: String| format!("line {}: {}", number + 1, e);
+            if let Some(rest) = line.strip_prefix("BO_ ") {
+                let message = parse_message(rest).map_err(context)?;
+                let raw_id = message.id | if message.extended { 0x8000_0000 } else { 0 };
+                current = Some(raw_id);
+                dbc.messages.insert(raw_id, message);
+            } else if let Some(rest) = line.strip_prefix("SG_ ") {
+                let raw_id = current.ok_or_else(|| context("SG_ outside of a BO_".to_string()))?;
+                let signal = parse_signal(rest).map_err(context)?;
+                dbc.messages.get_mut(&raw_id).unwrap().signals.push(signal);
+            } else if let Some(rest) = line.strip_prefix("BA_ ") {
+                if let Some((raw_id, signal, field)) = parse_binding(rest) {
+                    dbc.bindings.insert((raw_id, signal), field);
+                } else if let Some((raw_id, cycle_ms)) = parse_cycle_time(rest) {
+                    let message = dbc
+                        .messages
+                        .get_mut(&raw_id)
+                        .ok_or_else(|| context(format!("{} for unknown message {}", CYCLE_TIME_ATTRIBUTE, raw_id)))?;
+                    message.cycle_ms = Some(cycle_ms);
+                }
+            } else if line.is_empty() {
+                current = None;
+            }
+        }
+        for (raw_id, signal) in dbc.bindings.keys() {
+            let known = dbc
+                .messages
+                .get(raw_id)
+                .is_some_and(|message| message.signals.iter().any(|s| &s.name == signal));
+            if !known {
+                return Err(format!("{} bound to unknown signal {} in message {}", CAR_STATE_ATTRIBUTE, signal, raw_id));
+            }
+        }
+        Ok(dbc)
+    }
+
+    pub fn generate(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "// Generated by build.rs from the DBC file, do not edit.").unwrap();
+        for (index, message) in self.messages.values().enumerate() {
+            writeln!(out, "const MESSAGE_{}_SIGNALS: &[SignalDef] = &[", index).unwrap();
+            for signal in &message.signals {
+                let multiplex = match signal.multiplex {
+                    None => "Multiplex::None".to_string(),
+                    Some(None) => "Multiplex::Multiplexor".to_string(),
+                    Some(Some(value)) => format!("Multiplex::Multiplexed({})", value),
+                };
+                writeln!(
+                    out,
+                    "    SignalDef {{ name: {:?}, start_bit: {}, length: {}, byte_order: ByteOrder::{}, signed: {}, factor: {:?}, offset: {:?}, min: {:?}, max: {:?}, unit: {:?}, multiplex: {} }},",
+                    signal.name,
+                    signal.start_bit,
+                    signal.length,
+                    if signal.big_endian { "BigEndian" } else { "LittleEndian" },
+                    signal.signed,
+                    signal.factor,
+                    signal.offset,
+                    signal.min,
+                    signal.max,
+                    signal.unit,
+                    multiplex,
+                )
+                .unwrap();
+            }
+            writeln!(out, "];").unwrap();
+            writeln!(
+                out,
+                "const MESSAGE_{}: MessageDef = MessageDef {{ id: {:#x}, extended: {}, name: {:?}, dlc: {}, cycle_ms: {:?}, signals: MESSAGE_{}_SIGNALS }};",
+                index, message.id, message.extended, message.name, message.dlc, message.cycle_ms, index
+            )
+            .unwrap();
+        }
+        writeln!(out, "pub static MESSAGES: &[MessageDef] = &[").unwrap();
+        for index in 0..self.messages.len() {
+            writeln!(out, "    MESSAGE_{},", index).unwrap();
+        }
+        writeln!(out, "];").unwrap();
+
+        writeln!(out, "pub static CAR_STATE_UPDATES: &[UpdateEntry] = &[").unwrap();
+        for (index, (raw_id, message)) in self.messages.iter().enumerate() {
+            let bound: Vec<(usize, &String)> = message
+                .signals
+                .iter()
+                .enumerate()
+                .filter_map(|(signal_index, signal)| {
+                    self.bindings
+                        .get(&(*raw_id, signal.name.clone()))
+                        .map(|field| (signal_index, field))
+                })
+                .collect();
+            if bound.is_empty() {
+                continue;
+            }
+            writeln!(out, "    UpdateEntry {{ message: &MESSAGE_{}, bindings: &[", index).unwrap();
+            for (signal_index, field) in bound {
+                writeln!(
+                    out,
+                    "        SignalBinding {{ signal: &MESSAGE_{}_SIGNALS[{}], field: CarField::{} }},",
+                    index,
+                    signal_index,
+                    camel_case(field)
+                )
+                .unwrap();
+            }
+            writeln!(out, "    ] }},").unwrap();
+        }
+        writeln!(out, "];").unwrap();
+        out
+    }
+}
+
+fn parse_message(rest: &str) -> Result<Message, String> {
+    // BO_ <id> <name>: <dlc> <transmitter>
+    let (head, tail) = rest.split_once(':').ok_or("BO_ without ':'")?;
+    let mut head = head.split_whitespace();
+    let raw_id: u32 = head
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or("BO_ without a valid id")?;
+    let name = head.next().ok_or("BO_ without a name")?.to_string();
+    let dlc: u8 = tail
+        .split_whitespace()
+        .next()
+        .and_then(|dlc| dlc.parse().ok())
+        .ok_or("BO_ without a valid dlc")?;
+    Ok(Message {
+        id: raw_id & 0x1FFF_FFFF,
+        extended: raw_id & 0x8000_0000 != 0,
+        name,
+        dlc,
+        cycle_ms: None,
+        signals: Vec::new(),
+    })
+}
+
+fn parse_signal(rest: &str) -> Result<Signal, String> {
+    // SG_ <name> [M|m<n>] : <start>|<length>@<order><sign> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>
+    let (head, tail) = rest.split_once(':').ok_or("SG_ without ':'")?;
+    let mut head = head.split_whitespace();
+    let name = head.next().ok_or("SG_ without a name")?.to_string();
+    let multiplex = match head.next() {
+        None => None,
+        Some("M") => Some(None),
+        Some(tag) => {
+            // Extended multiplexing ("m1M") is not supported, only the m<n> part is used
+            let value = tag
+                .strip_prefix('m')
+                .map(|value| value.trim_end_matches('M'))
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| format!("Invalid multiplex indicator {}", tag))?;
+            Some(Some(value))
+        }
+    };
+    let tail = tail.trim();
+    let (layout, tail) = tail.split_once(' ').ok_or("SG_ without a bit layout")?;
+    let (start_bit, layout) = layout.split_once('|').ok_or("Invalid bit layout")?;
+    let (length, layout) = layout.split_once('@').ok_or("Invalid bit layout")?;
+    let mut flags = layout.chars();
+    let big_endian = match flags.next() {
+        Some('0') => true,
+        Some('1') => false,
+        _ => return Err(format!("Invalid byte order in {}", name)),
+    };
+    let signed = match flags.next() {
+        Some('-') => true,
+        Some('+') => false,
+        _ => return Err(format!("Invalid sign in {}", name)),
+    };
+    let (factor, offset) = between(tail, '(', ')')
+        .and_then(|scale| scale.split_once(','))
+        .ok_or_else(|| format!("Missing (factor,offset) in {}", name))?;
+    let (min, max) = between(tail, '[', ']')
+        .and_then(|range| range.split_once('|'))
+        .ok_or_else(|| format!("Missing [min|max] in {}", name))?;
+    let unit = between(tail, '"', '"').unwrap_or_default().to_string();
+    let number = |value: &str| -> Result<f64, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid number {} in {}", value, name))
+    };
+    let length: u8 = length.parse().map_err(|_| format!("Invalid length in {}", name))?;
+    if length == 0 || length > 64 {
+        return Err(format!("Signal {} has an unsupported length of {} bits", name, length));
+    }
+    Ok(Signal {
+        start_bit: start_bit.parse().map_err(|_| format!("Invalid start bit in {}", name))?,
+        length,
+        big_endian,
+        signed,
+        factor: number(factor)?,
+        offset: number(offset)?,
+        min: number(min)?,
+        max: number(max)?,
+        unit,
+        multiplex,
+        name,
+    })
+}
+
+fn parse_binding(rest: &str) -> Option<(u32, String, String)> {
+    // BA_ "CarStateField" SG_ <id> <signal> "<field>";
+    let rest = rest.strip_prefix(&format!("\"{}\"", CAR_STATE_ATTRIBUTE))?;
+    let mut parts = rest.split_whitespace();
+    if parts.next()? != "SG_" {
+        return None;
+    }
+    let raw_id = parts.next()?.parse().ok()?;
+    let signal = parts.next()?.to_string();
+    let field = between(parts.next()?, '"', '"')?.to_string();
+    Some((raw_id, signal, field))
+}
+
+fn parse_cycle_time(rest: &str) -> Option<(u32, u32)> {
+    // BA_ "GenMsgCycleTime" BO_ <id> <ms>;
+    let rest = rest.strip_prefix(&format!("\"{}\"", CYCLE_TIME_ATTRIBUTE))?;
+    let mut parts = rest.split_whitespace();
+    if parts.next()? != "BO_" {
+        return None;
+    }
+    let raw_id = parts.next()?.parse().ok()?;
+    let cycle_ms = parts.next()?.trim_end_matches(';').parse().ok()?;
+    Some((raw_id, cycle_ms))
+}
+
+fn between(text: &str, open: char, close: char) -> Option<&str> {
+    let start = text.find(open)? + 1;
+    let end = text[start..].find(close)? + start;
+    Some(&text[start..end])
+}
+
+/// engine_rpm -> EngineRpm, the generated code refers to `CarField` variants so
+/// a typo in the DBC turns into a compile error.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
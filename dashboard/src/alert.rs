@@ -0,0 +1,210 @@
+//! Rules that watch `CarState` and raise alerts: a threshold with hysteresis, a
+//! debounce time both ways and a priority. The most urgent alert nobody
+//! acknowledged yet goes on the banner.
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{car_state::{CarField, CarState}, freshness::Freshness, scale::Scale, telltale::COOLANT_HOT};
+
+/// Alerts kept in the history, the oldest go first.
+pub const HISTORY_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Field(CarField),
+    /// `CarState::voltage`, 0 until the first reading.
+    Voltage,
+    /// 1 once the field stopped coming, see `CarState::freshness`.
+    Lost(CarField),
+}
+
+impl Source {
+    fn read(self, state: &CarState, now_ms: u64) -> Option<f32> {
+        match self {
+            // A lost value is as good as none, it must not hold an alert up
+            Source::Field(field) => state.get(field).filter(|_| state.freshness(field, now_ms) != Freshness::Lost),
+            Source::Voltage => Some(state.voltage()).filter(|volts| *volts > 0.0),
+            Source::Lost(field) => Some(if state.freshness(field, now_ms) == Freshness::Lost { 1.0 } else { 0.0 }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Above(f32),
+    Below(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    /// Shown on the banner, up to 15 characters fit.
+    pub message: &'static str,
+    pub source: Source,
+    pub condition: Condition,
+    /// How far back past the threshold the value has to go before the alert clears.
+    pub hysteresis: f32,
+    /// How long the condition has to hold, or be gone, before the alert changes.
+    pub debounce_ms: u64,
+    pub priority: Priority,
+}
+
+impl Rule {
+    /// Whether `value` is bad, with the hysteresis applied while the alert is already active.
+    fn tripped(&self, value: f32, active: bool) -> bool {
+        let slack = if active { self.hysteresis } else { 0.0 };
+        match self.condition {
+            Condition::Above(limit) => value > limit - slack,
+            Condition::Below(limit) => value < limit + slack,
+        }
+    }
+}
+
+/// Coolant, battery, oil pressure and over-revving, and the main gauges' sensors going quiet.
+pub fn default_rules() -> Vec<Rule> {
+    let redline = Scale::tachometer().redline.map_or(6500.0, |band| band.from);
+    vec![
+        Rule {
+            message: "OIL PRESSURE",
+            source: Source::Field(CarField::OilPressureWarning),
+            condition: Condition::Above(0.5),
+            hysteresis: 0.0,
+            debounce_ms: 500,
+            priority: Priority::Critical,
+        },
+        Rule {
+            message: "COOLANT HOT",
+            source: Source::Field(CarField::CoolantTemp),
+            condition: Condition::Above(COOLANT_HOT),
+            hysteresis: 5.0,
+            debounce_ms: 2000,
+            priority: Priority::Critical,
+        },
+        Rule {
+            message: "OVER REV",
+            source: Source::Field(CarField::EngineRpm),
+            condition: Condition::Above(redline),
+            hysteresis: 200.0,
+            debounce_ms: 200,
+            priority: Priority::Warning,
+        },
+        // Cranking pulls the battery down for a few seconds, that is no reason to shout
+        Rule {
+            message: "LOW BATTERY",
+            source: Source::Voltage,
+            condition: Condition::Below(11.8),
+            hysteresis: 0.3,
+            debounce_ms: 5000,
+            priority: Priority::Warning,
+        },
+        sensor_lost("SPEED LOST", CarField::VehicleSpeed),
+        sensor_lost("RPM LOST", CarField::EngineRpm),
+        sensor_lost("COOLANT LOST", CarField::CoolantTemp),
+    ]
+}
+
+/// Losing a value already takes a while (`freshness::MIN_LOST_MS`), so no debounce on top.
+fn sensor_lost(message: &'static str, field: CarField) -> Rule {
+    Rule {
+        message,
+        source: Source::Lost(field),
+        condition: Condition::Above(0.5),
+        hysteresis: 0.0,
+        debounce_ms: 0,
+        priority: Priority::Warning,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub rule: Rule,
+    pub active: bool,
+    /// Acknowledged alerts stay active but leave the banner, until they clear and trip again.
+    pub acknowledged: bool,
+    /// When the condition started to disagree with `active`.
+    since: Option<u64>,
+}
+
+/// One time an alert went off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Index into `AlertEngine::alerts`.
+    pub alert: usize,
+    pub raised_ms: u64,
+    /// `None` while it is still active.
+    pub cleared_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertEngine {
+    pub alerts: Vec<Alert>,
+    /// Newest last.
+    pub history: VecDeque<HistoryEntry>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let alerts = rules
+            .into_iter()
+            .map(|rule| Alert { rule, active: false, acknowledged: false, since: None })
+            .collect();
+        AlertEngine { alerts, history: VecDeque::new() }
+    }
+
+    pub fn update(&mut self, state: &CarState, now_ms: u64) {
+        for (index, alert) in self.alerts.iter_mut().enumerate() {
+            // An unknown value counts as fine, there is nothing to warn about yet
+            let tripped = alert
+                .rule
+                .source
+                .read(state, now_ms)
+                .is_some_and(|value| alert.rule.tripped(value, alert.active));
+            if tripped == alert.active {
+                alert.since = None;
+                continue;
+            }
+            let since = *alert.since.get_or_insert(now_ms);
+            if now_ms.saturating_sub(since) >= alert.rule.debounce_ms {
+                alert.active = tripped;
+                alert.acknowledged = false;
+                alert.since = None;
+                if tripped {
+                    if self.history.len() == HISTORY_LEN {
+                        self.history.pop_front();
+                    }
+                    self.history.push_back(HistoryEntry { alert: index, raised_ms: now_ms, cleared_ms: None });
+                } else if let Some(entry) = self.history.iter_mut().rev().find(|entry| entry.alert == index) {
+                    entry.cleared_ms = Some(now_ms);
+                }
+            }
+        }
+    }
+
+    /// The alert for the banner: the highest priority active one not acknowledged yet,
+    /// the first rule wins a tie.
+    pub fn banner(&self) -> Option<usize> {
+        self.alerts
+            .iter()
+            .enumerate()
+            .filter(|(_, alert)| alert.active && !alert.acknowledged)
+            .min_by_key(|(index, alert)| (core::cmp::Reverse(alert.rule.priority), *index))
+            .map(|(index, _)| index)
+    }
+
+    /// Acknowledges the alert on the banner, the next one takes its place. Returns whether there was one.
+    pub fn acknowledge(&mut self) -> bool {
+        let Some(index) = self.banner() else {
+            return false;
+        };
+        self.alerts[index].acknowledged = true;
+        true
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts.iter().filter(|alert| alert.active)
+    }
+}
@@ -0,0 +1,181 @@
+//! Anti-aliased primitives for `Rgb565` targets. Every pixel near an edge gets the
+//! share of the colour the shape covers, positions are in floating point so a
+//! needle moves in fractions of a pixel instead of whole degree steps.
+//!
+//! The cropped and clipped targets the gauge draws through cannot be read back, so
+//! edges are blended towards a known `background`. These are meant for shapes on
+//! a plain fill, where that is exact.
+use core::{convert::Infallible, f32::consts::PI};
+
+use embedded_graphics::{
+    geometry::{Point, Size},
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, RgbColor},
+    primitives::{PointsIter, Rectangle},
+    Pixel,
+};
+use num_traits::Float;
+
+/// A position between pixel centres, pixel `(x, y)` is sampled at `(x, y)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointF {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl PointF {
+    pub const fn new(x: f32, y: f32) -> Self {
+        PointF { x, y }
+    }
+
+    /// The point `radius` away at `degrees`, clockwise from 3 o'clock like `Arc`.
+    pub fn polar(self, radius: f32, degrees: f32) -> PointF {
+        let (sin, cos) = Float::sin_cos(degrees * PI / 180.0);
+        PointF::new(self.x + radius * cos, self.y + radius * sin)
+    }
+
+    fn distance(self, other: PointF) -> f32 {
+        Float::hypot(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl From<Point> for PointF {
+    fn from(point: Point) -> Self {
+        PointF::new(point.x as f32, point.y as f32)
+    }
+}
+
+/// `color` over `background`, `coverage` of 0 is all background and 1 all colour.
+pub fn blend(background: Rgb565, color: Rgb565, coverage: f32) -> Rgb565 {
+    if coverage >= 1.0 {
+        return color;
+    }
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * coverage + 0.5) as u8;
+    Rgb565::new(
+        mix(background.r(), color.r()),
+        mix(background.g(), color.g()),
+        mix(background.b(), color.b()),
+    )
+}
+
+/// How much of a pixel lies inside an edge its centre is `inside` pixels in from.
+fn edge(inside: f32) -> f32 {
+    (inside + 0.5).clamp(0.0, 1.0)
+}
+
+/// The whole pixels around everything within `margin` of `a` and `b`.
+pub(crate) fn bounds(a: PointF, b: PointF, margin: f32) -> Rectangle {
+    let left = Float::floor(a.x.min(b.x) - margin) as i32;
+    let top = Float::floor(a.y.min(b.y) - margin) as i32;
+    let right = Float::ceil(a.x.max(b.x) + margin) as i32;
+    let bottom = Float::ceil(a.y.max(b.y) + margin) as i32;
+    Rectangle::new(Point::new(left, top), Size::new((right - left + 1) as u32, (bottom - top + 1) as u32))
+}
+
+/// Draws the pixels of `area` the target shows, with the colour share `coverage` gives for each.
+fn fill<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    target: &mut D,
+    area: Rectangle,
+    color: Rgb565,
+    background: Rgb565,
+    coverage: impl Fn(PointF) -> f32,
+) {
+    let area = area.intersection(&target.bounding_box());
+    let pixels = area.points().filter_map(|point| {
+        let coverage = coverage(point.into());
+        (coverage > 0.0).then(|| Pixel(point, blend(background, color, coverage)))
+    });
+    target.draw_iter(pixels).unwrap();
+}
+
+/// How far along `a`..`b` (0..=1) the point closest to `p` is, and its distance.
+fn segment(p: PointF, a: PointF, b: PointF) -> (f32, f32) {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = dx * dx + dy * dy;
+    let along = if length == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / length).clamp(0.0, 1.0)
+    };
+    let closest = PointF::new(a.x + along * dx, a.y + along * dy);
+    (along, p.distance(closest))
+}
+
+/// A line `width` pixels wide with round ends.
+pub fn line<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    target: &mut D,
+    from: PointF,
+    to: PointF,
+    width: f32,
+    color: Rgb565,
+    background: Rgb565,
+) {
+    let half = width / 2.0;
+    fill(target, bounds(from, to, half + 1.0), color, background, |p| {
+        edge(half - segment(p, from, to).1)
+    });
+}
+
+/// A needle narrowing from `base_width` at `base` to `tip_width` at `tip`, with round ends.
+pub fn tapered_line<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    target: &mut D,
+    base: PointF,
+    tip: PointF,
+    base_width: f32,
+    tip_width: f32,
+    color: Rgb565,
+    background: Rgb565,
+) {
+    let margin = base_width.max(tip_width) / 2.0 + 1.0;
+    fill(target, bounds(base, tip, margin), color, background, |p| {
+        let (along, distance) = segment(p, base, tip);
+        let half = (base_width + (tip_width - base_width) * along) / 2.0;
+        edge(half - distance)
+    });
+}
+
+/// A filled circle, like the hub a needle turns on.
+pub fn disc<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    target: &mut D,
+    centre: PointF,
+    radius: f32,
+    color: Rgb565,
+    background: Rgb565,
+) {
+    fill(target, bounds(centre, centre, radius + 1.0), color, background, |p| {
+        edge(radius - p.distance(centre))
+    });
+}
+
+/// A stroke `width` pixels wide along the circle of `radius`, from `start_degrees`
+/// clockwise over `sweep_degrees`, with square ends.
+#[allow(clippy::too_many_arguments)]
+pub fn arc<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    target: &mut D,
+    centre: PointF,
+    radius: f32,
+    width: f32,
+    start_degrees: f32,
+    sweep_degrees: f32,
+    color: Rgb565,
+    background: Rgb565,
+) {
+    let half = width / 2.0;
+    fill(target, bounds(centre, centre, radius + half + 1.0), color, background, |p| {
+        let distance = p.distance(centre);
+        let radial = edge(half - Float::abs(distance - radius));
+        if radial == 0.0 || sweep_degrees >= 360.0 {
+            return radial;
+        }
+        let degrees = Float::atan2(p.y - centre.y, p.x - centre.x) * 180.0 / PI;
+        let along = (degrees - start_degrees) % 360.0;
+        let along = if along < 0.0 { along + 360.0 } else { along };
+        // Degrees to the nearer end, negative outside the sweep
+        let inside = if along <= sweep_degrees {
+            along.min(sweep_degrees - along)
+        } else {
+            -(along - sweep_degrees).min(360.0 - along)
+        };
+        radial * edge(inside * PI / 180.0 * distance)
+    });
+}
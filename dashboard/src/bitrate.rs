@@ -0,0 +1,64 @@
+/// The bitrates found on car buses. OBD-II is 500k on most cars, comfort buses
+/// are often 125k or 250k.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitrate {
+    K125,
+    K250,
+    K500,
+    M1,
+}
+
+/// A listen-only window needs at least this many frames to count as a match,
+/// one frame could be a fluke on a noisy bus.
+pub const MIN_PROBE_FRAMES: usize = 3;
+const RECORD_MAGIC: u8 = 0xB7;
+
+impl Bitrate {
+    /// In the order auto-baud tries them, most common first.
+    pub const ALL: [Bitrate; 4] = [Bitrate::K500, Bitrate::K125, Bitrate::K250, Bitrate::M1];
+
+    pub fn kbps(self) -> u32 {
+        match self {
+            Bitrate::K125 => 125,
+            Bitrate::K250 => 250,
+            Bitrate::K500 => 500,
+            Bitrate::M1 => 1000,
+        }
+    }
+
+    pub fn from_kbps(kbps: u32) -> Option<Bitrate> {
+        Bitrate::ALL.into_iter().find(|bitrate| bitrate.kbps() == kbps)
+    }
+
+    /// Persisted form: a magic byte, the rate in units of 125k and its complement,
+    /// so erased (0xFF) or half written flash reads back as `None`.
+    pub fn to_record(self) -> [u8; 3] {
+        let code = (self.kbps() / 125) as u8;
+        [RECORD_MAGIC, code, !code]
+    }
+
+    pub fn from_record(record: &[u8]) -> Option<Bitrate> {
+        match record {
+            [RECORD_MAGIC, code, check, ..] if *check == !*code => Bitrate::from_kbps(*code as u32 * 125),
+            _ => None,
+        }
+    }
+}
+
+/// What one listen-only window at a candidate bitrate saw.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Probe {
+    pub frames: usize,
+    /// Receive errors reported by the driver during the window.
+    pub errors: usize,
+    pub error_count_before: u8,
+    pub error_count_after: u8,
+}
+
+impl Probe {
+    /// At the wrong bitrate the controller sees bit and form errors on every frame,
+    /// so a match is valid frames with the receive error counter not growing.
+    pub fn is_match(&self) -> bool {
+        self.frames >= MIN_PROBE_FRAMES && self.errors == 0 && self.error_count_after <= self.error_count_before
+    }
+}
@@ -0,0 +1,185 @@
+//! How the CAN bus is doing: traffic, errors and the controller's error state, and
+//! when to try getting back on the bus after a bus-off.
+
+use embedded_can::Frame;
+
+use crate::bitrate::Bitrate;
+use crate::scheduler::frame_bits;
+
+/// `BusHealth` is worked out over windows this long.
+pub const HEALTH_WINDOW_MS: u64 = 1000;
+/// The first recovery is tried this long after going bus-off, doubling each time after that.
+pub const FIRST_RECOVERY_MS: u64 = 100;
+pub const MAX_RECOVERY_MS: u64 = 5000;
+/// Back to the quick first retry once the bus stayed up this long.
+pub const STABLE_MS: u64 = 10_000;
+
+/// Fault confinement state, from the error counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorState {
+    #[default]
+    Active,
+    /// An error counter went past 127: the controller still works, but no longer
+    /// sends active error frames.
+    Passive,
+    /// The transmit counter went past 255 and the controller left the bus.
+    BusOff,
+}
+
+impl ErrorState {
+    pub fn from_counters(tx_errors: u8, rx_errors: u8, bus_off: bool) -> Self {
+        if bus_off {
+            ErrorState::BusOff
+        } else if tx_errors >= 128 || rx_errors >= 128 {
+            ErrorState::Passive
+        } else {
+            ErrorState::Active
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorState::Active => "OK",
+            ErrorState::Passive => "passive",
+            ErrorState::BusOff => "BUS OFF",
+        }
+    }
+}
+
+/// What the diagnostics page shows about the bus. The traffic figures only count what
+/// the controller hands over: frames that passed the acceptance filter and those sent.
+/// Everything the filter keeps out is invisible, so with a filter set they are the
+/// dashboard's share of the bus, not the whole of it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BusHealth {
+    /// Accepted and sent over the last window.
+    pub frames_per_s: f32,
+    /// Errors as a percentage of accepted frames and errors together. Errors are seen
+    /// on all traffic, so a filter makes this look worse than it is.
+    pub error_rate: f32,
+    /// Percentage of the bus bandwidth taken by accepted and sent frames.
+    pub bus_load: f32,
+    pub tx_errors: u8,
+    pub rx_errors: u8,
+    pub state: ErrorState,
+    /// Since power on.
+    pub bus_off_count: u32,
+    pub arbitration_lost: u32,
+}
+
+/// Sums up the traffic and errors reported by the receive and transmit tasks.
+#[derive(Debug, Clone)]
+pub struct BusMonitor {
+    bitrate: Bitrate,
+    window_start_ms: Option<u64>,
+    frames: u32,
+    bits: u64,
+    errors: u32,
+    health: BusHealth,
+}
+
+impl BusMonitor {
+    pub fn new(bitrate: Bitrate) -> Self {
+        BusMonitor { bitrate, window_start_ms: None, frames: 0, bits: 0, errors: 0, health: BusHealth::default() }
+    }
+
+    /// A frame received, that is one the acceptance filter let through.
+    pub fn on_frame<F: Frame>(&mut self, frame: &F) {
+        self.frames += 1;
+        self.bits += frame_bits(frame) as u64;
+    }
+
+    /// Frames sent, counted by the transmit task with `frame_bits`.
+    pub fn on_sent(&mut self, frames: u32, bits: u32) {
+        self.frames += frames;
+        self.bits += bits as u64;
+    }
+
+    /// A receive or transmit error.
+    pub fn on_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn on_arbitration_lost(&mut self, count: u32) {
+        self.health.arbitration_lost += count;
+    }
+
+    /// Takes the controller's counters, returns the new state when it changed.
+    pub fn on_counters(&mut self, tx_errors: u8, rx_errors: u8, bus_off: bool) -> Option<ErrorState> {
+        self.health.tx_errors = tx_errors;
+        self.health.rx_errors = rx_errors;
+        let state = ErrorState::from_counters(tx_errors, rx_errors, bus_off);
+        if state == self.health.state {
+            return None;
+        }
+        if state == ErrorState::BusOff {
+            self.health.bus_off_count += 1;
+        }
+        self.health.state = state;
+        Some(state)
+    }
+
+    /// Closes the window once it is `HEALTH_WINDOW_MS` long and returns the new figures.
+    pub fn update(&mut self, now_ms: u64) -> Option<BusHealth> {
+        let elapsed = now_ms.saturating_sub(*self.window_start_ms.get_or_insert(now_ms));
+        if elapsed < HEALTH_WINDOW_MS {
+            return None;
+        }
+        let seconds = elapsed as f32 / 1000.0;
+        self.health.frames_per_s = self.frames as f32 / seconds;
+        let attempts = self.frames + self.errors;
+        self.health.error_rate = if attempts == 0 { 0.0 } else { self.errors as f32 * 100.0 / attempts as f32 };
+        self.health.bus_load = self.bits as f32 / (self.bitrate.kbps() as f32 * 10.0 * seconds);
+        self.window_start_ms = Some(now_ms);
+        self.frames = 0;
+        self.bits = 0;
+        self.errors = 0;
+        Some(self.health)
+    }
+
+    pub fn health(&self) -> BusHealth {
+        self.health
+    }
+}
+
+/// Paces the attempts to get back on the bus. A car with a real fault would otherwise
+/// see the dashboard drop off and rejoin in a tight loop.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// Attempts since the bus was last stable.
+    attempts: u32,
+    retry_at_ms: Option<u64>,
+    up_since_ms: Option<u64>,
+}
+
+impl Recovery {
+    /// Call it regularly with the current error state, returns true when a recovery should start now.
+    pub fn poll(&mut self, state: ErrorState, now_ms: u64) -> bool {
+        if state != ErrorState::BusOff {
+            self.retry_at_ms = None;
+            let up_since = *self.up_since_ms.get_or_insert(now_ms);
+            if now_ms.saturating_sub(up_since) >= STABLE_MS {
+                self.attempts = 0;
+            }
+            return false;
+        }
+        self.up_since_ms = None;
+        let delay = self.delay_ms();
+        let retry_at = *self.retry_at_ms.get_or_insert(now_ms + delay);
+        if now_ms < retry_at {
+            return false;
+        }
+        self.attempts += 1;
+        self.retry_at_ms = None;
+        true
+    }
+
+    /// How long the next attempt waits.
+    pub fn delay_ms(&self) -> u64 {
+        (FIRST_RECOVERY_MS << self.attempts.min(16)).min(MAX_RECOVERY_MS)
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
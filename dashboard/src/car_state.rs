@@ -0,0 +1,360 @@
+use embedded_can::{Frame, Id::Extended};
+use heapless::String;
+use log::{info, warn};
+
+use crate::bus_health::BusHealth;
+use crate::dbc;
+use crate::dtc::{self, ClearConfirmation, DtcList, DtcReply};
+use crate::freshness::{Freshness, Stamp, MIN_LOST_MS};
+use crate::isotp::IsoTpMessage;
+use crate::ring::RingStats;
+use crate::source::CanEvent;
+use crate::obd::{self, ObdClient, Pid, PidReading, VIN_LEN};
+use crate::trip::{self, Sample, TripComputer, TripId};
+
+/// The values `CarState` can be fed with, both from OBD-II replies and from
+/// broadcast signals bound in the DBC (`engine_rpm` there is `CarField::EngineRpm`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarField {
+    EngineRpm,
+    VehicleSpeed,
+    CoolantTemp,
+    ThrottlePosition,
+    MafAirFlow,
+    IntakeAirTemp,
+    FuelLevel,
+    FuelRate,
+    // Lamps and warnings, 1 is on
+    TurnLeft,
+    TurnRight,
+    LowBeam,
+    HighBeam,
+    OilPressureWarning,
+    ChargeWarning,
+    CheckEngine,
+    AbsWarning,
+    SeatbeltWarning,
+}
+
+const FIELD_COUNT: usize = CarField::SeatbeltWarning as usize + 1;
+
+fn pid_field(pid: Pid) -> CarField {
+    match pid {
+        Pid::EngineRpm => CarField::EngineRpm,
+        Pid::VehicleSpeed => CarField::VehicleSpeed,
+        Pid::CoolantTemp => CarField::CoolantTemp,
+        Pid::ThrottlePosition => CarField::ThrottlePosition,
+        Pid::MafAirFlow => CarField::MafAirFlow,
+        Pid::IntakeAirTemp => CarField::IntakeAirTemp,
+        Pid::FuelLevel => CarField::FuelLevel,
+        Pid::FuelRate => CarField::FuelRate,
+    }
+}
+
+#[derive(Debug,Default,Clone)]
+pub struct CarState {
+    message_count: usize,
+    avg_voltage: f32,
+    obd: ObdClient,
+    engine_rpm: Option<f32>,
+    vehicle_speed: Option<f32>,
+    coolant_temp: Option<f32>,
+    throttle_position: Option<f32>,
+    maf_air_flow: Option<f32>,
+    intake_air_temp: Option<f32>,
+    fuel_level: Option<f32>,
+    fuel_rate: Option<f32>,
+    turn_left: Option<f32>,
+    turn_right: Option<f32>,
+    low_beam: Option<f32>,
+    high_beam: Option<f32>,
+    oil_pressure_warning: Option<f32>,
+    charge_warning: Option<f32>,
+    check_engine: Option<f32>,
+    abs_warning: Option<f32>,
+    seatbelt_warning: Option<f32>,
+    vin: Option<String<VIN_LEN>>,
+    trips: TripComputer,
+    dtcs: DtcList,
+    dtc_clear: ClearConfirmation,
+    bus_health: BusHealth,
+    ring_stats: RingStats,
+    render_fps: Option<u32>,
+    /// What updates from the bus are stamped with.
+    now_ms: u64,
+    /// Per `CarField`, `None` for values without an expected period.
+    stamps: [Option<Stamp>; FIELD_COUNT],
+    /// Per `CarField`, when it was first asked for over OBD-II.
+    requested_ms: [Option<u64>; FIELD_COUNT],
+}
+
+impl CarState {
+    pub fn process_event<F: Frame>(&mut self, event: CanEvent<F>) {
+        match event {
+            CanEvent::Frame(frame) => self.process_message(frame),
+            CanEvent::Message(message) => self.process_isotp(&message),
+        }
+    }
+
+    pub fn process_message<F: Frame>(&mut self, frame: F) {
+        if let Some(entry) = dbc::lookup(frame.id()) {
+            for binding in entry.bindings {
+                if let Some(value) = entry.message.decode(binding.signal, frame.data()) {
+                    self.update(binding.field, value, entry.message.signal_period_ms(binding.signal));
+                }
+            }
+        } else if let Extended(_) = frame.id() {
+            info!("Extended frame found!");
+        }
+        self.message_count+=1
+    }
+
+    /// Handle a reassembled ISO-TP message, only OBD-II replies are understood for now.
+    pub fn process_isotp(&mut self, message: &IsoTpMessage) {
+        let Some(ecu) = obd::response_ecu(message.id) else {
+            return;
+        };
+        if let Some(reading) = obd::parse_payload(ecu, &message.data) {
+            self.apply_reading(&reading);
+        } else if let Some(vin) = obd::parse_vin(&message.data) {
+            info!("VIN: {}", vin);
+            self.vin = Some(vin);
+        } else if let Some(reply) = dtc::parse_reply(&message.data) {
+            self.apply_dtc_reply(reply);
+        }
+    }
+
+    fn apply_reading(&mut self, reading: &PidReading) {
+        self.obd.on_reading(reading);
+        self.update(pid_field(reading.pid), reading.value, Some(reading.pid.period_ms()));
+    }
+
+    fn apply_dtc_reply(&mut self, reply: DtcReply) {
+        match reply {
+            DtcReply::Codes(kind, codes) => self.dtcs.add(kind, &codes),
+            DtcReply::Cleared => {
+                info!("Trouble codes cleared");
+                self.read_dtcs();
+            }
+            DtcReply::ClearRejected(code) => warn!("Clearing trouble codes refused, NRC {:#04x}", code),
+        }
+    }
+
+    /// The time values from the bus are stamped with, set it before handing over events.
+    pub fn set_time(&mut self, now_ms: u64) {
+        self.now_ms = now_ms;
+    }
+
+    fn update(&mut self, field: CarField, value: f32, period_ms: Option<u64>) {
+        self.set(field, value);
+        self.stamps[field as usize] = period_ms.map(|period_ms| Stamp { updated_ms: self.now_ms, period_ms });
+    }
+
+    /// Values set here have no expected period, they never go stale.
+    pub fn set(&mut self, field: CarField, value: f32) {
+        self.stamps[field as usize] = None;
+        let value = Some(value);
+        match field {
+            CarField::EngineRpm => self.engine_rpm = value,
+            CarField::VehicleSpeed => self.vehicle_speed = value,
+            CarField::CoolantTemp => self.coolant_temp = value,
+            CarField::ThrottlePosition => self.throttle_position = value,
+            CarField::MafAirFlow => self.maf_air_flow = value,
+            CarField::IntakeAirTemp => self.intake_air_temp = value,
+            CarField::FuelLevel => self.fuel_level = value,
+            CarField::FuelRate => self.fuel_rate = value,
+            CarField::TurnLeft => self.turn_left = value,
+            CarField::TurnRight => self.turn_right = value,
+            CarField::LowBeam => self.low_beam = value,
+            CarField::HighBeam => self.high_beam = value,
+            CarField::OilPressureWarning => self.oil_pressure_warning = value,
+            CarField::ChargeWarning => self.charge_warning = value,
+            CarField::CheckEngine => self.check_engine = value,
+            CarField::AbsWarning => self.abs_warning = value,
+            CarField::SeatbeltWarning => self.seatbelt_warning = value,
+        }
+    }
+
+    pub fn get(&self, field: CarField) -> Option<f32> {
+        match field {
+            CarField::EngineRpm => self.engine_rpm,
+            CarField::VehicleSpeed => self.vehicle_speed,
+            CarField::CoolantTemp => self.coolant_temp,
+            CarField::ThrottlePosition => self.throttle_position,
+            CarField::MafAirFlow => self.maf_air_flow,
+            CarField::IntakeAirTemp => self.intake_air_temp,
+            CarField::FuelLevel => self.fuel_level,
+            CarField::FuelRate => self.fuel_rate,
+            CarField::TurnLeft => self.turn_left,
+            CarField::TurnRight => self.turn_right,
+            CarField::LowBeam => self.low_beam,
+            CarField::HighBeam => self.high_beam,
+            CarField::OilPressureWarning => self.oil_pressure_warning,
+            CarField::ChargeWarning => self.charge_warning,
+            CarField::CheckEngine => self.check_engine,
+            CarField::AbsWarning => self.abs_warning,
+            CarField::SeatbeltWarning => self.seatbelt_warning,
+        }
+    }
+
+    /// Whether `field` still comes as often as it should. A field that is expected but
+    /// never came is lost `MIN_LOST_MS` after it should have started: from start-up for
+    /// broadcast signals with a cycle time, from the first request for OBD-II readings.
+    /// Values set by hand never go stale.
+    pub fn freshness(&self, field: CarField, now_ms: u64) -> Freshness {
+        if let Some(stamp) = self.stamps[field as usize] {
+            return stamp.freshness(now_ms);
+        }
+        // The clock starts at boot
+        let expected_ms = if dbc::is_broadcast(field) { Some(0) } else { self.requested_ms[field as usize] };
+        match expected_ms {
+            Some(since) if self.get(field).is_none() && now_ms.saturating_sub(since) > MIN_LOST_MS => Freshness::Lost,
+            _ => Freshness::Fresh,
+        }
+    }
+
+    /// Next OBD-II request to put on the bus, if one is due.
+    pub fn next_obd_request<F: Frame>(&mut self, now_ms: u64) -> Option<F> {
+        let request = self.obd.poll(now_ms);
+        if request.is_some()
+            && let Some(pid) = self.obd.outstanding()
+        {
+            self.requested_ms[pid_field(pid) as usize].get_or_insert(now_ms);
+        }
+        request
+    }
+
+    pub fn message_count(&self)->usize {
+        self.message_count
+    }
+
+    pub fn voltage(&self)->f32 {
+        self.avg_voltage
+    }
+
+    pub fn set_voltage(&mut self, value: f32) {
+        self.avg_voltage = value;
+    }
+
+    /// Updated once a second by the receive task, stays at the default while replaying.
+    pub fn bus_health(&self) -> &BusHealth {
+        &self.bus_health
+    }
+
+    pub fn set_bus_health(&mut self, health: BusHealth) {
+        self.bus_health = health;
+    }
+
+    /// How the ring between the receive task and this state kept up, frames counted
+    /// as dropped never got here.
+    pub fn ring_stats(&self) -> &RingStats {
+        &self.ring_stats
+    }
+
+    pub fn set_ring_stats(&mut self, stats: RingStats) {
+        self.ring_stats = stats;
+    }
+
+    /// Frames a second that went out to the display, the render loop measures it.
+    pub fn render_fps(&self) -> Option<u32> {
+        self.render_fps
+    }
+
+    pub fn set_render_fps(&mut self, fps: u32) {
+        self.render_fps = Some(fps);
+    }
+
+    /// Engine speed in rpm
+    pub fn engine_rpm(&self) -> Option<f32> {
+        self.engine_rpm
+    }
+
+    /// Vehicle speed in km/h
+    pub fn vehicle_speed(&self) -> Option<f32> {
+        self.vehicle_speed
+    }
+
+    /// Engine coolant temperature in °C
+    pub fn coolant_temp(&self) -> Option<f32> {
+        self.coolant_temp
+    }
+
+    /// Absolute throttle position in percent
+    pub fn throttle_position(&self) -> Option<f32> {
+        self.throttle_position
+    }
+
+    /// Mass air flow in g/s
+    pub fn maf_air_flow(&self) -> Option<f32> {
+        self.maf_air_flow
+    }
+
+    /// Intake air temperature in °C
+    pub fn intake_air_temp(&self) -> Option<f32> {
+        self.intake_air_temp
+    }
+
+    /// Fuel tank level in percent
+    pub fn fuel_level(&self) -> Option<f32> {
+        self.fuel_level
+    }
+
+    /// Fuel flow in l/h, estimated from the air flow when the car does not send it
+    pub fn fuel_rate(&self) -> Option<f32> {
+        self.fuel_rate.or(self.maf_air_flow.map(trip::fuel_rate_from_maf))
+    }
+
+    /// Feeds the trip counters with the current speed and fuel flow, call it often.
+    /// Only fresh values count, a speed that stopped coming is not driven on.
+    pub fn update_trips(&mut self, now_ms: u64) {
+        let fresh = |field| self.get(field).filter(|_| self.freshness(field, now_ms) == Freshness::Fresh);
+        let fuel_rate = fresh(CarField::FuelRate).or(fresh(CarField::MafAirFlow).map(trip::fuel_rate_from_maf));
+        let sample = Sample { at_ms: now_ms, speed: fresh(CarField::VehicleSpeed), fuel_rate };
+        self.trips.add_sample(sample);
+    }
+
+    pub fn trips(&self) -> &TripComputer {
+        &self.trips
+    }
+
+    pub fn trips_mut(&mut self) -> &mut TripComputer {
+        &mut self.trips
+    }
+
+    pub fn reset_trip(&mut self, id: TripId) {
+        info!("{} reset", id.name());
+        self.trips.reset(id);
+    }
+
+    /// Asks every ECU for its trouble codes again, the list fills as they answer.
+    pub fn read_dtcs(&mut self) {
+        self.dtcs = DtcList::default();
+        self.obd.read_dtcs();
+    }
+
+    pub fn dtcs(&self) -> &DtcList {
+        &self.dtcs
+    }
+
+    /// One half of the clear gesture, the second press within `dtc::CLEAR_CONFIRM_MS`
+    /// sends Mode 04. Returns whether it did.
+    pub fn confirm_clear_dtcs(&mut self, now_ms: u64) -> bool {
+        if !self.dtc_clear.press(now_ms) {
+            info!("Hold again to clear the trouble codes");
+            return false;
+        }
+        info!("Clearing trouble codes");
+        self.obd.clear_dtcs();
+        true
+    }
+
+    /// Waiting for the second press.
+    pub fn dtc_clear_armed(&self, now_ms: u64) -> bool {
+        self.dtc_clear.armed(now_ms)
+    }
+
+    /// Vehicle identification number, once an ECU answered the Mode 09 request
+    pub fn vin(&self) -> Option<&str> {
+        self.vin.as_deref()
+    }
+}
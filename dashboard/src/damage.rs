@@ -0,0 +1,161 @@
+//! Damage tracking, so only the parts of the framebuffer that were drawn on get
+//! sent to the display.
+use embedded_graphics::{
+    geometry::{Dimensions, Point},
+    pixelcolor::PixelColor,
+    prelude::DrawTarget,
+    primitives::Rectangle,
+    Pixel,
+};
+
+/// More separate rectangles than this get merged, every window costs a few
+/// command bytes on the SPI bus.
+pub const MAX_RECTS: usize = 8;
+
+/// The smallest rectangle containing both.
+pub fn union(a: &Rectangle, b: &Rectangle) -> Rectangle {
+    let (Some(a_end), Some(b_end)) = (a.bottom_right(), b.bottom_right()) else {
+        return if a.is_zero_sized() { *b } else { *a };
+    };
+    Rectangle::with_corners(
+        Point::new(a.top_left.x.min(b.top_left.x), a.top_left.y.min(b.top_left.y)),
+        Point::new(a_end.x.max(b_end.x), a_end.y.max(b_end.y)),
+    )
+}
+
+fn area(rect: &Rectangle) -> u32 {
+    rect.size.width * rect.size.height
+}
+
+/// The regions of a frame that changed, as a short list of rectangles.
+#[derive(Debug, Clone, Default)]
+pub struct Damage {
+    rects: heapless::Vec<Rectangle, MAX_RECTS>,
+}
+
+impl Damage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, rect: Rectangle) {
+        if rect.is_zero_sized() {
+            return;
+        }
+        let mut rect = rect;
+        // Fold in every rectangle that overlaps, or is close enough that one window
+        // costs no more pixels than two
+        while let Some(index) = self.rects.iter().position(|other| {
+            let merged = union(&rect, other);
+            !rect.intersection(other).is_zero_sized() || area(&merged) <= area(&rect) + area(other)
+        }) {
+            rect = union(&rect, &self.rects.swap_remove(index));
+        }
+        if let Err(rect) = self.rects.push(rect) {
+            // Full: merge with whichever rectangle grows the least
+            let index = (0..self.rects.len())
+                .min_by_key(|index| area(&union(&rect, &self.rects[*index])))
+                .unwrap();
+            let merged = union(&rect, &self.rects.swap_remove(index));
+            self.add(merged);
+        }
+    }
+
+    pub fn rects(&self) -> &[Rectangle] {
+        &self.rects
+    }
+
+    /// Pixels covered, the rectangles never overlap.
+    pub fn area(&self) -> u32 {
+        self.rects.iter().map(area).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+}
+
+/// Draws into `target` and records every touched region in `damage`.
+pub struct Tracked<'a, D> {
+    target: &'a mut D,
+    damage: &'a mut Damage,
+}
+
+impl<'a, D: DrawTarget> Tracked<'a, D> {
+    pub fn new(target: &'a mut D, damage: &'a mut Damage) -> Self {
+        Tracked { target, damage }
+    }
+}
+
+impl<D: DrawTarget> Dimensions for Tracked<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<C: PixelColor, D: DrawTarget<Color = C>> DrawTarget for Tracked<'_, D> {
+    type Color = C;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.target.bounding_box();
+        let mut min = Point::new(i32::MAX, i32::MAX);
+        let mut max = Point::new(i32::MIN, i32::MIN);
+        let result = self.target.draw_iter(pixels.into_iter().inspect(|Pixel(point, _)| {
+            min = min.component_min(*point);
+            max = max.component_max(*point);
+        }));
+        if min.x <= max.x {
+            self.damage.add(Rectangle::with_corners(min, max).intersection(&bounds));
+        }
+        result
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.damage.add(area.intersection(&self.target.bounding_box()));
+        self.target.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.damage.add(area.intersection(&self.target.bounding_box()));
+        self.target.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.damage.add(self.target.bounding_box());
+        self.target.clear(color)
+    }
+}
+
+/// The pixels of `area` out of a row-major buffer `width` pixels wide, in the
+/// order a display window expects them.
+pub fn region<C: Copy>(data: &[C], width: usize, area: Rectangle) -> impl Iterator<Item = C> + '_ {
+    let columns = area.columns();
+    area.rows().flat_map(move |y| {
+        let start = y as usize * width;
+        data[start + columns.start as usize..start + columns.end as usize].iter().copied()
+    })
+}
+
+/// Copies `areas` from one row-major buffer to another, to bring a stale buffer of a
+/// double-buffered pair up to date with the frame that was just drawn.
+pub fn copy_regions<C: Copy>(from: &[C], to: &mut [C], width: usize, areas: &[Rectangle]) {
+    for area in areas {
+        let columns = area.columns();
+        for y in area.rows() {
+            let start = y as usize * width;
+            let row = start + columns.start as usize..start + columns.end as usize;
+            to[row.clone()].copy_from_slice(&from[row]);
+        }
+    }
+}
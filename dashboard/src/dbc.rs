@@ -0,0 +1,174 @@
+//! Signal decoding driven by the DBC file.
+//!
+//! `build.rs` parses the DBC (`dbc/vehicle.dbc`, or whatever `CAN_DBC` points at)
+//! and generates the message and signal tables below, plus the table telling
+//! `CarState` which signal feeds which of its fields. Supporting another car is a
+//! matter of swapping the DBC.
+use alloc::vec::Vec;
+use embedded_can::Id;
+
+use crate::car_state::CarField;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Intel, `@1` in the DBC: the start bit is the least significant bit.
+    LittleEndian,
+    /// Motorola, `@0` in the DBC: the start bit is the most significant bit.
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplex {
+    None,
+    /// This signal selects which multiplexed signals are present.
+    Multiplexor,
+    /// Only present when the multiplexor holds this value.
+    Multiplexed(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalDef {
+    pub name: &'static str,
+    pub start_bit: u16,
+    pub length: u8,
+    pub byte_order: ByteOrder,
+    pub signed: bool,
+    pub factor: f32,
+    pub offset: f32,
+    pub min: f32,
+    pub max: f32,
+    pub unit: &'static str,
+    pub multiplex: Multiplex,
+}
+
+impl SignalDef {
+    /// The raw, unscaled bits of the signal. `None` when the frame is too short.
+    pub fn raw(&self, data: &[u8]) -> Option<u64> {
+        let mut raw: u64 = 0;
+        let mut bit = self.start_bit as usize;
+        for i in 0..self.length as usize {
+            let value = (*data.get(bit / 8)? >> (bit % 8)) & 1;
+            match self.byte_order {
+                ByteOrder::LittleEndian => {
+                    raw |= (value as u64) << i;
+                    bit += 1;
+                }
+                ByteOrder::BigEndian => {
+                    raw = (raw << 1) | value as u64;
+                    // Motorola bits run from 7 down to 0, then continue at bit 7 of the next byte
+                    bit = if bit.is_multiple_of(8) { bit + 15 } else { bit - 1 };
+                }
+            }
+        }
+        Some(raw)
+    }
+
+    /// The physical value: sign extended, scaled and clamped to the DBC range.
+    pub fn decode(&self, data: &[u8]) -> Option<f32> {
+        let raw = self.raw(data)?;
+        let value = if self.signed && self.length < 64 && (raw >> (self.length - 1)) & 1 == 1 {
+            (raw | (u64::MAX << self.length)) as i64 as f32
+        } else if self.signed {
+            raw as i64 as f32
+        } else {
+            raw as f32
+        };
+        let value = value * self.factor + self.offset;
+        // Some DBCs leave the range at [0|0], meaning unbounded
+        if self.min < self.max {
+            Some(value.clamp(self.min, self.max))
+        } else {
+            Some(value)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageDef {
+    pub id: u32,
+    pub extended: bool,
+    pub name: &'static str,
+    pub dlc: u8,
+    /// How often the message is sent, `GenMsgCycleTime` in the DBC.
+    pub cycle_ms: Option<u32>,
+    pub signals: &'static [SignalDef],
+}
+
+impl MessageDef {
+    pub fn matches(&self, id: Id) -> bool {
+        key(id) == (self.extended, self.id)
+    }
+
+    fn multiplexor(&self) -> Option<&'static SignalDef> {
+        self.signals
+            .iter()
+            .find(|signal| signal.multiplex == Multiplex::Multiplexor)
+    }
+
+    /// Decode one of this message's signals, `None` if a multiplexed signal is not in this frame.
+    pub fn decode(&self, signal: &SignalDef, data: &[u8]) -> Option<f32> {
+        if let Multiplex::Multiplexed(value) = signal.multiplex {
+            let selected = self.multiplexor()?.raw(data)?;
+            if selected != value as u64 {
+                return None;
+            }
+        }
+        signal.decode(data)
+    }
+
+    /// How often `signal` comes by: a multiplexed signal only every so many messages.
+    pub fn signal_period_ms(&self, signal: &SignalDef) -> Option<u64> {
+        let cycle_ms = self.cycle_ms? as u64;
+        let Multiplex::Multiplexed(_) = signal.multiplex else {
+            return Some(cycle_ms);
+        };
+        let mut values = Vec::new();
+        for other in self.signals {
+            if let Multiplex::Multiplexed(value) = other.multiplex
+                && !values.contains(&value)
+            {
+                values.push(value);
+            }
+        }
+        Some(cycle_ms * values.len() as u64)
+    }
+}
+
+/// A signal that feeds a `CarState` field.
+#[derive(Debug, Clone, Copy)]
+pub struct SignalBinding {
+    pub signal: &'static SignalDef,
+    pub field: CarField,
+}
+
+/// All `CarState` updates carried by one message.
+#[derive(Debug, Clone, Copy)]
+pub struct UpdateEntry {
+    pub message: &'static MessageDef,
+    pub bindings: &'static [SignalBinding],
+}
+
+fn key(id: Id) -> (bool, u32) {
+    match id {
+        Id::Standard(id) => (false, id.as_raw() as u32),
+        Id::Extended(id) => (true, id.as_raw()),
+    }
+}
+
+/// Find the update table entry for a CAN ID, the table is sorted so this is a binary search.
+pub fn lookup(id: Id) -> Option<&'static UpdateEntry> {
+    let key = key(id);
+    CAR_STATE_UPDATES
+        .binary_search_by_key(&key, |entry| (entry.message.extended, entry.message.id))
+        .ok()
+        .map(|index| &CAR_STATE_UPDATES[index])
+}
+
+/// Whether `field` is bound to a signal in a message with a cycle time, so it should keep coming.
+pub fn is_broadcast(field: CarField) -> bool {
+    CAR_STATE_UPDATES.iter().any(|entry| {
+        entry.message.cycle_ms.is_some() && entry.bindings.iter().any(|binding| binding.field == field)
+    })
+}
+
+include!(concat!(env!("OUT_DIR"), "/dbc.rs"));
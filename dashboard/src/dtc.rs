@@ -0,0 +1,225 @@
+//! Diagnostic trouble codes: reading them with OBD-II Modes 03, 07 and 0A,
+//! clearing them with Mode 04.
+
+use core::fmt;
+
+use embedded_can::{Frame, StandardId};
+use heapless::Vec;
+
+use crate::isotp;
+use crate::obd::FUNCTIONAL_REQUEST_ID;
+
+const MODE_CLEAR: u8 = 0x04;
+const POSITIVE_RESPONSE: u8 = 0x40;
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+/// More than fit on the page, the rest is only counted.
+pub const MAX_DTCS: usize = 16;
+/// A second long press has to come within this long of the first to clear the codes.
+pub const CLEAR_CONFIRM_MS: u64 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcKind {
+    /// Confirmed, these light the check engine lamp.
+    Stored,
+    /// Seen once, not confirmed yet.
+    Pending,
+    /// Only the ECU clears these, after the fault is gone for a few drive cycles.
+    Permanent,
+}
+
+impl DtcKind {
+    pub const ALL: [DtcKind; 3] = [DtcKind::Stored, DtcKind::Pending, DtcKind::Permanent];
+
+    pub fn mode(self) -> u8 {
+        match self {
+            DtcKind::Stored => 0x03,
+            DtcKind::Pending => 0x07,
+            DtcKind::Permanent => 0x0A,
+        }
+    }
+
+    fn from_mode(mode: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.mode() == mode)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DtcKind::Stored => "stored",
+            DtcKind::Pending => "pending",
+            DtcKind::Permanent => "perm.",
+        }
+    }
+}
+
+/// A trouble code as the two bytes it is sent in. The top two bits pick the system
+/// (powertrain, chassis, body, network), the rest are four digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dtc(pub u16);
+
+impl Dtc {
+    pub fn system(self) -> char {
+        ['P', 'C', 'B', 'U'][(self.0 >> 14) as usize]
+    }
+
+    /// What the code means, for the generic codes in `DESCRIPTIONS`.
+    pub fn description(self) -> Option<&'static str> {
+        let mut code = heapless::String::<5>::new();
+        fmt::write(&mut code, format_args!("{}", self)).ok()?;
+        DESCRIPTIONS.iter().find(|(known, _)| *known == code).map(|(_, description)| *description)
+    }
+}
+
+impl fmt::Display for Dtc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{:03X}", self.system(), (self.0 >> 12) & 0x3, self.0 & 0x0FFF)
+    }
+}
+
+/// The common SAE generic codes, short enough for one line on the page.
+/// Manufacturer codes (P1xxx and the like) differ per make and are shown without one.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("P0087", "Fuel rail pressure low"),
+    ("P0101", "MAF range/performance"),
+    ("P0102", "MAF circuit low"),
+    ("P0113", "Intake air temp high"),
+    ("P0117", "Coolant temp low input"),
+    ("P0118", "Coolant temp high input"),
+    ("P0128", "Thermostat below temp"),
+    ("P0130", "O2 sensor B1S1 circuit"),
+    ("P0135", "O2 heater B1S1 circuit"),
+    ("P0141", "O2 heater B1S2 circuit"),
+    ("P0171", "System too lean bank 1"),
+    ("P0172", "System too rich bank 1"),
+    ("P0174", "System too lean bank 2"),
+    ("P0175", "System too rich bank 2"),
+    ("P0300", "Random misfire"),
+    ("P0301", "Cylinder 1 misfire"),
+    ("P0302", "Cylinder 2 misfire"),
+    ("P0303", "Cylinder 3 misfire"),
+    ("P0304", "Cylinder 4 misfire"),
+    ("P0305", "Cylinder 5 misfire"),
+    ("P0306", "Cylinder 6 misfire"),
+    ("P0325", "Knock sensor 1 circuit"),
+    ("P0335", "Crankshaft sensor circuit"),
+    ("P0340", "Camshaft sensor circuit"),
+    ("P0401", "EGR flow insufficient"),
+    ("P0420", "Catalyst bank 1 low eff."),
+    ("P0430", "Catalyst bank 2 low eff."),
+    ("P0440", "EVAP system malfunction"),
+    ("P0442", "EVAP small leak"),
+    ("P0455", "EVAP large leak"),
+    ("P0456", "EVAP very small leak"),
+    ("P0500", "Vehicle speed sensor"),
+    ("P0505", "Idle control system"),
+    ("P0562", "System voltage low"),
+    ("P0563", "System voltage high"),
+    ("P0700", "Transmission fault"),
+    ("C0035", "Left front wheel speed"),
+    ("C0040", "Right front wheel speed"),
+    ("U0100", "Lost comm with ECM/PCM"),
+    ("U0121", "Lost comm with ABS"),
+    ("U0140", "Lost comm with BCM"),
+];
+
+/// What an ECU answered to a Mode 03/07/0A/04 request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DtcReply {
+    Codes(DtcKind, Vec<Dtc, MAX_DTCS>),
+    Cleared,
+    /// The negative response code, 0x22 when the engine is running.
+    ClearRejected(u8),
+}
+
+/// Parse a reply payload (mode byte onwards). On CAN a count byte follows the mode,
+/// older ECUs leave it out, so it is only skipped when the rest has an odd length.
+pub fn parse_reply(payload: &[u8]) -> Option<DtcReply> {
+    match payload {
+        [mode] if *mode == MODE_CLEAR + POSITIVE_RESPONSE => Some(DtcReply::Cleared),
+        [NEGATIVE_RESPONSE, MODE_CLEAR, code, ..] => Some(DtcReply::ClearRejected(*code)),
+        [mode, rest @ ..] if *mode >= POSITIVE_RESPONSE => {
+            let kind = DtcKind::from_mode(mode - POSITIVE_RESPONSE)?;
+            let pairs = if rest.len() % 2 == 1 { &rest[1..] } else { rest };
+            // 0x0000 pads the reply up to a whole number of codes
+            let codes = pairs
+                .chunks_exact(2)
+                .map(|pair| Dtc(u16::from_be_bytes([pair[0], pair[1]])))
+                .filter(|dtc| dtc.0 != 0)
+                .take(MAX_DTCS)
+                .collect();
+            Some(DtcReply::Codes(kind, codes))
+        }
+        _ => None,
+    }
+}
+
+/// A request for the OBD-II poller to put on the bus, between its PID requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcRequest {
+    Read(DtcKind),
+    /// Mode 04: clears the stored and pending codes and the freeze frames, and turns
+    /// the check engine lamp off. Readiness monitors reset too.
+    Clear,
+}
+
+impl DtcRequest {
+    /// Functional, every ECU answers.
+    pub fn frame<F: Frame>(self) -> Option<F> {
+        let mode = match self {
+            DtcRequest::Read(kind) => kind.mode(),
+            DtcRequest::Clear => MODE_CLEAR,
+        };
+        isotp::single_frame(StandardId::new(FUNCTIONAL_REQUEST_ID)?, &[mode])
+    }
+}
+
+/// The codes every ECU reported, in the order they came in.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DtcList {
+    pub codes: Vec<(DtcKind, Dtc), MAX_DTCS>,
+    /// Codes that did not fit.
+    pub dropped: usize,
+}
+
+impl DtcList {
+    /// Adds one ECU's reply, a code two ECUs both report is listed once.
+    pub fn add(&mut self, kind: DtcKind, codes: &[Dtc]) {
+        for dtc in codes {
+            if self.codes.contains(&(kind, *dtc)) {
+                continue;
+            }
+            if self.codes.push((kind, *dtc)).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len() + self.dropped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Clearing wipes the freeze frames a garage would want, so it takes two long presses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClearConfirmation {
+    armed_at: Option<u64>,
+}
+
+impl ClearConfirmation {
+    /// The first press arms, a second one within `CLEAR_CONFIRM_MS` returns true.
+    pub fn press(&mut self, now_ms: u64) -> bool {
+        if self.armed(now_ms) {
+            self.armed_at = None;
+            return true;
+        }
+        self.armed_at = Some(now_ms);
+        false
+    }
+
+    pub fn armed(&self, now_ms: u64) -> bool {
+        self.armed_at.is_some_and(|armed_at| now_ms.saturating_sub(armed_at) < CLEAR_CONFIRM_MS)
+    }
+}
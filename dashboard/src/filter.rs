@@ -0,0 +1,183 @@
+//! Plans the TWAI acceptance filter from the ids `CarState` decodes, so the
+//! controller drops everything else before it reaches the receive FIFO.
+//!
+//! A filter is a code and a mask: set mask bits have to match the code, clear ones
+//! are don't-care. Such a filter accepts a power of two sized block of ids, so a
+//! plan usually lets a few ids through that nobody asked for.
+use alloc::vec::Vec;
+use embedded_can::{ExtendedId, Id, StandardId};
+
+use crate::dbc;
+use crate::obd::{RESPONSE_ID_FIRST, RESPONSE_ID_LAST};
+
+const STANDARD_BITS: u32 = 11;
+const EXTENDED_BITS: u32 = 29;
+const STANDARD_ALL: u32 = (1 << STANDARD_BITS) - 1;
+const EXTENDED_ALL: u32 = (1 << EXTENDED_BITS) - 1;
+/// Beyond this many ids not every split into two filters is tried.
+const EXHAUSTIVE_LIMIT: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeMask {
+    pub code: u32,
+    pub mask: u32,
+}
+
+impl CodeMask {
+    /// The narrowest filter accepting all of `ids`: only the bits they agree on are checked.
+    fn covering(ids: impl IntoIterator<Item = u32>, all: u32) -> Option<CodeMask> {
+        let mut ids = ids.into_iter();
+        let first = ids.next()?;
+        let mut mask = all;
+        for id in ids {
+            mask &= !(id ^ first);
+        }
+        Some(CodeMask { code: first & mask, mask })
+    }
+
+    pub fn matches(&self, raw: u32) -> bool {
+        raw & self.mask == self.code
+    }
+
+    fn accepted(&self, bits: u32) -> u32 {
+        1 << (bits - self.mask.count_ones())
+    }
+
+    /// Ids accepted by both filters.
+    fn overlap(&self, other: &CodeMask, bits: u32) -> u32 {
+        let both = self.mask & other.mask;
+        if self.code & both != other.code & both {
+            return 0;
+        }
+        1 << (bits - (self.mask | other.mask).count_ones())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterPlan {
+    /// Nothing to filter on, or standard and extended ids mixed.
+    AcceptAll,
+    /// One filter over the 11 bit id.
+    Single(CodeMask),
+    /// Two filters over the 11 bit id, a frame passes if either matches.
+    Dual(CodeMask, CodeMask),
+    /// One filter over the 29 bit id.
+    Extended(CodeMask),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    pub filter: FilterPlan,
+    /// Distinct ids asked for.
+    pub wanted: usize,
+    /// Ids the filter lets through that are not in the wanted set.
+    pub unwanted_accepted: u32,
+}
+
+impl Plan {
+    pub fn accepts(&self, id: Id) -> bool {
+        match (self.filter, id) {
+            (FilterPlan::AcceptAll, _) => true,
+            (FilterPlan::Single(filter), Id::Standard(id)) => filter.matches(id.as_raw() as u32),
+            (FilterPlan::Dual(first, second), Id::Standard(id)) => {
+                first.matches(id.as_raw() as u32) || second.matches(id.as_raw() as u32)
+            }
+            (FilterPlan::Extended(filter), Id::Extended(id)) => filter.matches(id.as_raw()),
+            _ => false,
+        }
+    }
+}
+
+/// Every id `CarState` does something with: the DBC messages with bound signals
+/// and the OBD-II responses.
+pub fn decoded_ids() -> Vec<Id> {
+    let mut ids: Vec<Id> = dbc::CAR_STATE_UPDATES
+        .iter()
+        .filter_map(|entry| {
+            if entry.message.extended {
+                ExtendedId::new(entry.message.id).map(Id::Extended)
+            } else {
+                StandardId::new(entry.message.id as u16).map(Id::Standard)
+            }
+        })
+        .collect();
+    ids.extend((RESPONSE_ID_FIRST..=RESPONSE_ID_LAST).filter_map(|id| StandardId::new(id).map(Id::Standard)));
+    ids
+}
+
+/// Finds the filter that lets the fewest unwanted ids through.
+pub fn plan(ids: &[Id]) -> Plan {
+    let mut standard: Vec<u32> = Vec::new();
+    let mut extended: Vec<u32> = Vec::new();
+    for id in ids {
+        match id {
+            Id::Standard(id) => standard.push(id.as_raw() as u32),
+            Id::Extended(id) => extended.push(id.as_raw()),
+        }
+    }
+    standard.sort_unstable();
+    standard.dedup();
+    extended.sort_unstable();
+    extended.dedup();
+    let wanted = standard.len() + extended.len();
+
+    if !standard.is_empty() && !extended.is_empty() || wanted == 0 {
+        let accepted = (STANDARD_ALL + 1) + (EXTENDED_ALL + 1);
+        return Plan {
+            filter: FilterPlan::AcceptAll,
+            wanted,
+            unwanted_accepted: accepted - wanted as u32,
+        };
+    }
+    if let Some(filter) = CodeMask::covering(extended.iter().copied(), EXTENDED_ALL) {
+        return Plan {
+            filter: FilterPlan::Extended(filter),
+            wanted,
+            unwanted_accepted: filter.accepted(EXTENDED_BITS) - wanted as u32,
+        };
+    }
+
+    let single = CodeMask::covering(standard.iter().copied(), STANDARD_ALL).unwrap();
+    let mut best = Plan {
+        filter: FilterPlan::Single(single),
+        wanted,
+        unwanted_accepted: single.accepted(STANDARD_BITS) - wanted as u32,
+    };
+    for (first, second) in splits(&standard) {
+        let accepted = first.accepted(STANDARD_BITS) + second.accepted(STANDARD_BITS)
+            - first.overlap(&second, STANDARD_BITS);
+        if accepted - (wanted as u32) < best.unwanted_accepted {
+            best.filter = FilterPlan::Dual(first, second);
+            best.unwanted_accepted = accepted - wanted as u32;
+        }
+    }
+    best
+}
+
+/// Candidate ways to cover the ids with two filters. Small sets try every split,
+/// larger ones split on the value of each id bit.
+fn splits(ids: &[u32]) -> Vec<(CodeMask, CodeMask)> {
+    let cover = |selector: &dyn Fn(usize, u32) -> bool, side: bool| {
+        CodeMask::covering(
+            ids.iter().enumerate().filter(|(index, id)| selector(*index, **id) == side).map(|(_, id)| *id),
+            STANDARD_ALL,
+        )
+    };
+    let mut candidates = Vec::new();
+    let mut push = |selector: &dyn Fn(usize, u32) -> bool| {
+        if let (Some(first), Some(second)) = (cover(selector, true), cover(selector, false)) {
+            candidates.push((first, second));
+        }
+    };
+    if ids.len() <= EXHAUSTIVE_LIMIT {
+        // The first id always goes to the first filter, that halves the work
+        for split in 0..1u32 << (ids.len() - 1) {
+            push(&|index, _| index == 0 || split & (1 << (index - 1)) != 0);
+        }
+    } else {
+        for bit in 0..STANDARD_BITS {
+            push(&|_, id| id & (1 << bit) != 0);
+        }
+    }
+    candidates
+}
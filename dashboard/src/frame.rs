@@ -0,0 +1,63 @@
+use embedded_can::{Frame, Id};
+
+/// A plain classic CAN frame, for everything that does not come from the TWAI
+/// peripheral: the simulator, log replay and tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanFrame {
+    id: Id,
+    remote: bool,
+    dlc: usize,
+    data: [u8; 8],
+}
+
+impl Frame for CanFrame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        Some(CanFrame {
+            id: id.into(),
+            remote: false,
+            dlc: data.len(),
+            data: bytes,
+        })
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+        Some(CanFrame {
+            id: id.into(),
+            remote: true,
+            dlc,
+            data: [0; 8],
+        })
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        self.dlc
+    }
+
+    fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.dlc]
+        }
+    }
+}
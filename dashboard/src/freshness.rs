@@ -0,0 +1,39 @@
+//! How current the values in `CarState` are. Each value from the bus remembers when it
+//! came and how often it should come, a sensor that goes quiet shows as late, then as lost.
+
+/// Late once this many periods went by without a new value.
+pub const LATE_PERIODS: u64 = 3;
+pub const LOST_PERIODS: u64 = 10;
+/// A little jitter on a fast signal is no reason to grey out a gauge, let alone blank it.
+pub const MIN_LATE_MS: u64 = 250;
+pub const MIN_LOST_MS: u64 = 1000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    #[default]
+    Fresh,
+    /// Still shown, greyed out.
+    Late,
+    /// As good as no value: gauges drop to their minimum and readouts show `--`.
+    Lost,
+}
+
+/// When a value was last updated, and how often it is expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub updated_ms: u64,
+    pub period_ms: u64,
+}
+
+impl Stamp {
+    pub fn freshness(&self, now_ms: u64) -> Freshness {
+        let age = now_ms.saturating_sub(self.updated_ms);
+        if age > (self.period_ms * LOST_PERIODS).max(MIN_LOST_MS) {
+            Freshness::Lost
+        } else if age > (self.period_ms * LATE_PERIODS).max(MIN_LATE_MS) {
+            Freshness::Late
+        } else {
+            Freshness::Fresh
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use core::{convert::Infallible, f32::consts::PI};
+
+use alloc::vec::Vec;
+
+use embedded_graphics::{
+    geometry::Point, mono_font::{ascii::{FONT_10X20, FONT_8X13}, MonoTextStyle, MonoTextStyleBuilder}, pixelcolor::{
+        raw::RawU16, Rgb565
+    }, prelude::{Dimensions, DrawTarget, DrawTargetExt, RgbColor}, primitives::{
+        Circle, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, StyledDrawable,
+    }, text::Text, Drawable
+};
+use heapless::String;
+// use num_traits::ToPrimitive;
+use num_traits::cast::ToPrimitive;
+use num_traits::Float;
+
+use crate::antialias::{self, PointF};
+use crate::needle::{Needle, Response};
+use crate::scale::{Band, Label, Scale, Zone};
+
+// use crate::dashboard::{DashboardContext, I_L_OFFSET, I_N_OFFSET, I_OUTER_OFFSET, I_P_OFFSET};
+pub const OUTER_OFFSET: f32 = 10.0;
+pub const P_OFFSET: f32 = 20.0;
+pub const L_OFFSET: f32 = 40.0;
+pub const N_OFFSET: f32 = 70.0;
+
+pub const I_OUTER_OFFSET: u32 = 10;
+pub const I_P_OFFSET: u32 = 20;
+pub const I_L_OFFSET: u32 = 40;
+pub const I_N_OFFSET: u32 = 70;
+
+/// The needle narrows to this towards the scale, it is `needle_style` wide at the hub end.
+const NEEDLE_TIP_WIDTH: f32 = 1.5;
+const HUB_RADIUS: f32 = 3.5;
+
+/// The colour and width of a stroke style, for the anti-aliased primitives.
+fn stroke(style: &PrimitiveStyle<Rgb565>) -> (Rgb565, f32) {
+    (style.stroke_color.unwrap(), style.stroke_width as f32)
+}
+
+pub struct Gauge<
+    const W: usize,
+    const H: usize,
+    const BUFFER: usize,
+    const CLEAR_RADIUS: usize,
+> {
+    pub value: f32,
+    /// Where the needle is, it follows `value` according to its `response`.
+    pub needle: Needle,
+    last_update_ms: Option<u64>,
+    pub scale: Scale,
+    /// Formatted once, the labels are redrawn every frame.
+    labels: Vec<(Point, Zone, Label)>,
+    line1: String<6>,
+    line2: String<6>,
+}
+
+#[allow(dead_code)]
+/// Static context for the dashboard, shouldn't change much after creation
+pub struct DashboardContext<'a, const GAUGE_WIDTH: usize, const GAUGE_HEIGHT: usize> {
+    pub centre: Point,
+    /// The cleared centre of the dial.
+    pub back_color: Rgb565,
+    /// Behind the scale, outside the cleared centre. The display starts out black.
+    pub scale_back_color: Rgb565,
+    pub gauge_color: Rgb565,
+    purple: Rgb565,
+    pub needle_color: Rgb565,
+    pub outer_style: PrimitiveStyle<Rgb565>,
+    pub inner_style: PrimitiveStyle<Rgb565>,
+    pub redline_style: PrimitiveStyle<Rgb565>,
+    pub warning_style: PrimitiveStyle<Rgb565>,
+    pub tick_style: PrimitiveStyle<Rgb565>,
+    pub red_tick_style: PrimitiveStyle<Rgb565>,
+    pub warning_tick_style: PrimitiveStyle<Rgb565>,
+    pub needle_style: PrimitiveStyle<Rgb565>,
+    pub headlight_on_style: PrimitiveStyle<Rgb565>,
+    pub indicator_on_style: PrimitiveStyle<Rgb565>,
+    pub blinker_on_style: PrimitiveStyle<Rgb565>,
+    pub blinker_off_style: PrimitiveStyle<Rgb565>,
+    pub headlight_high_style: PrimitiveStyle<Rgb565>,
+    pub light_off_style: PrimitiveStyle<Rgb565>,
+    /// Amber telltales, something needs attention soon.
+    pub caution_light_style: PrimitiveStyle<Rgb565>,
+    /// Red telltales, stop or act now.
+    pub danger_light_style: PrimitiveStyle<Rgb565>,
+    pub text_style: MonoTextStyle<'a, Rgb565>,
+    pub red_text_style: MonoTextStyle<'a, Rgb565>,
+    pub warning_text_style: MonoTextStyle<'a, Rgb565>,
+    pub centre_text_style: MonoTextStyle<'a, Rgb565>,
+    pub clearing_circle_bounds: Rectangle,
+}
+
+impl<
+    const W: usize,
+    const H: usize,
+    const BUFFER: usize,
+    const CLEAR_RADIUS: usize,
+> Gauge<W, H, BUFFER, CLEAR_RADIUS>
+{
+    const CX: i32 = (W / 2) as i32;
+    const CY: i32 = (H / 2) as i32;
+
+    pub fn new(scale: Scale) -> Self {
+        let labels = scale
+            .ticks()
+            .filter(|(_, major)| *major)
+            .map(|(value, _)| (Self::point(L_OFFSET, scale.angle(value)), scale.zone(value), scale.label(value)))
+            .collect();
+        Gauge {
+            value: scale.min,
+            needle: Needle::new(Response::default(), scale.min),
+            last_update_ms: None,
+            scale,
+            labels,
+            line1: String::new(),
+            line2: String::new(),
+        }
+    }
+
+    pub fn new_speedo() -> Self {
+        Self::new(Scale::speedo())
+    }
+
+    pub fn with_response(mut self, response: Response) -> Self {
+        self.needle.response = response;
+        self
+    }
+
+    fn centre() -> PointF {
+        PointF::new(Self::CX as f32, Self::CY as f32)
+    }
+
+    /// The point `offset` pixels in from the edge at `degrees`, between pixels.
+    fn point_f(offset: f32, degrees: f32) -> PointF {
+        Self::centre().polar((W / 2) as f32 - offset, degrees)
+    }
+
+    /// The point `offset` pixels in from the edge at `degrees`.
+    fn point(offset: f32, degrees: f32) -> Point {
+        let r = (W as i32 / 2).to_f32().unwrap();
+        let (sin, cos) = Float::sin_cos(degrees * PI / 180.0);
+        Point {
+            x: ((r - offset) * cos).to_i32().unwrap() + Self::CX,
+            y: ((r - offset) * sin).to_i32().unwrap() + Self::CY,
+        }
+    }
+
+    /// An arc round the dial centre `offset` pixels in from the edge, over `sweep` degrees from `start`.
+    fn draw_arc<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        framebuffer: &mut D,
+        offset: u32,
+        start: f32,
+        sweep: f32,
+        style: &PrimitiveStyle<Rgb565>,
+        background: Rgb565,
+    ) {
+        let (color, width) = stroke(style);
+        let radius = (W as u32 - offset) as f32 / 2.0;
+        antialias::arc(framebuffer, Self::centre(), radius, width, start, sweep, color, background);
+    }
+
+    fn draw_band<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        band: Option<Band>,
+        style: &PrimitiveStyle<Rgb565>,
+        background: Rgb565,
+    ) {
+        let Some(band) = band else {
+            return;
+        };
+        let start = self.scale.angle(band.from);
+        Self::draw_arc(framebuffer, I_L_OFFSET, start, self.scale.angle(band.to) - start, style, background);
+    }
+
+    pub fn set_line1(&mut self, value: String<6>) {
+        self.line1 = value;
+    }
+
+    pub fn get_line1(&mut self)->&mut String<6> {
+        &mut self.line1
+    }
+
+    pub fn set_line2(&mut self, value: String<6>) {
+        self.line2 = value;
+    }
+
+    pub fn get_line2(&mut self)->&mut String<6> {
+        &mut self.line2
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value;
+    }
+
+    pub fn indicated_value(&self) -> f32 {
+        self.needle.position
+    }
+
+    /// Moves the needle on by the time since the previous call, the first call only starts the clock.
+    pub fn update_indicated(&mut self, now_ms: u64) {
+        let elapsed_ms = self.last_update_ms.map_or(0, |last| now_ms.saturating_sub(last));
+        self.last_update_ms = Some(now_ms);
+        self.needle.step(self.value, elapsed_ms as f32 / 1000.0);
+    }
+
+    /// Shows `value` with the needle at rest on it.
+    pub fn settle_at(&mut self, value: f32) {
+        self.value = value;
+        self.needle.settle(value);
+    }
+
+    pub fn draw_static<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        context: &DashboardContext<W, H>,
+    ) {
+        let background = context.scale_back_color;
+        let (start, sweep) = (self.scale.start_angle, self.scale.sweep);
+        Self::draw_arc(framebuffer, I_OUTER_OFFSET, start, sweep, &context.outer_style, background);
+        Self::draw_arc(framebuffer, I_P_OFFSET, start, sweep, &context.inner_style, background);
+        self.draw_band(framebuffer, self.scale.warning, &context.warning_style, background);
+        self.draw_band(framebuffer, self.scale.redline, &context.redline_style, background);
+        for (value, _) in self.scale.ticks() {
+            let tick = match self.scale.zone(value) {
+                Zone::Normal => context.tick_style,
+                Zone::Warning => context.warning_tick_style,
+                Zone::Redline => context.red_tick_style,
+            };
+            let angle = self.scale.angle(value);
+            let (color, width) = stroke(&tick);
+            antialias::line(
+                framebuffer,
+                Self::point_f(OUTER_OFFSET, angle),
+                Self::point_f(P_OFFSET, angle),
+                width,
+                color,
+                background,
+            );
+        }
+    }
+
+    /// The hub and tip end of the needle pointing at `value`.
+    fn needle_ends(&self, value: f32) -> (PointF, PointF) {
+        let angle = self.scale.angle(value);
+        (Self::point_f(N_OFFSET, angle), Self::point_f(L_OFFSET, angle))
+    }
+
+    /// The area the needle covers when it points at `value`.
+    pub fn needle_bounds(&self, value: f32) -> Rectangle {
+        let (hub, tip) = self.needle_ends(value);
+        antialias::bounds(hub, tip, HUB_RADIUS + 1.0)
+    }
+
+    /// Redraws only what lies inside `area`, like the part of the dial the needle left and entered.
+    pub fn draw_region<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        context: &DashboardContext<W, H>,
+        area: &Rectangle,
+    ) {
+        let mut clipped = framebuffer.clipped(area);
+        self.draw_clear_mask(&mut clipped, context);
+        self.draw_dynamic(&mut clipped, context);
+    }
+
+    pub fn draw_clear_mask<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        context: &DashboardContext<W, H>,
+    ) {
+        Circle::with_center(
+            Point {
+                x: Self::CX,
+                y: Self::CY,
+            },
+            CLEAR_RADIUS.to_u32().unwrap(),
+        )
+        .draw_styled(
+            &PrimitiveStyleBuilder::new()
+                .fill_color(context.back_color)
+                .build(),
+            framebuffer,
+        )
+        .unwrap();
+    }
+
+    pub fn draw_dynamic<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        context: &DashboardContext<W, H>,
+    ) {
+        // The labels reach into the cleared circle, so they are drawn every frame
+        for (position, zone, label) in &self.labels {
+            let text_style = match zone {
+                Zone::Normal => context.text_style,
+                Zone::Warning => context.warning_text_style,
+                Zone::Redline => context.red_text_style,
+            };
+            Text::with_alignment(
+                label,
+                *position,
+                text_style,
+                embedded_graphics::text::Alignment::Center,
+            )
+            .draw(framebuffer)
+            .unwrap();
+        }
+        // Out of range values pin the needle to the end stops instead of wrapping around the dial
+        let (hub, tip) = self.needle_ends(self.indicated_value());
+        // Big mistery: Uncommenting the following code will cause the screen to stop working. It starts, it prints to out, just no screen.
+        // Even if the code is _never executed_
+        // Compiler bug? Weird linker thing? I give up
+        // if self.indicated_value() > 10000 {
+        //     let gauge_angle2: usize = (self.indicated_value() * 360).try_into().unwrap();
+        // }
+        let (needle_color, needle_width) = stroke(&context.needle_style);
+        antialias::tapered_line(framebuffer, hub, tip, needle_width, NEEDLE_TIP_WIDTH, needle_color, context.back_color);
+        antialias::disc(framebuffer, hub, HUB_RADIUS, needle_color, context.back_color);
+        let (color, width) = stroke(&context.outer_style);
+        let radius = (W as u32 - I_N_OFFSET) as f32 / 4.0;
+        antialias::arc(framebuffer, Self::centre(), radius, width, 100.0, 340.0, color, context.back_color);
+
+        // TODO disable line so I can remove mut
+        // write!(self.line1, "{}", self.value).unwrap();
+        // self.set_line1(String::from(self.value));
+        Text::with_alignment(
+            &self.line1,
+            context.centre,
+            context.centre_text_style,
+            embedded_graphics::text::Alignment::Center,
+        )
+        .draw(framebuffer)
+        .unwrap();
+        Text::with_alignment(
+            &self.line2,
+            Point::new(context.centre.x, context.centre.y + 18),
+            context.centre_text_style,
+            embedded_graphics::text::Alignment::Center,
+        )
+        .draw(framebuffer)
+        .unwrap();
+    }
+}
+
+
+impl <const GAUGE_WIDTH: usize,const GAUGE_HEIGHT: usize> Default for DashboardContext<'_,GAUGE_WIDTH,GAUGE_HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a, const GAUGE_WIDTH: usize,const GAUGE_HEIGHT: usize> DashboardContext<'a,GAUGE_WIDTH,GAUGE_HEIGHT> {
+    pub fn new()->Self {
+        let r: f32 = (GAUGE_WIDTH as i32 / 2).to_f32().unwrap();
+        let cx = (GAUGE_WIDTH / 2) as i32;
+        let cy = (GAUGE_HEIGHT / 2) as i32;
+        let centre = Point::new(cx, cy);
+        let clearing_circle_bounds = Circle::with_center(centre, (2.0*(r - L_OFFSET)).to_u32().unwrap()).bounding_box();
+        let back_color = Rgb565::from(RawU16::from(0x0026));
+        let gauge_color = Rgb565::from(RawU16::from(0x055D));
+        let purple = Rgb565::from(RawU16::from(0xEA16));
+        let needle_color = Rgb565::from(RawU16::from(0xF811));
+        let amber = Rgb565::from(RawU16::from(0xFD20));
+        let outer_style = PrimitiveStyleBuilder::new()
+            .stroke_color(gauge_color)
+            .stroke_width(3)
+            .build();
+        let inner_style = PrimitiveStyleBuilder::new()
+            .stroke_color(Rgb565::WHITE)
+            .stroke_width(3)
+            .build();
+        let redline_style = PrimitiveStyleBuilder::new()
+            .stroke_color(purple)
+            .stroke_width(3)
+            .build();
+        let warning_style = PrimitiveStyleBuilder::new()
+            .stroke_color(amber)
+            .stroke_width(3)
+            .build();
+        let tick_style = PrimitiveStyleBuilder::new()
+            .stroke_color(Rgb565::WHITE)
+            .stroke_width(2)
+            .build();
+        let red_tick_style = PrimitiveStyleBuilder::new()
+            .stroke_color(purple)
+            .stroke_width(2)
+            .build();
+        let warning_tick_style = PrimitiveStyleBuilder::new()
+            .stroke_color(amber)
+            .stroke_width(2)
+            .build();
+        let needle_style = PrimitiveStyleBuilder::new()
+            .stroke_color(needle_color)
+            .stroke_width(4)
+            .build();
+        let headlight_on_style = PrimitiveStyleBuilder::new()
+            .fill_color(Rgb565::GREEN)
+            .stroke_width(1)
+            .stroke_color(Rgb565::GREEN)
+            .build();
+        let headlight_high_style = PrimitiveStyleBuilder::new()
+            .fill_color(gauge_color)
+            .stroke_width(1)
+            .stroke_color(gauge_color)
+            .build();
+
+        let indicator_on_style = PrimitiveStyleBuilder::new()
+            .stroke_color(Rgb565::GREEN)
+            .stroke_width(2)
+            .build();
+        let blinker_on_style = PrimitiveStyleBuilder::new()
+            .fill_color(Rgb565::GREEN)
+            .build();
+        let blinker_off_style = PrimitiveStyleBuilder::new()
+            .fill_color(Rgb565::new(0x4, 0x8, 0x4))
+            .build();
+        // let color = Rgb565::new(0x33, 0x33, 0x33);
+
+
+        let light_off_style = PrimitiveStyleBuilder::new()
+            .stroke_color(Rgb565::new(0x4, 0x8, 0x4))
+            .stroke_width(1)
+            .fill_color(Rgb565::new(0x4, 0x8, 0x4))
+            .build();
+        let caution_light_style = PrimitiveStyleBuilder::new()
+            .fill_color(amber)
+            .stroke_width(1)
+            .stroke_color(amber)
+            .build();
+        let danger_light_style = PrimitiveStyleBuilder::new()
+            .fill_color(Rgb565::RED)
+            .stroke_width(1)
+            .stroke_color(Rgb565::RED)
+            .build();
+        let text_style = MonoTextStyleBuilder::new()
+            .text_color(Rgb565::WHITE)
+            .font(&FONT_8X13)
+            .build();
+        let red_text_style = MonoTextStyleBuilder::new()
+            .text_color(purple)
+            .font(&FONT_8X13)
+            .build();
+        let warning_text_style = MonoTextStyleBuilder::new()
+            .text_color(amber)
+            .font(&FONT_8X13)
+            .build();
+
+        let centre_text_style = MonoTextStyleBuilder::new()
+            .text_color(Rgb565::WHITE)
+            .font(&FONT_10X20)
+            .build();
+
+        DashboardContext { 
+            centre,
+            back_color,
+            scale_back_color: Rgb565::BLACK,
+            gauge_color,
+            purple,
+            needle_color,
+            outer_style,
+            inner_style,
+            redline_style,
+            warning_style,
+            tick_style,
+            red_tick_style,
+            warning_tick_style,
+            needle_style,
+            headlight_on_style,
+            headlight_high_style,
+            indicator_on_style,
+            blinker_on_style,
+            blinker_off_style,
+            light_off_style,            
+            caution_light_style,
+            danger_light_style,
+            text_style,
+            red_text_style,
+            warning_text_style,
+            centre_text_style,
+            clearing_circle_bounds,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,456 @@
+//! ISO 15765-2 (ISO-TP) transport layer on top of classic CAN frames.
+//!
+//! Everything in here is sans-io: frames go in, frames and complete messages
+//! come out, and time is passed in as milliseconds. That keeps it generic over
+//! [`embedded_can::Frame`] and testable on the host.
+use alloc::vec::Vec;
+use embedded_can::{Frame, Id, StandardId};
+
+/// Largest payload a classic CAN first frame can announce.
+pub const MAX_MESSAGE_LEN: usize = 4095;
+/// Filler for the unused bytes of a frame, ECUs tend to insist on a DLC of 8.
+pub const PADDING: u8 = 0x00;
+/// N_Bs / N_Cr: the ISO 15765-2 default of one second.
+const TIMEOUT_MS: u64 = 1000;
+/// The peer may answer with a number of FC.WAIT frames before giving up.
+const MAX_WAIT_FRAMES: u8 = 10;
+
+const PCI_SINGLE: u8 = 0x0;
+const PCI_FIRST: u8 = 0x1;
+const PCI_CONSECUTIVE: u8 = 0x2;
+const PCI_FLOW_CONTROL: u8 = 0x3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowStatus {
+    ContinueToSend,
+    Wait,
+    Overflow,
+}
+
+impl FlowStatus {
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(FlowStatus::ContinueToSend),
+            1 => Some(FlowStatus::Wait),
+            2 => Some(FlowStatus::Overflow),
+            _ => None,
+        }
+    }
+
+    fn raw(self) -> u8 {
+        match self {
+            FlowStatus::ContinueToSend => 0,
+            FlowStatus::Wait => 1,
+            FlowStatus::Overflow => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsoTpError {
+    /// The peer went quiet in the middle of a transfer (N_Bs / N_Cr expired).
+    Timeout,
+    /// A consecutive frame arrived out of order, the message is dropped.
+    WrongSequence,
+    /// The receiver told us it cannot take a message this long.
+    Overflow,
+    /// A transmission is already running on this channel.
+    Busy,
+    /// The payload does not fit in a classic CAN ISO-TP message.
+    TooLong,
+    /// There is nothing to send, ISO-TP has no empty messages.
+    Empty,
+}
+
+/// A fully reassembled message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsoTpMessage {
+    /// The CAN ID the message was received on.
+    pub id: Id,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum Event<F> {
+    /// A complete message was received.
+    Message(IsoTpMessage),
+    /// A frame that needs to go on the bus (flow control or consecutive frame).
+    Transmit(F),
+    Error(IsoTpError),
+}
+
+/// Flow control parameters we hand out to a peer that sends us a multi-frame message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlowControl {
+    /// Consecutive frames the peer may send before waiting for the next flow control, 0 is unlimited.
+    pub block_size: u8,
+    /// Raw STmin, minimum separation time between consecutive frames.
+    pub st_min: u8,
+}
+
+enum Pci<'a> {
+    Single(&'a [u8]),
+    First { len: usize, data: &'a [u8] },
+    Consecutive { sn: u8, data: &'a [u8] },
+    FlowControl { status: FlowStatus, block_size: u8, st_min: u8 },
+}
+
+fn parse_pci(data: &[u8]) -> Option<Pci<'_>> {
+    let pci = *data.first()?;
+    match pci >> 4 {
+        PCI_SINGLE => {
+            let len = (pci & 0x0F) as usize;
+            // A length of 0 is the CAN FD escape sequence, not supported on classic CAN
+            if len == 0 || len + 1 > data.len() {
+                return None;
+            }
+            Some(Pci::Single(&data[1..=len]))
+        }
+        PCI_FIRST => {
+            let len = (((pci & 0x0F) as usize) << 8) | *data.get(1)? as usize;
+            if len <= 7 {
+                return None;
+            }
+            Some(Pci::First { len, data: &data[2..] })
+        }
+        PCI_CONSECUTIVE => Some(Pci::Consecutive {
+            sn: pci & 0x0F,
+            data: &data[1..],
+        }),
+        PCI_FLOW_CONTROL => Some(Pci::FlowControl {
+            status: FlowStatus::from_raw(pci & 0x0F)?,
+            block_size: *data.get(1)?,
+            st_min: *data.get(2)?,
+        }),
+        _ => None,
+    }
+}
+
+/// Converts a raw STmin to milliseconds. The 100-900µs range rounds up to 1ms,
+/// reserved values count as the maximum of 127ms as the standard asks.
+pub fn st_min_ms(raw: u8) -> u64 {
+    match raw {
+        0x00..=0x7F => raw as u64,
+        0xF1..=0xF9 => 1,
+        _ => 0x7F,
+    }
+}
+
+fn padded_frame<F: Frame>(id: Id, bytes: &[u8]) -> Option<F> {
+    let mut data = [PADDING; 8];
+    data[..bytes.len()].copy_from_slice(bytes);
+    F::new(id, &data)
+}
+
+/// Build a single frame carrying `payload` (at most 7 bytes).
+pub fn single_frame<F: Frame>(id: impl Into<Id>, payload: &[u8]) -> Option<F> {
+    if payload.is_empty() || payload.len() > 7 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    bytes[0] = (PCI_SINGLE << 4) | payload.len() as u8;
+    bytes[1..=payload.len()].copy_from_slice(payload);
+    padded_frame(id.into(), &bytes[..=payload.len()])
+}
+
+fn flow_control_frame<F: Frame>(id: Id, status: FlowStatus, flow_control: FlowControl) -> Option<F> {
+    padded_frame(
+        id,
+        &[
+            (PCI_FLOW_CONTROL << 4) | status.raw(),
+            flow_control.block_size,
+            flow_control.st_min,
+        ],
+    )
+}
+
+enum RxState {
+    Idle,
+    Receiving {
+        data: Vec<u8>,
+        expected: usize,
+        next_sn: u8,
+        block_remaining: u8,
+        deadline_ms: u64,
+    },
+}
+
+enum TxState {
+    Idle,
+    WaitingForFlowControl {
+        deadline_ms: u64,
+        waits: u8,
+    },
+    Sending {
+        /// Frames left in the current block, `None` when the block size is unlimited.
+        block_remaining: Option<u8>,
+        st_min_ms: u64,
+        next_due_ms: u64,
+    },
+}
+
+/// One ISO-TP connection: we receive on `rx_id` and send (data and flow control) on `tx_id`.
+pub struct Channel {
+    rx_id: Id,
+    tx_id: Id,
+    flow_control: FlowControl,
+    rx: RxState,
+    tx: TxState,
+    tx_data: Vec<u8>,
+    tx_offset: usize,
+    tx_sn: u8,
+}
+
+impl Channel {
+    pub fn new(rx_id: impl Into<Id>, tx_id: impl Into<Id>, flow_control: FlowControl) -> Self {
+        Channel {
+            rx_id: rx_id.into(),
+            tx_id: tx_id.into(),
+            flow_control,
+            rx: RxState::Idle,
+            tx: TxState::Idle,
+            tx_data: Vec::new(),
+            tx_offset: 0,
+            tx_sn: 0,
+        }
+    }
+
+    pub fn rx_id(&self) -> Id {
+        self.rx_id
+    }
+
+    pub fn tx_id(&self) -> Id {
+        self.tx_id
+    }
+
+    /// Feed a frame received on `rx_id`.
+    pub fn on_frame<F: Frame>(&mut self, frame: &F, now_ms: u64) -> Option<Event<F>> {
+        match parse_pci(frame.data())? {
+            Pci::Single(data) => {
+                // A new single frame aborts any reception in progress
+                self.rx = RxState::Idle;
+                Some(Event::Message(IsoTpMessage {
+                    id: self.rx_id,
+                    data: Vec::from(data),
+                }))
+            }
+            Pci::First { len, data } => {
+                if len > MAX_MESSAGE_LEN {
+                    self.rx = RxState::Idle;
+                    return flow_control_frame(self.tx_id, FlowStatus::Overflow, self.flow_control)
+                        .map(Event::Transmit);
+                }
+                let mut buffer = Vec::with_capacity(len);
+                buffer.extend_from_slice(&data[..data.len().min(len)]);
+                self.rx = RxState::Receiving {
+                    data: buffer,
+                    expected: len,
+                    next_sn: 1,
+                    block_remaining: self.flow_control.block_size,
+                    deadline_ms: now_ms + TIMEOUT_MS,
+                };
+                flow_control_frame(self.tx_id, FlowStatus::ContinueToSend, self.flow_control)
+                    .map(Event::Transmit)
+            }
+            Pci::Consecutive { sn, data } => self.on_consecutive(sn, data, now_ms),
+            Pci::FlowControl { status, block_size, st_min } => {
+                self.on_flow_control(status, block_size, st_min, now_ms)
+            }
+        }
+    }
+
+    fn on_consecutive<F: Frame>(&mut self, sn: u8, data: &[u8], now_ms: u64) -> Option<Event<F>> {
+        let RxState::Receiving { data: buffer, expected, next_sn, block_remaining, deadline_ms } = &mut self.rx else {
+            // Not expecting anything, ignore stray frames
+            return None;
+        };
+        if sn != *next_sn {
+            self.rx = RxState::Idle;
+            return Some(Event::Error(IsoTpError::WrongSequence));
+        }
+        let take = data.len().min(*expected - buffer.len());
+        buffer.extend_from_slice(&data[..take]);
+        if buffer.len() == *expected {
+            let RxState::Receiving { data, .. } = core::mem::replace(&mut self.rx, RxState::Idle) else {
+                unreachable!()
+            };
+            return Some(Event::Message(IsoTpMessage { id: self.rx_id, data }));
+        }
+        *next_sn = (*next_sn + 1) & 0x0F;
+        *deadline_ms = now_ms + TIMEOUT_MS;
+        if self.flow_control.block_size != 0 {
+            *block_remaining -= 1;
+            if *block_remaining == 0 {
+                *block_remaining = self.flow_control.block_size;
+                return flow_control_frame(self.tx_id, FlowStatus::ContinueToSend, self.flow_control)
+                    .map(Event::Transmit);
+            }
+        }
+        None
+    }
+
+    fn on_flow_control<F: Frame>(&mut self, status: FlowStatus, block_size: u8, st_min: u8, now_ms: u64) -> Option<Event<F>> {
+        let waits = match self.tx {
+            TxState::WaitingForFlowControl { waits, .. } => waits,
+            _ => return None,
+        };
+        match status {
+            FlowStatus::ContinueToSend => {
+                self.tx = TxState::Sending {
+                    block_remaining: if block_size == 0 { None } else { Some(block_size) },
+                    st_min_ms: st_min_ms(st_min),
+                    next_due_ms: now_ms,
+                };
+                self.next_consecutive(now_ms)
+            }
+            FlowStatus::Wait if waits < MAX_WAIT_FRAMES => {
+                self.tx = TxState::WaitingForFlowControl {
+                    deadline_ms: now_ms + TIMEOUT_MS,
+                    waits: waits + 1,
+                };
+                None
+            }
+            FlowStatus::Wait => {
+                self.abort_tx();
+                Some(Event::Error(IsoTpError::Timeout))
+            }
+            FlowStatus::Overflow => {
+                self.abort_tx();
+                Some(Event::Error(IsoTpError::Overflow))
+            }
+        }
+    }
+
+    /// Start sending `payload` to `tx_id`. Returns the first frame to put on the bus,
+    /// the rest comes out of [`Channel::poll`] once the peer sends flow control.
+    pub fn send<F: Frame>(&mut self, payload: &[u8], now_ms: u64) -> Result<F, IsoTpError> {
+        if !matches!(self.tx, TxState::Idle) {
+            return Err(IsoTpError::Busy);
+        }
+        if payload.is_empty() {
+            return Err(IsoTpError::Empty);
+        }
+        if payload.len() > MAX_MESSAGE_LEN {
+            return Err(IsoTpError::TooLong);
+        }
+        if payload.len() <= 7 {
+            return single_frame(self.tx_id, payload).ok_or(IsoTpError::TooLong);
+        }
+        let len = payload.len();
+        let mut bytes = [0u8; 8];
+        bytes[0] = (PCI_FIRST << 4) | (len >> 8) as u8;
+        bytes[1] = len as u8;
+        bytes[2..].copy_from_slice(&payload[..6]);
+        self.tx_data = Vec::from(payload);
+        self.tx_offset = 6;
+        self.tx_sn = 1;
+        self.tx = TxState::WaitingForFlowControl {
+            deadline_ms: now_ms + TIMEOUT_MS,
+            waits: 0,
+        };
+        F::new(self.tx_id, &bytes).ok_or(IsoTpError::TooLong)
+    }
+
+    fn next_consecutive<F: Frame>(&mut self, now_ms: u64) -> Option<Event<F>> {
+        let TxState::Sending { block_remaining, st_min_ms, next_due_ms } = &mut self.tx else {
+            return None;
+        };
+        if now_ms < *next_due_ms {
+            return None;
+        }
+        let end = (self.tx_offset + 7).min(self.tx_data.len());
+        let mut bytes = [0u8; 8];
+        bytes[0] = (PCI_CONSECUTIVE << 4) | self.tx_sn;
+        bytes[1..=end - self.tx_offset].copy_from_slice(&self.tx_data[self.tx_offset..end]);
+        let frame = padded_frame(self.tx_id, &bytes[..=end - self.tx_offset]);
+        self.tx_offset = end;
+        self.tx_sn = (self.tx_sn + 1) & 0x0F;
+        *next_due_ms = now_ms + *st_min_ms;
+        if self.tx_offset == self.tx_data.len() {
+            self.abort_tx();
+        } else if let Some(remaining) = block_remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.tx = TxState::WaitingForFlowControl {
+                    deadline_ms: now_ms + TIMEOUT_MS,
+                    waits: 0,
+                };
+            }
+        }
+        frame.map(Event::Transmit)
+    }
+
+    fn abort_tx(&mut self) {
+        self.tx = TxState::Idle;
+        self.tx_data = Vec::new();
+        self.tx_offset = 0;
+    }
+
+    /// Drive timeouts and pending consecutive frames. Call it regularly and
+    /// keep calling while it returns events.
+    pub fn poll<F: Frame>(&mut self, now_ms: u64) -> Option<Event<F>> {
+        if let RxState::Receiving { deadline_ms, .. } = self.rx
+            && now_ms > deadline_ms
+        {
+            self.rx = RxState::Idle;
+            return Some(Event::Error(IsoTpError::Timeout));
+        }
+        if let TxState::WaitingForFlowControl { deadline_ms, .. } = self.tx
+            && now_ms > deadline_ms
+        {
+            self.abort_tx();
+            return Some(Event::Error(IsoTpError::Timeout));
+        }
+        self.next_consecutive(now_ms)
+    }
+
+    /// True while a segmented transmission is still in progress.
+    pub fn is_sending(&self) -> bool {
+        !matches!(self.tx, TxState::Idle)
+    }
+}
+
+/// A set of channels, frames are dispatched on their CAN ID.
+#[derive(Default)]
+pub struct IsoTpLayer {
+    channels: Vec<Channel>,
+}
+
+impl IsoTpLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Channels for the eight OBD-II ECU response addresses (0x7E8-0x7EF),
+    /// each replying to its physical request address 8 below.
+    pub fn obd(flow_control: FlowControl) -> Self {
+        let mut layer = Self::new();
+        for rx in crate::obd::RESPONSE_ID_FIRST..=crate::obd::RESPONSE_ID_LAST {
+            if let (Some(rx_id), Some(tx_id)) = (StandardId::new(rx), StandardId::new(rx - 8)) {
+                layer.add_channel(Channel::new(rx_id, tx_id, flow_control));
+            }
+        }
+        layer
+    }
+
+    pub fn add_channel(&mut self, channel: Channel) {
+        self.channels.push(channel);
+    }
+
+    pub fn handles(&self, id: Id) -> bool {
+        self.channels.iter().any(|channel| channel.rx_id == id)
+    }
+
+    pub fn channel_mut(&mut self, rx_id: Id) -> Option<&mut Channel> {
+        self.channels.iter_mut().find(|channel| channel.rx_id == rx_id)
+    }
+
+    pub fn on_frame<F: Frame>(&mut self, frame: &F, now_ms: u64) -> Option<Event<F>> {
+        self.channel_mut(frame.id())?.on_frame(frame, now_ms)
+    }
+
+    /// Polls every channel, returning the first event found.
+    pub fn poll<F: Frame>(&mut self, now_ms: u64) -> Option<Event<F>> {
+        self.channels.iter_mut().find_map(|channel| channel.poll(now_ms))
+    }
+}
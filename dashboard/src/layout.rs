@@ -0,0 +1,679 @@
+//! What goes where on the screen. A layout is a list of widgets, each with its
+//! bounds and the `CarState` field it shows. Widgets are drawn in order, so
+//! the main gauge (which clears its centre every frame) comes first.
+use core::{convert::Infallible, fmt::Write, mem::replace};
+
+use alloc::{vec, vec::Vec};
+use embedded_graphics::{
+    geometry::{Point, Size},
+    mono_font::{ascii::{FONT_10X20, FONT_6X9, FONT_8X13}, MonoFont, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, DrawTargetExt, Primitive, RgbColor, Transform},
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+use heapless::String;
+
+use crate::{
+    alert::{AlertEngine, Priority, HISTORY_LEN},
+    antialias::{self, PointF},
+    bus_health::ErrorState,
+    car_state::{CarField, CarState},
+    damage,
+    dtc::{DtcKind, DtcList},
+    freshness::Freshness,
+    gauge::Gauge,
+    scale::Scale,
+    screen::{Context, ScreenGauge},
+    telltale::{Icon, Telltale},
+    trip::{self, EconomyUnit, TripId},
+};
+
+/// Values that are late, still shown but no longer to be trusted.
+const STALE_COLOR: Rgb565 = Rgb565::new(0x0C, 0x18, 0x0C);
+
+/// Sub-dials sweep 270 degrees, open at the bottom.
+const DIAL_START_DEGREES: f32 = 135.0;
+const DIAL_SWEEP_DEGREES: f32 = 270.0;
+
+/// Where `value` sits between `min` and `max`, clamped to 0..=1.
+fn fraction(value: f32, min: f32, max: f32) -> f32 {
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// A small round dial with a needle and a label underneath the hub.
+pub struct Dial {
+    pub min: f32,
+    pub max: f32,
+    pub label: &'static str,
+    value: f32,
+}
+
+impl Dial {
+    pub fn new(min: f32, max: f32, label: &'static str) -> Self {
+        Dial { min, max, label, value: min }
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        bounds: Rectangle,
+        context: &Context,
+        needle_color: Rgb565,
+    ) {
+        let diameter = bounds.size.width.min(bounds.size.height);
+        let centre = bounds.center();
+        let hub = PointF::from(centre);
+        antialias::arc(
+            framebuffer,
+            hub,
+            (diameter - 2) as f32 / 2.0,
+            2.0,
+            DIAL_START_DEGREES,
+            DIAL_SWEEP_DEGREES,
+            context.gauge_color,
+            context.back_color,
+        );
+        let angle = DIAL_START_DEGREES + DIAL_SWEEP_DEGREES * fraction(self.value, self.min, self.max);
+        let tip = hub.polar((diameter / 2) as f32 - 4.0, angle);
+        antialias::line(framebuffer, hub, tip, 2.0, needle_color, context.back_color);
+        Text::with_text_style(
+            self.label,
+            Point::new(centre.x, bounds.top_left.y + diameter as i32 - 1),
+            MonoTextStyle::new(&FONT_6X9, Rgb565::new(0x1F, 0x3F, 0x1F)),
+            TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Bottom).build(),
+        )
+        .draw(framebuffer)
+        .unwrap();
+    }
+}
+
+/// A horizontal bar filling from the left.
+pub struct BarGraph {
+    pub min: f32,
+    pub max: f32,
+    value: f32,
+}
+
+impl BarGraph {
+    pub fn new(min: f32, max: f32) -> Self {
+        BarGraph { min, max, value: min }
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &self,
+        framebuffer: &mut D,
+        bounds: Rectangle,
+        context: &Context,
+        fill_color: Rgb565,
+    ) {
+        bounds
+            .into_styled(PrimitiveStyle::with_stroke(context.gauge_color, 1))
+            .draw(framebuffer)
+            .unwrap();
+        let inner = bounds.offset(-2);
+        let filled = (inner.size.width as f32 * fraction(self.value, self.min, self.max)) as u32;
+        Rectangle::new(inner.top_left, Size::new(filled, inner.size.height))
+            .into_styled(PrimitiveStyle::with_fill(fill_color))
+            .draw(framebuffer)
+            .unwrap();
+    }
+}
+
+/// A number with a unit, `--` while the value is unknown.
+pub struct Readout {
+    pub decimals: usize,
+    pub unit: &'static str,
+    pub font: &'static MonoFont<'static>,
+    text: String<16>,
+}
+
+impl Readout {
+    pub fn new(decimals: usize, unit: &'static str, font: &'static MonoFont<'static>) -> Self {
+        let mut readout = Readout { decimals, unit, font, text: String::new() };
+        readout.update(None);
+        readout
+    }
+
+    /// Returns whether the text changed.
+    fn update(&mut self, value: Option<f32>) -> bool {
+        let previous = self.text.clone();
+        self.text.clear();
+        // Does not fit only for absurd values, those just show truncated
+        let _ = match value {
+            Some(value) => write!(self.text, "{:.*}{}", self.decimals, value, self.unit),
+            None => write!(self.text, "--{}", self.unit),
+        };
+        self.text != previous
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, color: Rgb565) {
+        Text::with_text_style(
+            &self.text,
+            bounds.center(),
+            MonoTextStyle::new(self.font, color),
+            TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Middle).build(),
+        )
+        .draw(framebuffer)
+        .unwrap();
+    }
+}
+
+const TRIP_ROWS: [&str; 8] = ["Distance", "Time", "Moving", "Avg speed", "Max speed", "Fuel used", "Now", "Average"];
+const TRIP_ROW_HEIGHT: i32 = 16;
+
+/// The counters of one trip, a row per value under the trip's name.
+pub struct TripPanel {
+    pub trip: TripId,
+    pub unit: EconomyUnit,
+    values: [String<12>; TRIP_ROWS.len()],
+}
+
+impl TripPanel {
+    pub fn new(trip: TripId, unit: EconomyUnit) -> Self {
+        TripPanel { trip, unit, values: Default::default() }
+    }
+
+    /// Returns whether any of the text changed.
+    fn update(&mut self, state: &CarState) -> bool {
+        let trip = state.trips().trip(self.trip);
+        let unit = self.unit;
+        let mut values: [String<12>; TRIP_ROWS.len()] = Default::default();
+        let economy = |text: &mut String<12>, consumption: Option<f32>| match consumption {
+            Some(consumption) => write!(text, "{:.1}{}", unit.convert(consumption), unit.label()),
+            None => write!(text, "--{}", unit.label()),
+        };
+        // Like `Readout`, anything too long just shows truncated
+        let _ = write!(values[0], "{:.1}km", trip.distance_km());
+        let _ = trip::format_duration(trip.elapsed_ms(), &mut values[1]);
+        let _ = trip::format_duration(trip.moving_ms(), &mut values[2]);
+        let _ = match trip.average_speed() {
+            Some(speed) => write!(values[3], "{:.0}km/h", speed),
+            None => write!(values[3], "--km/h"),
+        };
+        let _ = write!(values[4], "{:.0}km/h", trip.max_speed());
+        let _ = write!(values[5], "{:.2}L", trip.fuel_used());
+        let _ = economy(&mut values[6], state.trips().instant_consumption());
+        let _ = economy(&mut values[7], trip.average_consumption());
+        replace(&mut self.values, values) != self.values
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, context: &Context) {
+        let rows = TRIP_ROWS.iter().zip(&self.values).map(|(label, value)| (*label, value.as_str(), Rgb565::WHITE));
+        draw_rows(framebuffer, bounds, self.trip.name(), rows, context);
+    }
+}
+
+/// A title, then a label on the left and a value on the right per row.
+fn draw_rows<'a, D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+    framebuffer: &mut D,
+    bounds: Rectangle,
+    title: &str,
+    rows: impl Iterator<Item = (&'a str, &'a str, Rgb565)>,
+    context: &Context,
+) {
+    let text_style = |alignment| TextStyleBuilder::new().alignment(alignment).baseline(Baseline::Top).build();
+    draw_title(framebuffer, bounds, title, context);
+    let right = bounds.top_left.x + bounds.size.width as i32 - 1;
+    for (row, (label, value, color)) in rows.enumerate() {
+        let y = bounds.top_left.y + 28 + row as i32 * TRIP_ROW_HEIGHT;
+        Text::with_text_style(
+            label,
+            Point::new(bounds.top_left.x, y),
+            MonoTextStyle::new(&FONT_8X13, context.gauge_color),
+            text_style(Alignment::Left),
+        )
+        .draw(framebuffer)
+        .unwrap();
+        Text::with_text_style(value, Point::new(right, y), MonoTextStyle::new(&FONT_8X13, color), text_style(Alignment::Right))
+            .draw(framebuffer)
+            .unwrap();
+    }
+}
+
+fn draw_title<D: DrawTarget<Color = Rgb565, Error = Infallible>>(framebuffer: &mut D, bounds: Rectangle, title: &str, context: &Context) {
+    Text::with_text_style(
+        title,
+        Point::new(bounds.center().x, bounds.top_left.y),
+        MonoTextStyle::new(&FONT_10X20, context.needle_color),
+        TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Top).build(),
+    )
+    .draw(framebuffer)
+    .unwrap();
+}
+
+const DIAGNOSTIC_ROWS: [&str; 9] =
+    ["Messages", "Dropped", "Voltage", "Render fps", "Accepted/s", "Accepted load", "Error rate", "TEC/REC", "Bus state"];
+
+/// What the old debug screen showed (frames received, battery voltage and the frame rate),
+/// and how the bus is doing. Render fps counts the frames sent to the display, the bus
+/// frame rate and load only count accepted traffic, see `BusHealth`.
+pub struct DiagnosticsPanel {
+    values: [String<12>; 9],
+    bus_state: ErrorState,
+    /// Frames the receive ring had to drop, shown in the caution colour once there are any.
+    dropped: u32,
+}
+
+impl DiagnosticsPanel {
+    pub fn new() -> Self {
+        DiagnosticsPanel {
+            values: Default::default(),
+            bus_state: ErrorState::Active,
+            dropped: 0,
+        }
+    }
+
+    fn update(&mut self, state: &CarState) -> bool {
+        let bus = state.bus_health();
+        let mut values: [String<12>; 9] = Default::default();
+        let _ = write!(values[0], "{}", state.message_count());
+        self.dropped = state.ring_stats().dropped;
+        let _ = write!(values[1], "{}", self.dropped);
+        let _ = write!(values[2], "{:.2}V", state.voltage());
+        let _ = match state.render_fps() {
+            Some(fps) => write!(values[3], "{}", fps),
+            None => write!(values[3], "--"),
+        };
+        let _ = write!(values[4], "{:.0}", bus.frames_per_s);
+        let _ = write!(values[5], "{:.1}%", bus.bus_load);
+        let _ = write!(values[6], "{:.1}%", bus.error_rate);
+        let _ = write!(values[7], "{}/{}", bus.tx_errors, bus.rx_errors);
+        let _ = values[8].push_str(bus.state.label());
+        let state_changed = replace(&mut self.bus_state, bus.state) != self.bus_state;
+        replace(&mut self.values, values) != self.values || state_changed
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, context: &Context) {
+        let state_color = match self.bus_state {
+            ErrorState::Active => Rgb565::WHITE,
+            ErrorState::Passive => context.caution_light_style.fill_color.unwrap(),
+            ErrorState::BusOff => context.danger_light_style.fill_color.unwrap(),
+        };
+        let dropped_color = if self.dropped > 0 { context.caution_light_style.fill_color.unwrap() } else { Rgb565::WHITE };
+        let rows = DIAGNOSTIC_ROWS.iter().zip(&self.values).enumerate().map(|(row, (label, value))| {
+            let color = match row {
+                1 => dropped_color,
+                8 => state_color,
+                _ => Rgb565::WHITE,
+            };
+            (*label, value.as_str(), color)
+        });
+        draw_rows(framebuffer, bounds, "DIAGNOSTICS", rows, context);
+    }
+}
+
+impl Default for DiagnosticsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HistoryRow {
+    message: &'static str,
+    /// When it was raised, `h:mm` since power on.
+    time: String<8>,
+    priority: Priority,
+    active: bool,
+}
+
+/// The last alerts, newest at the top, in their colour while still active.
+#[derive(Default)]
+pub struct AlertHistoryPanel {
+    rows: heapless::Vec<HistoryRow, HISTORY_LEN>,
+}
+
+impl AlertHistoryPanel {
+    fn update(&mut self, alerts: &AlertEngine) -> bool {
+        let rows = alerts
+            .history
+            .iter()
+            .rev()
+            .map(|entry| {
+                let mut time = String::new();
+                let _ = trip::format_duration(entry.raised_ms, &mut time);
+                HistoryRow {
+                    message: alerts.alerts[entry.alert].rule.message,
+                    time,
+                    priority: alerts.alerts[entry.alert].rule.priority,
+                    active: entry.cleared_ms.is_none(),
+                }
+            })
+            .collect();
+        replace(&mut self.rows, rows) != self.rows
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, context: &Context) {
+        let rows = self.rows.iter().map(|row| {
+            let color = match (row.active, row.priority) {
+                (false, _) => context.gauge_color,
+                (true, Priority::Warning) => context.caution_light_style.fill_color.unwrap(),
+                (true, Priority::Critical) => context.danger_light_style.fill_color.unwrap(),
+            };
+            (row.time.as_str(), row.message, color)
+        });
+        draw_rows(framebuffer, bounds, "ALERTS", rows, context);
+        if self.rows.is_empty() {
+            Text::with_text_style(
+                "None so far",
+                bounds.center(),
+                MonoTextStyle::new(&FONT_8X13, context.gauge_color),
+                TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Middle).build(),
+            )
+            .draw(framebuffer)
+            .unwrap();
+        }
+    }
+}
+
+/// Codes shown, each takes a line for the code and one for its description.
+const DTC_ROWS: usize = 4;
+const DTC_ROW_HEIGHT: i32 = 26;
+
+/// The trouble codes the ECUs reported, and the prompt while a clear waits for confirmation.
+#[derive(Default)]
+pub struct DtcPanel {
+    dtcs: DtcList,
+    clear_armed: bool,
+}
+
+impl DtcPanel {
+    fn update(&mut self, state: &CarState, now_ms: u64) -> bool {
+        let clear_armed = state.dtc_clear_armed(now_ms);
+        let changed = self.dtcs != *state.dtcs() || self.clear_armed != clear_armed;
+        if changed {
+            self.dtcs = state.dtcs().clone();
+            self.clear_armed = clear_armed;
+        }
+        changed
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, context: &Context) {
+        let text_style = |alignment| TextStyleBuilder::new().alignment(alignment).baseline(Baseline::Top).build();
+        let danger = context.danger_light_style.fill_color.unwrap();
+        draw_title(framebuffer, bounds, "CODES", context);
+        let right = bounds.top_left.x + bounds.size.width as i32 - 1;
+        let bottom = bounds.top_left.y + bounds.size.height as i32 - 13;
+        for (row, (kind, dtc)) in self.dtcs.codes.iter().take(DTC_ROWS).enumerate() {
+            let y = bounds.top_left.y + 28 + row as i32 * DTC_ROW_HEIGHT;
+            let color = match kind {
+                DtcKind::Pending => context.caution_light_style.fill_color.unwrap(),
+                _ => danger,
+            };
+            let mut code = String::<5>::new();
+            let _ = write!(code, "{}", dtc);
+            Text::with_text_style(&code, Point::new(bounds.top_left.x, y), MonoTextStyle::new(&FONT_8X13, color), text_style(Alignment::Left))
+                .draw(framebuffer)
+                .unwrap();
+            Text::with_text_style(
+                kind.label(),
+                Point::new(right, y),
+                MonoTextStyle::new(&FONT_8X13, context.gauge_color),
+                text_style(Alignment::Right),
+            )
+            .draw(framebuffer)
+            .unwrap();
+            Text::with_text_style(
+                dtc.description().unwrap_or(""),
+                Point::new(bounds.top_left.x, y + 14),
+                MonoTextStyle::new(&FONT_6X9, Rgb565::WHITE),
+                text_style(Alignment::Left),
+            )
+            .draw(framebuffer)
+            .unwrap();
+        }
+        let mut footer = String::<24>::new();
+        let footer_color = if self.clear_armed {
+            let _ = footer.push_str("Hold again to clear");
+            danger
+        } else if self.dtcs.is_empty() {
+            let _ = footer.push_str("No codes");
+            context.gauge_color
+        } else {
+            if self.dtcs.len() > DTC_ROWS {
+                let _ = write!(footer, "+{} more", self.dtcs.len() - DTC_ROWS);
+            }
+            context.gauge_color
+        };
+        Text::with_text_style(
+            &footer,
+            Point::new(bounds.center().x, bottom),
+            MonoTextStyle::new(&FONT_8X13, footer_color),
+            text_style(Alignment::Center),
+        )
+        .draw(framebuffer)
+        .unwrap();
+    }
+}
+
+pub enum WidgetKind {
+    /// The full size speedo, its bounds have to match its size.
+    MainGauge(ScreenGauge),
+    SubDial(Dial),
+    BarGraph(BarGraph),
+    Readout(Readout),
+    Telltale(Telltale),
+    /// Reads the trip counters rather than its field, which should be the speed they come from.
+    TripComputer(TripPanel),
+    /// These ignore the field as well.
+    Diagnostics(DiagnosticsPanel),
+    AlertHistory(AlertHistoryPanel),
+    TroubleCodes(DtcPanel),
+}
+
+pub struct Widget {
+    pub bounds: Rectangle,
+    pub field: CarField,
+    pub kind: WidgetKind,
+    /// Something visible changed since the last `draw`.
+    changed: bool,
+    /// The field is late, its value is drawn greyed out.
+    late: bool,
+    /// Where the main gauge's needle was drawn last.
+    needle: Option<Rectangle>,
+}
+
+impl Widget {
+    pub fn new(top_left: Point, size: Size, field: CarField, kind: WidgetKind) -> Self {
+        Widget {
+            bounds: Rectangle::new(top_left, size),
+            field,
+            kind,
+            changed: true,
+            late: false,
+            needle: None,
+        }
+    }
+
+    pub fn draw_static<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, context: &Context) {
+        if let WidgetKind::MainGauge(gauge) = &self.kind {
+            gauge.draw_static(&mut framebuffer.cropped(&self.bounds), context);
+        }
+    }
+
+    pub fn update(&mut self, state: &CarState, now_ms: u64) {
+        let freshness = state.freshness(self.field, now_ms);
+        let value = state.get(self.field).filter(|_| freshness != Freshness::Lost);
+        let late = freshness == Freshness::Late;
+        self.changed |= replace(&mut self.late, late) != late;
+        let changed = match &mut self.kind {
+            WidgetKind::MainGauge(gauge) => {
+                let previous = gauge.indicated_value();
+                gauge.set_value(value.unwrap_or(gauge.scale.min));
+                gauge.update_indicated(now_ms);
+                gauge.indicated_value() != previous
+            }
+            WidgetKind::SubDial(dial) => replace(&mut dial.value, value.unwrap_or(dial.min)) != dial.value,
+            WidgetKind::BarGraph(bar) => replace(&mut bar.value, value.unwrap_or(bar.min)) != bar.value,
+            WidgetKind::Readout(readout) => readout.update(value),
+            WidgetKind::Telltale(telltale) => telltale.update(value, now_ms),
+            WidgetKind::TripComputer(panel) => panel.update(state),
+            WidgetKind::Diagnostics(panel) => panel.update(state),
+            WidgetKind::AlertHistory(_) => false,
+            WidgetKind::TroubleCodes(panel) => panel.update(state, now_ms),
+        };
+        self.changed |= changed;
+    }
+
+    /// Lets the alert history see the alerts, after `update`.
+    pub fn update_alerts(&mut self, alerts: &AlertEngine) {
+        if let WidgetKind::AlertHistory(panel) = &mut self.kind {
+            self.changed |= panel.update(alerts);
+        }
+    }
+
+    /// Draws what changed since the last call, or everything when `force` is set because
+    /// something else drew over this widget. Returns the area that was redrawn.
+    pub fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(
+        &mut self,
+        framebuffer: &mut D,
+        context: &Context,
+        force: bool,
+    ) -> Option<Rectangle> {
+        if !self.changed && !force {
+            return None;
+        }
+        self.changed = false;
+        let clear = |framebuffer: &mut D| framebuffer.fill_solid(&self.bounds, context.back_color).unwrap();
+        let value_color = |color| if self.late { STALE_COLOR } else { color };
+        match &self.kind {
+            WidgetKind::MainGauge(gauge) => {
+                let needle = gauge.needle_bounds(gauge.indicated_value());
+                // Only the old and the new needle position have to be repainted
+                let area = match self.needle {
+                    Some(previous) if !force => damage::union(&previous, &needle),
+                    _ => Rectangle::new(Point::zero(), self.bounds.size),
+                };
+                self.needle = Some(needle);
+                gauge.draw_region(&mut framebuffer.cropped(&self.bounds), context, &area);
+                return Some(area.translate(self.bounds.top_left));
+            }
+            WidgetKind::SubDial(dial) => {
+                clear(framebuffer);
+                dial.draw(framebuffer, self.bounds, context, value_color(context.needle_color));
+            }
+            WidgetKind::BarGraph(bar) => {
+                clear(framebuffer);
+                bar.draw(framebuffer, self.bounds, context, value_color(Rgb565::WHITE));
+            }
+            WidgetKind::Readout(readout) => {
+                clear(framebuffer);
+                readout.draw(framebuffer, self.bounds, value_color(Rgb565::WHITE));
+            }
+            WidgetKind::Telltale(telltale) => {
+                clear(framebuffer);
+                telltale.draw(framebuffer, self.bounds, context);
+            }
+            WidgetKind::TripComputer(panel) => {
+                clear(framebuffer);
+                panel.draw(framebuffer, self.bounds, context);
+            }
+            WidgetKind::Diagnostics(panel) => {
+                clear(framebuffer);
+                panel.draw(framebuffer, self.bounds, context);
+            }
+            WidgetKind::AlertHistory(panel) => {
+                clear(framebuffer);
+                panel.draw(framebuffer, self.bounds, context);
+            }
+            WidgetKind::TroubleCodes(panel) => {
+                clear(framebuffer);
+                panel.draw(framebuffer, self.bounds, context);
+            }
+        }
+        Some(self.bounds)
+    }
+}
+
+fn telltale(x: i32, y: i32, field: CarField, icon: Icon) -> Widget {
+    Widget::new(Point::new(x, y), Size::new(12, 12), field, WidgetKind::Telltale(Telltale::new(icon)))
+}
+
+/// Speedo round the edge, speed and revs in the middle, coolant temperature and
+/// fuel in the gap at the bottom of the dial, with a row of telltales above and below the fuel bar.
+pub fn default_layout() -> Vec<Widget> {
+    vec![
+        Widget::new(
+            Point::zero(),
+            Size::new(240, 240),
+            CarField::VehicleSpeed,
+            WidgetKind::MainGauge(Gauge::new(Scale::speedo())),
+        ),
+        Widget::new(
+            Point::new(80, 100),
+            Size::new(80, 22),
+            CarField::VehicleSpeed,
+            WidgetKind::Readout(Readout::new(0, "", &FONT_10X20)),
+        ),
+        Widget::new(
+            Point::new(80, 124),
+            Size::new(80, 14),
+            CarField::EngineRpm,
+            WidgetKind::Readout(Readout::new(0, "rpm", &FONT_8X13)),
+        ),
+        Widget::new(
+            Point::new(103, 158),
+            Size::new(34, 34),
+            CarField::CoolantTemp,
+            WidgetKind::SubDial(Dial::new(40.0, 130.0, "C")),
+        ),
+        Widget::new(
+            Point::new(92, 206),
+            Size::new(56, 8),
+            CarField::FuelLevel,
+            WidgetKind::BarGraph(BarGraph::new(0.0, 100.0)),
+        ),
+        telltale(78, 193, CarField::TurnLeft, Icon::TurnLeft),
+        telltale(96, 193, CarField::LowBeam, Icon::LowBeam),
+        telltale(114, 193, CarField::HighBeam, Icon::HighBeam),
+        telltale(132, 193, CarField::SeatbeltWarning, Icon::Seatbelt),
+        telltale(150, 193, CarField::TurnRight, Icon::TurnRight),
+        telltale(84, 217, CarField::OilPressureWarning, Icon::OilPressure),
+        telltale(99, 217, CarField::ChargeWarning, Icon::Battery),
+        telltale(114, 217, CarField::CheckEngine, Icon::CheckEngine),
+        telltale(129, 217, CarField::AbsWarning, Icon::Abs),
+        telltale(144, 217, CarField::CoolantTemp, Icon::CoolantTemp),
+    ]
+}
+
+/// The counters of one trip, filling the display.
+pub fn trip_layout(trip: TripId, unit: EconomyUnit) -> Vec<Widget> {
+    vec![Widget::new(
+        Point::new(40, 38),
+        Size::new(160, 156),
+        CarField::VehicleSpeed,
+        WidgetKind::TripComputer(TripPanel::new(trip, unit)),
+    )]
+}
+
+/// Frames received, battery voltage and frame rate.
+pub fn diagnostics_layout() -> Vec<Widget> {
+    vec![Widget::new(
+        Point::new(40, 30),
+        Size::new(160, 172),
+        CarField::VehicleSpeed,
+        WidgetKind::Diagnostics(DiagnosticsPanel::new()),
+    )]
+}
+
+/// The last alerts with the time they went off.
+pub fn alert_history_layout() -> Vec<Widget> {
+    vec![Widget::new(
+        Point::new(40, 38),
+        Size::new(160, 156),
+        CarField::VehicleSpeed,
+        WidgetKind::AlertHistory(AlertHistoryPanel::default()),
+    )]
+}
+
+/// Stored, pending and permanent trouble codes.
+pub fn trouble_codes_layout() -> Vec<Widget> {
+    vec![Widget::new(
+        Point::new(40, 38),
+        Size::new(160, 156),
+        CarField::CheckEngine,
+        WidgetKind::TroubleCodes(DtcPanel::default()),
+    )]
+}
@@ -0,0 +1,33 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod alert;
+pub mod antialias;
+pub mod bitrate;
+pub mod bus_health;
+pub mod car_state;
+pub mod damage;
+pub mod dtc;
+pub mod dbc;
+pub mod filter;
+pub mod frame;
+pub mod framebuffer;
+pub mod freshness;
+pub mod gauge;
+pub mod isotp;
+pub mod layout;
+pub mod needle;
+pub mod obd;
+pub mod page;
+pub mod replay;
+pub mod ring;
+pub mod scale;
+pub mod scheduler;
+pub mod screen;
+pub mod settings;
+pub mod source;
+pub mod store;
+pub mod telltale;
+pub mod trip;
+pub mod voltage;
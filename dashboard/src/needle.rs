@@ -0,0 +1,73 @@
+//! How a needle follows its value over time: a mass on a spring with a damper, so
+//! it swings over and settles like a real instrument, at the same speed whatever
+//! the frame rate.
+use num_traits::Float;
+
+/// Longer steps are split up, explicit integration of a stiff spring blows up otherwise.
+const MAX_STEP_SECONDS: f32 = 0.004;
+/// After a stall the needle just catches up over this much time, instead of integrating all of it.
+const MAX_ELAPSED_SECONDS: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response {
+    /// Shows the value straight away.
+    Snap,
+    /// Accelerates by `stiffness` per second squared for every unit it is off, and is
+    /// slowed by `damping` per second for every unit per second it moves.
+    Spring { stiffness: f32, damping: f32 },
+}
+
+impl Response {
+    /// A spring oscillating at `frequency` radians per second undamped. A `damping_ratio`
+    /// of 1 settles fastest without overshooting, below that it swings past the value.
+    pub fn spring(frequency: f32, damping_ratio: f32) -> Self {
+        Response::Spring {
+            stiffness: frequency * frequency,
+            damping: 2.0 * damping_ratio * frequency,
+        }
+    }
+}
+
+impl Default for Response {
+    /// Settles in about half a second with a little overshoot.
+    fn default() -> Self {
+        Response::spring(12.0, 0.6)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Needle {
+    pub response: Response,
+    pub position: f32,
+    /// Units per second.
+    pub velocity: f32,
+}
+
+impl Needle {
+    pub fn new(response: Response, position: f32) -> Self {
+        Needle { response, position, velocity: 0.0 }
+    }
+
+    /// Moves `elapsed` seconds further towards `target`.
+    pub fn step(&mut self, target: f32, elapsed: f32) {
+        let Response::Spring { stiffness, damping } = self.response else {
+            self.settle(target);
+            return;
+        };
+        let elapsed = elapsed.clamp(0.0, MAX_ELAPSED_SECONDS);
+        let steps = Float::ceil(elapsed / MAX_STEP_SECONDS).max(1.0);
+        let dt = elapsed / steps;
+        for _ in 0..steps as u32 {
+            // Semi-implicit Euler: the new velocity moves the needle, that keeps the energy bounded
+            let acceleration = stiffness * (target - self.position) - damping * self.velocity;
+            self.velocity += acceleration * dt;
+            self.position += self.velocity * dt;
+        }
+    }
+
+    /// Puts the needle on `value`, at rest.
+    pub fn settle(&mut self, value: f32) {
+        self.position = value;
+        self.velocity = 0.0;
+    }
+}
@@ -0,0 +1,268 @@
+use embedded_can::{Frame, Id, StandardId};
+use heapless::{Deque, String};
+
+use crate::dtc::{DtcKind, DtcRequest};
+use crate::isotp;
+
+/// Functional (broadcast) request address, every OBD-II capable ECU listens to it.
+pub const FUNCTIONAL_REQUEST_ID: u16 = 0x7DF;
+/// Physical response addresses, one per responding ECU (engine is usually 0x7E8).
+pub const RESPONSE_ID_FIRST: u16 = 0x7E8;
+pub const RESPONSE_ID_LAST: u16 = 0x7EF;
+
+const MODE_CURRENT_DATA: u8 = 0x01;
+const MODE_VEHICLE_INFO: u8 = 0x09;
+const POSITIVE_RESPONSE: u8 = 0x40;
+const PID_VIN: u8 = 0x02;
+pub const VIN_LEN: usize = 17;
+
+/// How long we wait for a reply before moving on to the next PID.
+const RESPONSE_TIMEOUT_MS: u64 = 100;
+/// Minimum gap between two requests, keeps the bus load from polling low.
+const REQUEST_INTERVAL_MS: u64 = 20;
+
+/// The Mode 01 PIDs the dashboard knows how to decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pid {
+    CoolantTemp,
+    EngineRpm,
+    VehicleSpeed,
+    IntakeAirTemp,
+    MafAirFlow,
+    ThrottlePosition,
+    FuelLevel,
+    FuelRate,
+}
+
+impl Pid {
+    pub const ALL: [Pid; 8] = [
+        Pid::EngineRpm,
+        Pid::VehicleSpeed,
+        Pid::CoolantTemp,
+        Pid::ThrottlePosition,
+        Pid::MafAirFlow,
+        Pid::IntakeAirTemp,
+        Pid::FuelLevel,
+        Pid::FuelRate,
+    ];
+
+    pub fn code(self) -> u8 {
+        match self {
+            Pid::CoolantTemp => 0x05,
+            Pid::EngineRpm => 0x0C,
+            Pid::VehicleSpeed => 0x0D,
+            Pid::IntakeAirTemp => 0x0F,
+            Pid::MafAirFlow => 0x10,
+            Pid::ThrottlePosition => 0x11,
+            Pid::FuelLevel => 0x2F,
+            Pid::FuelRate => 0x5E,
+        }
+    }
+
+    /// How often the value is asked for, the needles want the engine speed and road
+    /// speed far more often than the fuel gauge needs its level.
+    pub fn period_ms(self) -> u64 {
+        match self {
+            Pid::EngineRpm | Pid::VehicleSpeed => 100,
+            Pid::ThrottlePosition => 200,
+            Pid::MafAirFlow => 250,
+            Pid::FuelRate => 500,
+            Pid::CoolantTemp | Pid::IntakeAirTemp => 2000,
+            Pid::FuelLevel => 5000,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|pid| pid.code() == code)
+    }
+
+    /// Number of data bytes (A, B, ...) in the response.
+    fn data_len(self) -> usize {
+        match self {
+            Pid::EngineRpm | Pid::MafAirFlow | Pid::FuelRate => 2,
+            _ => 1,
+        }
+    }
+
+    /// Decode the data bytes using the SAE J1979 formulas.
+    /// Units: rpm, km/h, °C, g/s, percent and l/h.
+    pub fn decode(self, data: &[u8]) -> Option<f32> {
+        if data.len() < self.data_len() {
+            return None;
+        }
+        let a = data[0] as f32;
+        let value = match self {
+            Pid::CoolantTemp | Pid::IntakeAirTemp => a - 40.0,
+            Pid::EngineRpm => u16::from_be_bytes([data[0], data[1]]) as f32 / 4.0,
+            Pid::VehicleSpeed => a,
+            Pid::MafAirFlow => u16::from_be_bytes([data[0], data[1]]) as f32 / 100.0,
+            Pid::ThrottlePosition | Pid::FuelLevel => a * 100.0 / 255.0,
+            Pid::FuelRate => u16::from_be_bytes([data[0], data[1]]) as f32 / 20.0,
+        };
+        Some(value)
+    }
+}
+
+/// A decoded Mode 01 reply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidReading {
+    /// Index of the responding ECU, 0 for 0x7E8 up to 7 for 0x7EF.
+    pub ecu: u8,
+    pub pid: Pid,
+    pub value: f32,
+}
+
+/// Returns the ECU index if `id` is one of the OBD-II response addresses.
+pub fn response_ecu(id: Id) -> Option<u8> {
+    match id {
+        Id::Standard(id) if (RESPONSE_ID_FIRST..=RESPONSE_ID_LAST).contains(&id.as_raw()) => {
+            Some((id.as_raw() - RESPONSE_ID_FIRST) as u8)
+        }
+        _ => None,
+    }
+}
+
+/// Build a functional Mode 01 request for `pid`.
+pub fn request_frame<F: Frame>(pid: Pid) -> Option<F> {
+    let id = StandardId::new(FUNCTIONAL_REQUEST_ID)?;
+    isotp::single_frame(id, &[MODE_CURRENT_DATA, pid.code()])
+}
+
+/// Build a functional Mode 09 request for the vehicle identification number.
+/// The 20 byte answer only fits in a multi-frame ISO-TP reply.
+pub fn vin_request_frame<F: Frame>() -> Option<F> {
+    let id = StandardId::new(FUNCTIONAL_REQUEST_ID)?;
+    isotp::single_frame(id, &[MODE_VEHICLE_INFO, PID_VIN])
+}
+
+/// Parse a reply payload (mode byte onwards).
+pub fn parse_payload(ecu: u8, payload: &[u8]) -> Option<PidReading> {
+    match payload {
+        [mode, pid, data @ ..] if *mode == MODE_CURRENT_DATA + POSITIVE_RESPONSE => {
+            let pid = Pid::from_code(*pid)?;
+            let value = pid.decode(data)?;
+            Some(PidReading { ecu, pid, value })
+        }
+        _ => None,
+    }
+}
+
+/// Parse a Mode 09 VIN reply payload: mode, PID, item count and 17 ASCII characters.
+pub fn parse_vin(payload: &[u8]) -> Option<String<VIN_LEN>> {
+    match payload {
+        [mode, PID_VIN, _count, vin @ ..] if *mode == MODE_VEHICLE_INFO + POSITIVE_RESPONSE && vin.len() >= VIN_LEN => {
+            let vin = core::str::from_utf8(&vin[..VIN_LEN]).ok()?;
+            String::try_from(vin).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Mode 01 poller: one request in flight at a time, moving on when the reply
+/// arrives or after a timeout. Each PID is asked for at its own `Pid::period_ms`,
+/// the most overdue first, a tie goes to the PID listed first. The VIN is asked for
+/// once up front, queued trouble code requests go before the next PID.
+#[derive(Debug, Clone)]
+pub struct ObdClient {
+    pids: &'static [Pid],
+    vin_requested: bool,
+    dtc_requests: Deque<DtcRequest, 4>,
+    /// When each PID is due next, by `Pid as usize`.
+    due_ms: [u64; Pid::ALL.len()],
+    outstanding: Option<Pid>,
+    last_request_ms: u64,
+    timeouts: usize,
+}
+
+impl Default for ObdClient {
+    fn default() -> Self {
+        Self::new(&Pid::ALL)
+    }
+}
+
+impl ObdClient {
+    pub fn new(pids: &'static [Pid]) -> Self {
+        let mut client = ObdClient {
+            pids,
+            vin_requested: false,
+            dtc_requests: Deque::new(),
+            due_ms: [0; Pid::ALL.len()],
+            outstanding: None,
+            last_request_ms: 0,
+            timeouts: 0,
+        };
+        client.read_dtcs();
+        client
+    }
+
+    /// Returns the next request frame to transmit, if one is due at `now_ms`.
+    pub fn poll<F: Frame>(&mut self, now_ms: u64) -> Option<F> {
+        if self.pids.is_empty() {
+            return None;
+        }
+        let elapsed = now_ms.saturating_sub(self.last_request_ms);
+        if self.outstanding.is_some() {
+            if elapsed < RESPONSE_TIMEOUT_MS {
+                return None;
+            }
+            self.timeouts += 1;
+            self.outstanding = None;
+        }
+        if elapsed < REQUEST_INTERVAL_MS {
+            return None;
+        }
+        if !self.vin_requested {
+            self.vin_requested = true;
+            self.last_request_ms = now_ms;
+            return vin_request_frame();
+        }
+        if let Some(request) = self.dtc_requests.pop_front() {
+            self.last_request_ms = now_ms;
+            return request.frame();
+        }
+        let pid = self
+            .pids
+            .iter()
+            .copied()
+            .filter(|pid| self.due_ms[*pid as usize] <= now_ms)
+            .min_by_key(|pid| self.due_ms[*pid as usize])?;
+        self.due_ms[pid as usize] = now_ms + pid.period_ms();
+        self.outstanding = Some(pid);
+        self.last_request_ms = now_ms;
+        request_frame(pid)
+    }
+
+    /// Asks for all three kinds of trouble codes.
+    pub fn read_dtcs(&mut self) {
+        for kind in DtcKind::ALL {
+            self.queue(DtcRequest::Read(kind));
+        }
+    }
+
+    pub fn clear_dtcs(&mut self) {
+        self.queue(DtcRequest::Clear);
+    }
+
+    /// The same request twice in the queue would only be answered twice.
+    fn queue(&mut self, request: DtcRequest) {
+        if !self.dtc_requests.iter().any(|queued| *queued == request) {
+            let _ = self.dtc_requests.push_back(request);
+        }
+    }
+
+    /// Marks the outstanding request as answered.
+    pub fn on_reading(&mut self, reading: &PidReading) {
+        if self.outstanding == Some(reading.pid) {
+            self.outstanding = None;
+        }
+    }
+
+    /// The PID asked for last, until it is answered or times out.
+    pub fn outstanding(&self) -> Option<Pid> {
+        self.outstanding
+    }
+
+    pub fn timeouts(&self) -> usize {
+        self.timeouts
+    }
+}
@@ -0,0 +1,108 @@
+//! The pages the button steps through, and which one is on screen.
+
+use alloc::vec::Vec;
+
+use crate::{
+    layout::{self, Widget},
+    screen::{Screen, Transition},
+    trip::{EconomyUnit, TripId},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Gauges,
+    Trip(TripId),
+    Diagnostics,
+    TroubleCodes,
+    AlertHistory,
+}
+
+impl Page {
+    /// In the order the button steps through them.
+    pub const ALL: [Page; 6] = [
+        Page::Gauges,
+        Page::Trip(TripId::A),
+        Page::Trip(TripId::B),
+        Page::Diagnostics,
+        Page::TroubleCodes,
+        Page::AlertHistory,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Page::Gauges => "gauges",
+            Page::Trip(TripId::A) => "trip-a",
+            Page::Trip(TripId::B) => "trip-b",
+            Page::Diagnostics => "diagnostics",
+            Page::TroubleCodes => "codes",
+            Page::AlertHistory => "alerts",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Page::ALL.into_iter().find(|page| page.name() == name)
+    }
+
+    /// How the page is stored in the settings.
+    pub fn index(self) -> u8 {
+        Page::ALL.iter().position(|page| *page == self).unwrap() as u8
+    }
+
+    /// Unknown indices, from a newer or older firmware, are the gauges.
+    pub fn from_index(index: u8) -> Self {
+        Page::ALL.get(index as usize).copied().unwrap_or(Page::Gauges)
+    }
+
+    pub fn layout(self, unit: EconomyUnit) -> Vec<Widget> {
+        match self {
+            Page::Gauges => layout::default_layout(),
+            Page::Trip(trip) => layout::trip_layout(trip, unit),
+            Page::Diagnostics => layout::diagnostics_layout(),
+            Page::TroubleCodes => layout::trouble_codes_layout(),
+            Page::AlertHistory => layout::alert_history_layout(),
+        }
+    }
+}
+
+/// Moves between pages, wiping the next one in from the side it comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageManager {
+    page: Page,
+    unit: EconomyUnit,
+}
+
+impl PageManager {
+    pub fn new(page: Page, unit: EconomyUnit) -> Self {
+        PageManager { page, unit }
+    }
+
+    pub fn current(&self) -> Page {
+        self.page
+    }
+
+    /// A screen showing the current page.
+    pub fn screen(&self) -> Screen {
+        Screen::with_layout(self.page.layout(self.unit))
+    }
+
+    /// After the last page comes the first.
+    pub fn next(&mut self, screen: &mut Screen) {
+        let index = (self.page.index() as usize + 1) % Page::ALL.len();
+        self.switch(screen, Page::ALL[index], Transition::WipeLeft);
+    }
+
+    pub fn previous(&mut self, screen: &mut Screen) {
+        let index = (self.page.index() as usize + Page::ALL.len() - 1) % Page::ALL.len();
+        self.switch(screen, Page::ALL[index], Transition::WipeRight);
+    }
+
+    /// Goes straight to `page`, without a transition.
+    pub fn jump(&mut self, screen: &mut Screen, page: Page) {
+        self.switch(screen, page, Transition::Cut);
+    }
+
+    fn switch(&mut self, screen: &mut Screen, page: Page, transition: Transition) {
+        self.page = page;
+        screen.show(page.layout(self.unit), transition);
+    }
+}
@@ -0,0 +1,275 @@
+//! Replay of recorded CAN traffic.
+//!
+//! Two text formats are understood, both parsed lazily from an in-memory `&str`:
+//! * candump log files (`candump -l`): `(1436509052.249713) can0 123#DEADBEEF`
+//! * Vector ASC files: `   0.010000 1  123             Rx   d 8 01 02 03 04 05 06 07 08`
+//!
+//! Timestamps are made relative to the first frame of the log.
+use embedded_can::{ExtendedId, Frame, Id, StandardId};
+use embedded_hal_async::delay::DelayNs;
+
+use crate::{
+    frame::CanFrame,
+    isotp::{Event, FlowControl, IsoTpLayer, IsoTpMessage},
+    source::{CanEvent, FrameSource},
+};
+
+/// One recorded frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Microseconds since the first frame in the log.
+    pub timestamp_us: u64,
+    pub frame: CanFrame,
+}
+
+/// Parses `seconds.fraction` into microseconds without going through floats.
+fn parse_seconds(text: &str) -> Option<u64> {
+    let (seconds, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let mut micros: u64 = 0;
+    for position in 0..6 {
+        let digit = match fraction.as_bytes().get(position) {
+            Some(digit) => (*digit as char).to_digit(10)?,
+            None => 0,
+        };
+        micros = micros * 10 + digit as u64;
+    }
+    Some(seconds.parse::<u64>().ok()? * 1_000_000 + micros)
+}
+
+fn parse_id(text: &str, radix: u32, extended: bool) -> Option<Id> {
+    let raw = u32::from_str_radix(text, radix).ok()?;
+    if extended {
+        ExtendedId::new(raw).map(Id::Extended)
+    } else {
+        u16::try_from(raw).ok().and_then(StandardId::new).map(Id::Standard)
+    }
+}
+
+/// Turns absolute timestamps into offsets from the first record.
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeBase {
+    first_us: Option<u64>,
+}
+
+impl TimeBase {
+    fn relative(&mut self, timestamp_us: u64) -> u64 {
+        let first = *self.first_us.get_or_insert(timestamp_us);
+        timestamp_us.saturating_sub(first)
+    }
+}
+
+/// Iterator over the frames in a candump log. CAN FD and malformed lines are skipped.
+#[derive(Debug, Clone)]
+pub struct CandumpLog<'a> {
+    lines: core::str::Lines<'a>,
+    time_base: TimeBase,
+}
+
+impl<'a> CandumpLog<'a> {
+    pub fn new(text: &'a str) -> Self {
+        CandumpLog {
+            lines: text.lines(),
+            time_base: TimeBase::default(),
+        }
+    }
+
+    fn parse_line(line: &str) -> Option<(u64, CanFrame)> {
+        // (timestamp) interface id#data
+        let mut parts = line.split_whitespace();
+        let timestamp = parts.next()?.strip_prefix('(')?.strip_suffix(')')?;
+        let _interface = parts.next()?;
+        let (id, data) = parts.next()?.split_once('#')?;
+        // Standard ids are written with 3 hex digits, extended ones with 8
+        let id = parse_id(id, 16, id.len() > 3)?;
+        let frame = if let Some(dlc) = data.strip_prefix('R') {
+            CanFrame::new_remote(id, dlc.parse().unwrap_or(0))?
+        } else {
+            if data.starts_with('#') || data.len() % 2 != 0 || data.len() > 16 {
+                return None;
+            }
+            let mut bytes = [0u8; 8];
+            for (index, byte) in bytes.iter_mut().take(data.len() / 2).enumerate() {
+                *byte = u8::from_str_radix(&data[index * 2..index * 2 + 2], 16).ok()?;
+            }
+            CanFrame::new(id, &bytes[..data.len() / 2])?
+        };
+        Some((parse_seconds(timestamp)?, frame))
+    }
+}
+
+impl Iterator for CandumpLog<'_> {
+    type Item = LogRecord;
+
+    fn next(&mut self) -> Option<LogRecord> {
+        for line in self.lines.by_ref() {
+            if let Some((timestamp_us, frame)) = Self::parse_line(line) {
+                return Some(LogRecord {
+                    timestamp_us: self.time_base.relative(timestamp_us),
+                    frame,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the classic CAN frames in a Vector ASC file. Honours the
+/// `base hex|dec` and `timestamps absolute|relative` header, skips events,
+/// error frames and CAN FD lines.
+#[derive(Debug, Clone)]
+pub struct AscLog<'a> {
+    lines: core::str::Lines<'a>,
+    time_base: TimeBase,
+    radix: u32,
+    relative: bool,
+    previous_us: u64,
+}
+
+impl<'a> AscLog<'a> {
+    pub fn new(text: &'a str) -> Self {
+        AscLog {
+            lines: text.lines(),
+            time_base: TimeBase::default(),
+            radix: 16,
+            relative: false,
+            previous_us: 0,
+        }
+    }
+
+    fn parse_header(&mut self, line: &str) {
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some("base") {
+            return;
+        }
+        if parts.next() == Some("dec") {
+            self.radix = 10;
+        }
+        if parts.next() == Some("timestamps") {
+            self.relative = parts.next() == Some("relative");
+        }
+    }
+
+    fn parse_line(&self, line: &str) -> Option<(u64, CanFrame)> {
+        // timestamp channel id direction d|r dlc data...
+        let mut parts = line.split_whitespace();
+        let timestamp = parse_seconds(parts.next()?)?;
+        parts.next()?.parse::<u8>().ok()?;
+        let id = parts.next()?;
+        let (id, extended) = match id.strip_suffix('x') {
+            Some(id) => (id, true),
+            None => (id, false),
+        };
+        let id = parse_id(id, self.radix, extended)?;
+        match parts.next()? {
+            "Rx" | "Tx" => {}
+            _ => return None,
+        }
+        let kind = parts.next()?;
+        let dlc: usize = parts.next()?.parse().ok()?;
+        let frame = match kind {
+            "r" => CanFrame::new_remote(id, dlc)?,
+            "d" if dlc <= 8 => {
+                let mut bytes = [0u8; 8];
+                for byte in bytes.iter_mut().take(dlc) {
+                    *byte = u8::from_str_radix(parts.next()?, self.radix).ok()?;
+                }
+                CanFrame::new(id, &bytes[..dlc])?
+            }
+            _ => return None,
+        };
+        Some((timestamp, frame))
+    }
+}
+
+impl Iterator for AscLog<'_> {
+    type Item = LogRecord;
+
+    fn next(&mut self) -> Option<LogRecord> {
+        while let Some(line) = self.lines.next() {
+            let Some((timestamp_us, frame)) = self.parse_line(line) else {
+                self.parse_header(line);
+                continue;
+            };
+            let timestamp_us = if self.relative {
+                self.previous_us += timestamp_us;
+                self.previous_us
+            } else {
+                timestamp_us
+            };
+            return Some(LogRecord {
+                timestamp_us: self.time_base.relative(timestamp_us),
+                frame,
+            });
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Stop at the end of the log.
+    Once,
+    /// Start over from the first frame at the end of the log.
+    Loop,
+}
+
+/// Plays a log back as a [`FrameSource`], waiting out the recorded gaps between
+/// frames. Diagnostic replies are reassembled like on the live bus, flow control
+/// frames are not sent anywhere.
+pub struct ReplaySource<L, D> {
+    log: L,
+    current: L,
+    delay: D,
+    mode: ReplayMode,
+    previous_us: u64,
+    isotp: IsoTpLayer,
+    pending: Option<IsoTpMessage>,
+}
+
+impl<L: Iterator<Item = LogRecord> + Clone, D: DelayNs> ReplaySource<L, D> {
+    pub fn new(log: L, delay: D, mode: ReplayMode) -> Self {
+        ReplaySource {
+            current: log.clone(),
+            log,
+            delay,
+            mode,
+            previous_us: 0,
+            isotp: IsoTpLayer::obd(FlowControl::default()),
+            pending: None,
+        }
+    }
+
+    fn next_record(&mut self) -> Option<LogRecord> {
+        if let Some(record) = self.current.next() {
+            return Some(record);
+        }
+        if self.mode == ReplayMode::Once {
+            return None;
+        }
+        self.current = self.log.clone();
+        self.previous_us = 0;
+        // The clock goes back to the start, a reply cut off at the end of the log would never time out
+        self.isotp = IsoTpLayer::obd(FlowControl::default());
+        self.current.next()
+    }
+}
+
+impl<L: Iterator<Item = LogRecord> + Clone, D: DelayNs> FrameSource for ReplaySource<L, D> {
+    type Frame = CanFrame;
+
+    async fn next_event(&mut self) -> Option<CanEvent<CanFrame>> {
+        if let Some(message) = self.pending.take() {
+            return Some(CanEvent::Message(message));
+        }
+        let record = self.next_record()?;
+        let gap_us = record.timestamp_us.saturating_sub(self.previous_us);
+        self.previous_us = record.timestamp_us;
+        if gap_us > 0 {
+            self.delay.delay_us(gap_us.min(u32::MAX as u64) as u32).await;
+        }
+        if let Some(Event::Message(message)) = self.isotp.on_frame(&record.frame, record.timestamp_us / 1000) {
+            self.pending = Some(message);
+        }
+        Some(CanEvent::Frame(record.frame))
+    }
+}
@@ -0,0 +1,247 @@
+//! A lock-free single producer, single consumer ring for received frames. The
+//! producer never waits: when the ring is full it makes room by the `DropPolicy`
+//! and counts what it lost, so a stalled consumer cannot hold up reception.
+//!
+//! Both sides move the read index (the consumer after taking a frame, the producer
+//! to drop the oldest), so it is only ever advanced by compare and swap. Slots carry
+//! a sequence number that is odd while being written, a read that overlapped a write
+//! is thrown away and tried again.
+//!
+//! Coalescing writes into a slot that is still queued. Before it does, the producer
+//! claims the slot (odd sequence) and checks the consumer has not got to it yet. If
+//! it has, the consumer may already hold the old frame and be about to take it, so the
+//! slot is left as it was and the new frame is queued at the end instead.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{fence, AtomicU32, AtomicUsize, Ordering},
+};
+
+use embedded_can::Frame;
+
+/// What to give up when a frame arrives at a full ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    DropOldest,
+    /// Overwrite the queued frame with the same ID, only the latest value of a signal
+    /// matters to the gauges. Falls back to dropping the oldest when there is none.
+    CoalesceById,
+}
+
+/// A frame and when it arrived, in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamped<F> {
+    pub frame: F,
+    pub at_us: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RingStats {
+    pub pushed: u32,
+    /// Lost for good, the oldest frame was dropped to make room.
+    pub dropped: u32,
+    /// Replaced by a newer frame with the same ID.
+    pub coalesced: u32,
+    /// The most frames that were waiting at once.
+    pub high_water: u32,
+}
+
+struct Slot<T> {
+    seq: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    const fn new() -> Self {
+        Slot { seq: AtomicU32::new(0), value: UnsafeCell::new(MaybeUninit::uninit()) }
+    }
+}
+
+pub struct FrameRing<F, const N: usize> {
+    slots: [Slot<Stamped<F>>; N],
+    /// Both indices only ever grow (wrapping), the slot is the index modulo `N`.
+    read: AtomicUsize,
+    /// Only the producer moves this one.
+    write: AtomicUsize,
+    policy: DropPolicy,
+    pushed: AtomicU32,
+    dropped: AtomicU32,
+    coalesced: AtomicU32,
+    high_water: AtomicU32,
+}
+
+// The producer and consumer handles keep each side to one user, the slots are only
+// accessed through the sequence protocol.
+unsafe impl<F: Send, const N: usize> Sync for FrameRing<F, N> {}
+
+impl<F: Frame + Copy, const N: usize> FrameRing<F, N> {
+    pub const fn new(policy: DropPolicy) -> Self {
+        FrameRing {
+            slots: [const { Slot::new() }; N],
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+            policy,
+            pushed: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+            coalesced: AtomicU32::new(0),
+            high_water: AtomicU32::new(0),
+        }
+    }
+
+    /// Hands out the two sides, the ring cannot be split again while they live.
+    pub fn split(&mut self) -> (Producer<'_, F, N>, Consumer<'_, F, N>) {
+        (Producer { ring: self }, Consumer { ring: self })
+    }
+
+    pub fn stats(&self) -> RingStats {
+        RingStats {
+            pushed: self.pushed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            high_water: self.high_water.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.write.load(Ordering::Acquire).wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Marks the slot as being written, a consumer reading it meanwhile tries again.
+    /// Returns the odd sequence number to hand to `release`.
+    fn claim(&self, index: usize) -> u32 {
+        let slot = &self.slots[index % N];
+        let seq = slot.seq.load(Ordering::Relaxed).wrapping_add(1);
+        slot.seq.store(seq, Ordering::Relaxed);
+        // Pairs with the fence in `pop`: a consumer that moves `read` onto this slot
+        // after the producer looked at `read` sees the odd sequence
+        fence(Ordering::SeqCst);
+        seq
+    }
+
+    /// Ends a claim, with a new value or leaving the old one.
+    fn release(&self, index: usize, seq: u32, value: Option<Stamped<F>>) {
+        let slot = &self.slots[index % N];
+        if let Some(value) = value {
+            // Only the producer writes, a consumer reading meanwhile sees the odd sequence
+            unsafe { ptr::write_volatile(slot.value.get(), MaybeUninit::new(value)) };
+        }
+        slot.seq.store(seq.wrapping_add(1), Ordering::Release);
+    }
+
+    fn store(&self, index: usize, value: Stamped<F>) {
+        let seq = self.claim(index);
+        self.release(index, seq, Some(value));
+    }
+
+    /// The queued frame with the same ID as `frame`, newest first.
+    fn find_same_id(&self, read: usize, write: usize, frame: &F) -> Option<usize> {
+        (read..write).rev().find(|index| {
+            // The producer wrote every queued slot itself, so it can read them as they are
+            let queued = unsafe { (*self.slots[index % N].value.get()).assume_init_ref() };
+            queued.frame.id() == frame.id()
+        })
+    }
+
+    fn push(&self, value: Stamped<F>) {
+        self.pushed.fetch_add(1, Ordering::Relaxed);
+        let write = self.write.load(Ordering::Relaxed);
+        loop {
+            let read = self.read.load(Ordering::Acquire);
+            if write.wrapping_sub(read) < N {
+                break;
+            }
+            if self.policy == DropPolicy::CoalesceById
+                && let Some(index) = self.find_same_id(read, write, &value.frame)
+            {
+                let seq = self.claim(index);
+                let read = self.read.load(Ordering::Acquire);
+                if (read.wrapping_sub(index) as isize) < 0 {
+                    // The consumer has not reached it, it will see the new frame
+                    self.release(index, seq, Some(value));
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                // The consumer may be taking the old frame right now, leave it be
+                self.release(index, seq, None);
+                if read == index
+                    && self.read.compare_exchange(read, read.wrapping_add(1), Ordering::AcqRel, Ordering::Acquire).is_ok()
+                {
+                    // It was not taken after all, drop it for the new one at the end
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                // The consumer took the old frame, so there is room now
+                continue;
+            }
+            if self.read.compare_exchange(read, read.wrapping_add(1), Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+        }
+        self.store(write, value);
+        self.write.store(write.wrapping_add(1), Ordering::Release);
+        let len = write.wrapping_add(1).wrapping_sub(self.read.load(Ordering::Acquire)) as u32;
+        self.high_water.fetch_max(len, Ordering::Relaxed);
+    }
+
+    fn pop(&self) -> Option<Stamped<F>> {
+        loop {
+            let read = self.read.load(Ordering::Acquire);
+            if read == self.write.load(Ordering::Acquire) {
+                return None;
+            }
+            // See `claim`
+            fence(Ordering::SeqCst);
+            let slot = &self.slots[read % N];
+            let before = slot.seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                continue;
+            }
+            let value = unsafe { ptr::read_volatile(slot.value.get()) };
+            fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) != before {
+                continue;
+            }
+            if self.read.compare_exchange(read, read.wrapping_add(1), Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                // Written by the producer before it moved `write` past it
+                return Some(unsafe { value.assume_init() });
+            }
+        }
+    }
+}
+
+/// The receive side, never blocks.
+pub struct Producer<'a, F, const N: usize> {
+    ring: &'a FrameRing<F, N>,
+}
+
+impl<F: Frame + Copy, const N: usize> Producer<'_, F, N> {
+    pub fn push(&mut self, frame: F, at_us: u64) {
+        self.ring.push(Stamped { frame, at_us });
+    }
+
+    pub fn stats(&self) -> RingStats {
+        self.ring.stats()
+    }
+}
+
+pub struct Consumer<'a, F, const N: usize> {
+    ring: &'a FrameRing<F, N>,
+}
+
+impl<F: Frame + Copy, const N: usize> Consumer<'_, F, N> {
+    /// The oldest frame still queued.
+    pub fn pop(&mut self) -> Option<Stamped<F>> {
+        self.ring.pop()
+    }
+
+    pub fn stats(&self) -> RingStats {
+        self.ring.stats()
+    }
+}
@@ -0,0 +1,149 @@
+use core::fmt::Write;
+
+use heapless::String;
+use num_traits::Float;
+
+/// A range of values drawn in a different colour, like the redline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub from: f32,
+    pub to: f32,
+}
+
+impl Band {
+    pub fn contains(&self, value: f32) -> bool {
+        (self.from..=self.to).contains(&value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    Normal,
+    Warning,
+    Redline,
+}
+
+/// How tick labels are written: `value / divisor` with a fixed number of decimals,
+/// so a tachometer can label 0..8 for 0..8000 rpm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelFormat {
+    pub divisor: f32,
+    pub decimals: usize,
+}
+
+impl LabelFormat {
+    pub const PLAIN: LabelFormat = LabelFormat { divisor: 1.0, decimals: 0 };
+}
+
+pub type Label = String<8>;
+
+/// Everything about what a round gauge shows, independent of its size.
+/// Angles are in degrees, clockwise from 3 o'clock like `embedded_graphics::primitives::Arc`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub min: f32,
+    pub max: f32,
+    /// Labelled ticks, has to be a multiple of `minor_interval`.
+    pub major_interval: f32,
+    pub minor_interval: f32,
+    pub labels: LabelFormat,
+    pub warning: Option<Band>,
+    pub redline: Option<Band>,
+    pub start_angle: f32,
+    pub sweep: f32,
+}
+
+impl Scale {
+    /// 0..250 km/h over 300 degrees, open at the bottom.
+    pub fn speedo() -> Self {
+        Scale {
+            min: 0.0,
+            max: 250.0,
+            major_interval: 20.0,
+            minor_interval: 10.0,
+            labels: LabelFormat::PLAIN,
+            warning: None,
+            redline: Some(Band { from: 200.0, to: 250.0 }),
+            start_angle: 120.0,
+            sweep: 300.0,
+        }
+    }
+
+    /// 0..8000 rpm, labelled in thousands.
+    pub fn tachometer() -> Self {
+        Scale {
+            min: 0.0,
+            max: 8000.0,
+            major_interval: 1000.0,
+            minor_interval: 500.0,
+            labels: LabelFormat { divisor: 1000.0, decimals: 0 },
+            warning: Some(Band { from: 5500.0, to: 6500.0 }),
+            redline: Some(Band { from: 6500.0, to: 8000.0 }),
+            ..Self::speedo()
+        }
+    }
+
+    /// Manifold pressure relative to atmosphere, -1..2 bar.
+    pub fn boost() -> Self {
+        Scale {
+            min: -1.0,
+            max: 2.0,
+            major_interval: 0.5,
+            minor_interval: 0.25,
+            labels: LabelFormat { divisor: 1.0, decimals: 1 },
+            warning: Some(Band { from: 1.2, to: 1.5 }),
+            redline: Some(Band { from: 1.5, to: 2.0 }),
+            ..Self::speedo()
+        }
+    }
+
+    /// Coolant or oil temperature in degrees Celsius.
+    pub fn temperature() -> Self {
+        Scale {
+            min: 40.0,
+            max: 140.0,
+            major_interval: 20.0,
+            minor_interval: 10.0,
+            labels: LabelFormat::PLAIN,
+            warning: Some(Band { from: 105.0, to: 115.0 }),
+            redline: Some(Band { from: 115.0, to: 140.0 }),
+            ..Self::speedo()
+        }
+    }
+
+    /// Where `value` sits on the scale, clamped to 0..=1.
+    pub fn fraction(&self, value: f32) -> f32 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    /// Angle of the needle for `value`, pinned to the end stops outside the range.
+    pub fn angle(&self, value: f32) -> f32 {
+        self.start_angle + self.sweep * self.fraction(value)
+    }
+
+    pub fn zone(&self, value: f32) -> Zone {
+        if self.redline.is_some_and(|band| band.contains(value)) {
+            Zone::Redline
+        } else if self.warning.is_some_and(|band| band.contains(value)) {
+            Zone::Warning
+        } else {
+            Zone::Normal
+        }
+    }
+
+    /// Every tick from `min` to `max` with whether it is a major (labelled) one.
+    pub fn ticks(&self) -> impl Iterator<Item = (f32, bool)> + '_ {
+        let count = ((self.max - self.min) / self.minor_interval + 0.5) as usize;
+        let per_major = ((self.major_interval / self.minor_interval + 0.5) as usize).max(1);
+        (0..=count).map(move |index| (self.min + index as f32 * self.minor_interval, index % per_major == 0))
+    }
+
+    pub fn label(&self, value: f32) -> Label {
+        let mut label = Label::new();
+        let value = value / self.labels.divisor;
+        // Avoid "-0" for values that round to zero
+        let value = if value.abs() < 0.5 / Float::powi(10.0f32, self.labels.decimals as i32) { 0.0 } else { value };
+        let _ = write!(label, "{:.*}", self.labels.decimals, value);
+        label
+    }
+}
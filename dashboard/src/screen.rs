@@ -0,0 +1,231 @@
+use core::convert::Infallible;
+
+use alloc::vec::Vec;
+use embedded_graphics::{
+    geometry::{Point, Size},
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, DrawTargetExt, Primitive, RgbColor},
+    primitives::{PrimitiveStyleBuilder, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+
+use crate::{
+    alert::{self, AlertEngine, Priority},
+    car_state::CarState,
+    damage::Damage,
+    gauge::{DashboardContext, Gauge},
+    layout::{self, Widget},
+};
+
+pub const WIDTH: usize = 240;
+pub const HEIGHT: usize = 240;
+
+/// A gauge filling the whole display, its scale decides what it shows.
+pub type ScreenGauge = Gauge<WIDTH, HEIGHT, 10, 162>;
+pub type Context = DashboardContext<'static, WIDTH, HEIGHT>;
+
+/// Inside the cleared centre of the dial, so the widgets underneath can repaint all of it.
+pub const BANNER_AREA: Rectangle = Rectangle::new(Point::new(45, 108), Size::new(150, 24));
+/// The banner swaps between filled and outlined this often.
+const BANNER_FLASH_MS: u64 = 500;
+/// How long a wipe to new widgets takes.
+pub const TRANSITION_MS: u64 = 300;
+
+/// How new widgets replace the ones on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// All at once.
+    Cut,
+    /// The new widgets sweep in from the right, over the old ones.
+    WipeLeft,
+    /// From the left.
+    WipeRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Wipe {
+    transition: Transition,
+    /// Set by the first `update` after the widgets were replaced.
+    started_ms: Option<u64>,
+}
+
+impl Wipe {
+    /// The part of the screen the new widgets cover so far, and where the edge is drawn.
+    fn revealed(&self, now_ms: u64) -> (Rectangle, Option<i32>) {
+        let elapsed = self.started_ms.map_or(0, |started| now_ms.saturating_sub(started));
+        if self.transition == Transition::Cut || elapsed >= TRANSITION_MS {
+            return (Rectangle::new(Point::zero(), Size::new(WIDTH as u32, HEIGHT as u32)), None);
+        }
+        // Eases out, quick to start and settling at the far side
+        let linear = elapsed as f32 / TRANSITION_MS as f32;
+        let progress = 1.0 - (1.0 - linear) * (1.0 - linear);
+        let width = (WIDTH as f32 * progress) as u32;
+        let left = match self.transition {
+            Transition::WipeRight => 0,
+            _ => WIDTH as i32 - width as i32,
+        };
+        let edge = match self.transition {
+            Transition::WipeRight => left + width as i32,
+            _ => left - 1,
+        };
+        (Rectangle::new(Point::new(left, 0), Size::new(width, HEIGHT as u32)), Some(edge))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Banner {
+    /// Index into `AlertEngine::alerts`.
+    alert: usize,
+    filled: bool,
+}
+
+/// Everything on the round display, shared by the firmware and the simulator.
+pub struct Screen {
+    pub widgets: Vec<Widget>,
+    pub context: Context,
+    pub alerts: AlertEngine,
+    banner: Option<Banner>,
+    /// What the framebuffer shows on top of the widgets.
+    drawn_banner: Option<Banner>,
+    /// Replacing the widgets, until the new ones cover the whole screen.
+    wipe: Option<Wipe>,
+    now_ms: u64,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self::with_layout(layout::default_layout())
+    }
+
+    pub fn with_layout(widgets: Vec<Widget>) -> Self {
+        Screen {
+            widgets,
+            context: DashboardContext::new(),
+            alerts: AlertEngine::new(alert::default_rules()),
+            banner: None,
+            drawn_banner: None,
+            wipe: None,
+            now_ms: 0,
+        }
+    }
+
+    /// Replaces the widgets, `draw` starts over with them from a cleared screen.
+    pub fn show(&mut self, widgets: Vec<Widget>, transition: Transition) {
+        self.widgets = widgets;
+        self.wipe = Some(Wipe { transition, started_ms: None });
+    }
+
+    /// Whether a transition is still going on.
+    pub fn in_transition(&self) -> bool {
+        self.wipe.is_some()
+    }
+
+    /// Draws the parts that never change, once after clearing the display.
+    pub fn draw_static<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D) {
+        for widget in &self.widgets {
+            widget.draw_static(framebuffer, &self.context);
+        }
+    }
+
+    /// Moves every widget closer to the current car state, `now_ms` paces the needles
+    /// and the alerts.
+    pub fn update(&mut self, state: &CarState, now_ms: u64) {
+        for widget in &mut self.widgets {
+            widget.update(state, now_ms);
+        }
+        self.alerts.update(state, now_ms);
+        for widget in &mut self.widgets {
+            widget.update_alerts(&self.alerts);
+        }
+        if let Some(wipe) = self.wipe.as_mut() {
+            wipe.started_ms.get_or_insert(now_ms);
+        }
+        self.now_ms = now_ms;
+        self.banner = self.alerts.banner().map(|alert| Banner {
+            alert,
+            filled: (now_ms / BANNER_FLASH_MS).is_multiple_of(2),
+        });
+    }
+
+    /// Redraws the widgets that changed, and those a redrawn widget painted over.
+    /// The alert banner goes on top.
+    pub fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&mut self, framebuffer: &mut D) {
+        let mut redrawn = Damage::new();
+        if let Some(wipe) = self.wipe {
+            // Everything the new widgets cover so far gets redrawn each frame, that keeps the
+            // part already revealed up to date without the widgets tracking a clip
+            let (area, edge) = wipe.revealed(self.now_ms);
+            let target = &mut framebuffer.clipped(&area);
+            target.fill_solid(&area, self.context.back_color).unwrap();
+            self.draw_static(target);
+            for widget in &mut self.widgets {
+                widget.draw(target, &self.context, true);
+            }
+            if let Some(x) = edge {
+                let line = Rectangle::new(Point::new(x, 0), Size::new(1, HEIGHT as u32));
+                framebuffer.fill_solid(&line, self.context.needle_color).unwrap();
+                redrawn.add(line);
+            }
+            redrawn.add(area);
+            if edge.is_none() {
+                self.wipe = None;
+            }
+            self.draw_banner_over(framebuffer, &redrawn);
+            return;
+        }
+        if self.drawn_banner.is_some() && self.banner.is_none() {
+            // Uncover what was under the banner
+            redrawn.add(BANNER_AREA);
+        }
+        for widget in &mut self.widgets {
+            let covered = redrawn
+                .rects()
+                .iter()
+                .any(|area| !area.intersection(&widget.bounds).is_zero_sized());
+            if let Some(area) = widget.draw(framebuffer, &self.context, covered) {
+                redrawn.add(area);
+            }
+        }
+        self.draw_banner_over(framebuffer, &redrawn);
+    }
+
+    /// Draws the banner if it changed or something was drawn over it.
+    fn draw_banner_over<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&mut self, framebuffer: &mut D, redrawn: &Damage) {
+        if let Some(banner) = self.banner {
+            let covered = redrawn.rects().iter().any(|area| !area.intersection(&BANNER_AREA).is_zero_sized());
+            if covered || self.drawn_banner != Some(banner) {
+                self.draw_banner(framebuffer, banner);
+            }
+        }
+        self.drawn_banner = self.banner;
+    }
+
+    fn draw_banner<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, banner: Banner) {
+        let rule = &self.alerts.alerts[banner.alert].rule;
+        let color = match rule.priority {
+            Priority::Warning => self.context.caution_light_style.fill_color.unwrap(),
+            Priority::Critical => self.context.danger_light_style.fill_color.unwrap(),
+        };
+        let (fill, text) = if banner.filled { (color, Rgb565::WHITE) } else { (self.context.back_color, color) };
+        BANNER_AREA
+            .into_styled(PrimitiveStyleBuilder::new().fill_color(fill).stroke_color(color).stroke_width(2).build())
+            .draw(framebuffer)
+            .unwrap();
+        Text::with_text_style(
+            rule.message,
+            BANNER_AREA.center(),
+            MonoTextStyle::new(&FONT_10X20, text),
+            TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Middle).build(),
+        )
+        .draw(framebuffer)
+        .unwrap();
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
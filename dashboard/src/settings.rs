@@ -0,0 +1,97 @@
+//! What the dashboard keeps across reboots, and the `KvStore` keys it lives under.
+use crate::{
+    bitrate::Bitrate,
+    store::{Flash, KvStore, StoreError},
+    trip::{EconomyUnit, Trip, TripComputer, TripId, TRIP_RECORD_LEN},
+};
+
+pub const SETTINGS_KEY: u16 = 1;
+pub const ODOMETER_KEY: u16 = 2;
+pub const TRIP_A_KEY: u16 = 3;
+pub const TRIP_B_KEY: u16 = 4;
+/// Bumped when the layout of `Settings::to_bytes` changes, older records then read as the defaults.
+const SETTINGS_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub economy_unit: EconomyUnit,
+    /// Backlight in percent.
+    pub brightness: u8,
+    /// Found by auto-baud on an earlier boot, `None` to run it again.
+    pub bitrate: Option<Bitrate>,
+    /// The page showing when the power went, see `Page::index`.
+    pub page: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { economy_unit: EconomyUnit::LitresPer100Km, brightness: 100, bitrate: None, page: 0 }
+    }
+}
+
+impl Settings {
+    pub fn to_bytes(&self) -> [u8; 7] {
+        let unit = match self.economy_unit {
+            EconomyUnit::LitresPer100Km => 0,
+            EconomyUnit::Mpg => 1,
+        };
+        let [magic, code, check] = self.bitrate.map_or([0; 3], Bitrate::to_record);
+        [SETTINGS_VERSION, unit, self.brightness, magic, code, check, self.page]
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let &[SETTINGS_VERSION, unit, brightness, magic, code, check, page] = bytes else {
+            return None;
+        };
+        let economy_unit = match unit {
+            0 => EconomyUnit::LitresPer100Km,
+            1 => EconomyUnit::Mpg,
+            _ => return None,
+        };
+        Some(Settings { economy_unit, brightness, bitrate: Bitrate::from_record(&[magic, code, check]), page })
+    }
+
+    /// The stored settings, the defaults when there are none yet.
+    pub fn load<F: Flash>(store: &mut KvStore<F>) -> Result<Self, StoreError<F::Error>> {
+        let mut bytes = [0u8; 7];
+        let stored = store.get(SETTINGS_KEY, &mut bytes)?;
+        Ok(stored.and_then(|len| Settings::from_bytes(&bytes[..len])).unwrap_or_default())
+    }
+
+    pub fn save<F: Flash>(&self, store: &mut KvStore<F>) -> Result<(), StoreError<F::Error>> {
+        store.set(SETTINGS_KEY, &self.to_bytes())
+    }
+}
+
+const TRIP_KEYS: [(Option<TripId>, u16); 3] =
+    [(None, ODOMETER_KEY), (Some(TripId::A), TRIP_A_KEY), (Some(TripId::B), TRIP_B_KEY)];
+
+/// Puts the odometer and both trips back the way they were saved.
+pub fn load_trips<F: Flash>(store: &mut KvStore<F>, trips: &mut TripComputer) -> Result<(), StoreError<F::Error>> {
+    for (id, key) in TRIP_KEYS {
+        let mut bytes = [0u8; TRIP_RECORD_LEN];
+        if let Some(trip) = store.get(key, &mut bytes)?.and_then(|len| Trip::from_bytes(&bytes[..len])) {
+            trips.restore(id, trip);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the counters that changed since `saved`, and updates it.
+pub fn save_trips<F: Flash>(
+    store: &mut KvStore<F>,
+    trips: &TripComputer,
+    saved: &mut TripComputer,
+) -> Result<(), StoreError<F::Error>> {
+    for (id, key) in TRIP_KEYS {
+        let (trip, previous) = match id {
+            Some(id) => (trips.trip(id), saved.trip(id)),
+            None => (trips.total(), saved.total()),
+        };
+        if trip != previous {
+            store.set(key, &trip.to_bytes())?;
+            saved.restore(id, *trip);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use core::future::Future;
+
+use embedded_can::Frame;
+
+use crate::isotp::IsoTpMessage;
+
+/// What a frame source hands to the car state: every raw frame, plus
+/// complete ISO-TP messages once all their frames arrived.
+#[derive(Debug)]
+pub enum CanEvent<F> {
+    Frame(F),
+    Message(IsoTpMessage),
+}
+
+/// Where `CarState` gets its traffic from: the TWAI peripheral on the car,
+/// or a recorded log on the bench and in tests.
+pub trait FrameSource {
+    type Frame: Frame;
+
+    /// Waits for the next event, `None` once the source is exhausted.
+    fn next_event(&mut self) -> impl Future<Output = Option<CanEvent<Self::Frame>>>;
+
+    /// When the last event handed out arrived, in milliseconds since boot, for sources
+    /// that queue events and know better than the time they were handed out.
+    fn received_at_ms(&self) -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,366 @@
+//! A small key/value store for flash: records are appended to a ring of sectors,
+//! each with a CRC, and the newest valid record for a key wins.
+//!
+//! Nothing is ever overwritten in place. When the active sector is full the next
+//! one is erased and takes over, then the live records of the oldest sector are
+//! copied forward and it is erased, becoming the spare. Every sector gets erased
+//! once per trip round the ring, and a write cut short by a power failure fails
+//! its CRC and is ignored, leaving the previous value in place.
+use alloc::{vec, vec::Vec};
+use core::fmt::Debug;
+
+const SECTOR_MAGIC: u32 = 0x3153_564B;
+/// Magic, sequence number and its complement, so a half written header is not taken for the newest.
+const SECTOR_HEADER_LEN: u32 = 12;
+/// Key, value length and CRC.
+const RECORD_HEADER_LEN: usize = 8;
+/// Unprogrammed flash, also where the records of a sector end.
+const ERASED_KEY: u16 = 0xFFFF;
+pub const MAX_VALUE_LEN: usize = 120;
+pub const MAX_SECTORS: usize = 16;
+/// Distinct keys copied forward when a sector is reclaimed.
+pub const MAX_KEYS: usize = 32;
+
+/// Flash as the store needs it. Like NOR flash, writing can only clear bits and
+/// erasing a sector sets them all again. Offsets and lengths are multiples of 4.
+pub trait Flash {
+    type Error: Debug;
+    /// Erase granularity in bytes.
+    const SECTOR_SIZE: u32;
+
+    fn capacity(&self) -> u32;
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+    /// Erases the sector starting at `offset`.
+    fn erase(&mut self, offset: u32) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreError<E> {
+    Flash(E),
+    /// Fewer than two sectors, or more than `MAX_SECTORS`.
+    BadGeometry,
+    /// The value is longer than `MAX_VALUE_LEN` or the buffer for it too short.
+    TooLarge,
+    /// The live records no longer fit in one sector.
+    Full,
+    /// The record did not read back the same twice in a row.
+    WriteFailed,
+}
+
+impl<E> From<E> for StoreError<E> {
+    fn from(error: E) -> Self {
+        StoreError::Flash(error)
+    }
+}
+
+/// The standard CRC-32 (IEEE, as in zlib and Ethernet).
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn padded(len: usize) -> usize {
+    len.next_multiple_of(4)
+}
+
+fn record_crc(key: u16, value: &[u8]) -> u32 {
+    let mut bytes = [0u8; 4 + MAX_VALUE_LEN];
+    bytes[..2].copy_from_slice(&key.to_le_bytes());
+    bytes[2..4].copy_from_slice(&(value.len() as u16).to_le_bytes());
+    bytes[4..4 + value.len()].copy_from_slice(value);
+    crc32(&bytes[..4 + value.len()])
+}
+
+/// Calls `visit` with the key, offset and value of every valid record in the sector,
+/// returns where the next record goes.
+fn walk<F: Flash>(flash: &mut F, sector: u32, mut visit: impl FnMut(u16, u32, &[u8])) -> Result<u32, F::Error> {
+    let start = sector * F::SECTOR_SIZE;
+    let end = start + F::SECTOR_SIZE;
+    let mut offset = start + SECTOR_HEADER_LEN;
+    let mut value = [0u8; MAX_VALUE_LEN];
+    while offset + RECORD_HEADER_LEN as u32 <= end {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        flash.read(offset, &mut header)?;
+        if header == [0xFF; RECORD_HEADER_LEN] {
+            return Ok(offset - start);
+        }
+        let key = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = (RECORD_HEADER_LEN + padded(len)) as u32;
+        if len > MAX_VALUE_LEN || offset + size > end {
+            // A header cut short, nothing after it can be trusted
+            break;
+        }
+        flash.read(offset + RECORD_HEADER_LEN as u32, &mut value[..padded(len)])?;
+        if key != ERASED_KEY && record_crc(key, &value[..len]) == crc {
+            visit(key, offset, &value[..len]);
+        }
+        offset += size;
+    }
+    Ok(F::SECTOR_SIZE)
+}
+
+pub struct KvStore<F: Flash> {
+    flash: F,
+    sectors: u32,
+    active: u32,
+    sequence: u32,
+    /// Within the active sector.
+    write_pos: u32,
+}
+
+impl<F: Flash> KvStore<F> {
+    /// Finds the newest sector, or formats blank (or foreign) flash, and finishes
+    /// reclaiming a sector if the power went out in the middle of it.
+    pub fn mount(flash: F) -> Result<Self, StoreError<F::Error>> {
+        let sectors = flash.capacity() / F::SECTOR_SIZE;
+        if !(2..=MAX_SECTORS as u32).contains(&sectors) {
+            return Err(StoreError::BadGeometry);
+        }
+        let mut store = KvStore { flash, sectors, active: 0, sequence: 0, write_pos: 0 };
+        let mut newest = None;
+        for sector in 0..sectors {
+            if let Some(sequence) = store.sequence_of(sector)?
+                && newest.is_none_or(|(_, newest)| sequence > newest)
+            {
+                newest = Some((sector, sequence));
+            }
+        }
+        match newest {
+            Some((sector, sequence)) => {
+                store.active = sector;
+                store.sequence = sequence;
+                store.write_pos = walk(&mut store.flash, sector, |_, _, _| ())?;
+                store.reclaim(store.after(sector))?;
+            }
+            None => store.start_sector(0, 1)?,
+        }
+        Ok(store)
+    }
+
+    pub fn into_flash(self) -> F {
+        self.flash
+    }
+
+    pub fn flash_mut(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
+    /// Copies the newest value for `key` into `value` and returns its length.
+    pub fn get(&mut self, key: u16, value: &mut [u8]) -> Result<Option<usize>, StoreError<F::Error>> {
+        let mut found: Option<(usize, [u8; MAX_VALUE_LEN])> = None;
+        for sector in self.by_age()? {
+            walk(&mut self.flash, sector, |record_key, _, record| {
+                if record_key == key {
+                    let mut copy = [0u8; MAX_VALUE_LEN];
+                    copy[..record.len()].copy_from_slice(record);
+                    found = Some((record.len(), copy));
+                }
+            })?;
+        }
+        let Some((len, copy)) = found else {
+            return Ok(None);
+        };
+        value.get_mut(..len).ok_or(StoreError::TooLarge)?.copy_from_slice(&copy[..len]);
+        Ok(Some(len))
+    }
+
+    /// Stores `value` under `key`, any key but 0xFFFF.
+    pub fn set(&mut self, key: u16, value: &[u8]) -> Result<(), StoreError<F::Error>> {
+        if value.len() > MAX_VALUE_LEN || key == ERASED_KEY {
+            return Err(StoreError::TooLarge);
+        }
+        // A record that does not read back, say over bits an interrupted write already
+        // cleared, gets one more try further on
+        for _ in 0..2 {
+            if self.write_pos as usize + RECORD_HEADER_LEN + padded(value.len()) > F::SECTOR_SIZE as usize {
+                self.next_sector()?;
+            }
+            if self.append(key, value)? {
+                return Ok(());
+            }
+        }
+        Err(StoreError::WriteFailed)
+    }
+
+    /// Writes the record at the end of the active sector, returns whether it reads back valid.
+    fn append(&mut self, key: u16, value: &[u8]) -> Result<bool, StoreError<F::Error>> {
+        let size = RECORD_HEADER_LEN + padded(value.len());
+        if self.write_pos as usize + size > F::SECTOR_SIZE as usize {
+            return Err(StoreError::Full);
+        }
+        let mut record = [0xFFu8; RECORD_HEADER_LEN + MAX_VALUE_LEN];
+        record[..2].copy_from_slice(&key.to_le_bytes());
+        record[2..4].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        record[4..8].copy_from_slice(&record_crc(key, value).to_le_bytes());
+        record[RECORD_HEADER_LEN..RECORD_HEADER_LEN + value.len()].copy_from_slice(value);
+        let offset = self.active * F::SECTOR_SIZE + self.write_pos;
+        self.write_pos += size as u32;
+        self.flash.write(offset, &record[..size])?;
+        let mut check = [0u8; RECORD_HEADER_LEN + MAX_VALUE_LEN];
+        self.flash.read(offset, &mut check[..size])?;
+        Ok(check[..size] == record[..size])
+    }
+
+    fn after(&self, sector: u32) -> u32 {
+        (sector + 1) % self.sectors
+    }
+
+    fn sequence_of(&mut self, sector: u32) -> Result<Option<u32>, F::Error> {
+        let mut header = [0u8; SECTOR_HEADER_LEN as usize];
+        self.flash.read(sector * F::SECTOR_SIZE, &mut header)?;
+        let word = |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+        Ok((word(0) == SECTOR_MAGIC && word(2) == !word(1)).then(|| word(1)))
+    }
+
+    /// The sectors in use, oldest first.
+    fn by_age(&mut self) -> Result<heapless::Vec<u32, MAX_SECTORS>, F::Error> {
+        let mut sectors: heapless::Vec<(u32, u32), MAX_SECTORS> = heapless::Vec::new();
+        for sector in 0..self.sectors {
+            if let Some(sequence) = self.sequence_of(sector)? {
+                let _ = sectors.push((sequence, sector));
+            }
+        }
+        sectors.sort_unstable();
+        Ok(sectors.iter().map(|(_, sector)| *sector).collect())
+    }
+
+    fn is_blank(&mut self, sector: u32) -> Result<bool, F::Error> {
+        let mut chunk = [0u8; 64];
+        for offset in (0..F::SECTOR_SIZE).step_by(chunk.len()) {
+            self.flash.read(sector * F::SECTOR_SIZE + offset, &mut chunk)?;
+            if chunk != [0xFF; 64] {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn start_sector(&mut self, sector: u32, sequence: u32) -> Result<(), StoreError<F::Error>> {
+        if !self.is_blank(sector)? {
+            self.flash.erase(sector * F::SECTOR_SIZE)?;
+        }
+        let mut header = [0u8; SECTOR_HEADER_LEN as usize];
+        header[..4].copy_from_slice(&SECTOR_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        header[8..].copy_from_slice(&(!sequence).to_le_bytes());
+        self.flash.write(sector * F::SECTOR_SIZE, &header)?;
+        self.active = sector;
+        self.sequence = sequence;
+        self.write_pos = SECTOR_HEADER_LEN;
+        Ok(())
+    }
+
+    /// Moves on to the spare sector and reclaims the oldest one, the one after it.
+    fn next_sector(&mut self) -> Result<(), StoreError<F::Error>> {
+        self.start_sector(self.after(self.active), self.sequence.wrapping_add(1))?;
+        self.reclaim(self.after(self.active))
+    }
+
+    /// Copies the records of `sector` that have no newer version forward, then erases it.
+    fn reclaim(&mut self, sector: u32) -> Result<(), StoreError<F::Error>> {
+        if sector == self.active || self.sequence_of(sector)?.is_none() {
+            return Ok(());
+        }
+        let mut live: heapless::Vec<(u16, u32), MAX_KEYS> = heapless::Vec::new();
+        let mut overflow = false;
+        walk(&mut self.flash, sector, |key, offset, _| match live.iter_mut().find(|(live_key, _)| *live_key == key) {
+            Some(entry) => entry.1 = offset,
+            None => overflow |= live.push((key, offset)).is_err(),
+        })?;
+        if overflow {
+            return Err(StoreError::Full);
+        }
+        // Every other sector is newer than this one
+        for newer in self.by_age()? {
+            if newer != sector {
+                walk(&mut self.flash, newer, |key, _, _| live.retain(|(live_key, _)| *live_key != key))?;
+            }
+        }
+        let mut value = [0u8; MAX_VALUE_LEN];
+        for (key, offset) in live {
+            let mut header = [0u8; RECORD_HEADER_LEN];
+            self.flash.read(offset, &mut header)?;
+            let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+            self.flash.read(offset + RECORD_HEADER_LEN as u32, &mut value[..padded(len)])?;
+            if !self.append(key, &value[..len])? {
+                return Err(StoreError::WriteFailed);
+            }
+        }
+        self.flash.erase(sector * F::SECTOR_SIZE)?;
+        Ok(())
+    }
+}
+
+/// Lost power in the middle of a write or erase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerLoss;
+
+/// Flash in RAM for the host tests, with the same one way writes as the real thing.
+#[derive(Debug, Clone)]
+pub struct RamFlash {
+    pub data: Vec<u8>,
+    /// How often each sector was erased.
+    pub erases: Vec<u32>,
+    /// Bytes written before the power goes out, `None` to keep it on.
+    pub power_fails_after: Option<usize>,
+}
+
+impl RamFlash {
+    pub const SECTOR_SIZE: u32 = 4096;
+
+    pub fn new(sectors: usize) -> Self {
+        RamFlash {
+            data: vec![0xFF; sectors * Self::SECTOR_SIZE as usize],
+            erases: vec![0; sectors],
+            power_fails_after: None,
+        }
+    }
+}
+
+impl Flash for RamFlash {
+    type Error = PowerLoss;
+    const SECTOR_SIZE: u32 = RamFlash::SECTOR_SIZE;
+
+    fn capacity(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), PowerLoss> {
+        assert!(offset.is_multiple_of(4) && bytes.len().is_multiple_of(4), "unaligned read");
+        bytes.copy_from_slice(&self.data[offset as usize..offset as usize + bytes.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), PowerLoss> {
+        assert!(offset.is_multiple_of(4) && bytes.len().is_multiple_of(4), "unaligned write");
+        for (index, byte) in bytes.iter().enumerate() {
+            if let Some(left) = self.power_fails_after.as_mut() {
+                if *left == 0 {
+                    return Err(PowerLoss);
+                }
+                *left -= 1;
+            }
+            self.data[offset as usize + index] &= byte;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), PowerLoss> {
+        if self.power_fails_after == Some(0) {
+            return Err(PowerLoss);
+        }
+        let sector = (offset / Self::SECTOR_SIZE) as usize;
+        self.data[offset as usize..offset as usize + Self::SECTOR_SIZE as usize].fill(0xFF);
+        self.erases[sector] += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+//! Warning lights. Each telltale is an icon lit from a `CarState` field, in the
+//! colour ISO 2575 gives its meaning: red to stop or act now, amber for caution,
+//! green for something switched on and blue for high beam.
+use core::{convert::Infallible, mem::replace};
+
+use embedded_graphics::{
+    geometry::{Angle, Point, Size},
+    mono_font::{ascii::FONT_4X6, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, DrawTargetExt, Primitive},
+    primitives::{Arc, Circle, Line, PrimitiveStyle, Rectangle, Sector, Triangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+    Drawable,
+};
+
+use crate::screen::Context;
+
+/// 90 flashes a minute, ECE R6 allows 60 to 120.
+pub const BLINK_PERIOD_MS: u64 = 667;
+/// Coolant hotter than this lights the temperature telltale, where `Scale::temperature` starts its redline.
+pub const COOLANT_HOT: f32 = 115.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Red,
+    Amber,
+    Green,
+    Blue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon {
+    TurnLeft,
+    TurnRight,
+    LowBeam,
+    HighBeam,
+    OilPressure,
+    Battery,
+    CheckEngine,
+    Abs,
+    Seatbelt,
+    CoolantTemp,
+}
+
+impl Icon {
+    pub fn colour(self) -> Colour {
+        match self {
+            Icon::TurnLeft | Icon::TurnRight | Icon::LowBeam => Colour::Green,
+            Icon::HighBeam => Colour::Blue,
+            Icon::CheckEngine | Icon::Abs => Colour::Amber,
+            Icon::OilPressure | Icon::Battery | Icon::Seatbelt | Icon::CoolantTemp => Colour::Red,
+        }
+    }
+}
+
+/// When the field value lights the telltale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// A one bit signal is set.
+    Flag,
+    /// A measurement reached a limit.
+    AtLeast(f32),
+}
+
+impl Trigger {
+    fn is_active(self, value: f32) -> bool {
+        match self {
+            Trigger::Flag => value >= 0.5,
+            Trigger::AtLeast(limit) => value >= limit,
+        }
+    }
+}
+
+pub struct Telltale {
+    pub icon: Icon,
+    pub colour: Colour,
+    pub trigger: Trigger,
+    pub blinks: bool,
+    /// When the trigger went active, blinking starts with a flash from there.
+    active_since: Option<u64>,
+    lit: bool,
+}
+
+impl Telltale {
+    /// The icon with its ISO colour, the indicators blink and the coolant one watches the temperature.
+    pub fn new(icon: Icon) -> Self {
+        let trigger = match icon {
+            Icon::CoolantTemp => Trigger::AtLeast(COOLANT_HOT),
+            _ => Trigger::Flag,
+        };
+        Telltale {
+            icon,
+            colour: icon.colour(),
+            trigger,
+            blinks: matches!(icon, Icon::TurnLeft | Icon::TurnRight),
+            active_since: None,
+            lit: false,
+        }
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    /// Returns whether the light went on or off. An unknown value leaves it off.
+    pub fn update(&mut self, value: Option<f32>, now_ms: u64) -> bool {
+        if value.is_some_and(|value| self.trigger.is_active(value)) {
+            self.active_since.get_or_insert(now_ms);
+        } else {
+            self.active_since = None;
+        }
+        let lit = match self.active_since {
+            Some(since) if self.blinks => now_ms.saturating_sub(since) % BLINK_PERIOD_MS < BLINK_PERIOD_MS / 2,
+            Some(_) => true,
+            None => false,
+        };
+        replace(&mut self.lit, lit) != lit
+    }
+
+    fn style(&self, context: &Context) -> PrimitiveStyle<Rgb565> {
+        match (self.lit, self.colour) {
+            (false, _) if self.blinks => context.blinker_off_style,
+            (false, _) => context.light_off_style,
+            (true, _) if self.blinks => context.blinker_on_style,
+            (true, Colour::Green) => context.headlight_on_style,
+            (true, Colour::Blue) => context.headlight_high_style,
+            (true, Colour::Amber) => context.caution_light_style,
+            (true, Colour::Red) => context.danger_light_style,
+        }
+    }
+
+    /// Draws the icon into the top left 12 by 12 pixels of `bounds`.
+    pub fn draw<D: DrawTarget<Color = Rgb565, Error = Infallible>>(&self, framebuffer: &mut D, bounds: Rectangle, context: &Context) {
+        let style = self.style(context);
+        let color = style.fill_color.unwrap();
+        // Details cut out of filled shapes
+        let cut = PrimitiveStyle::with_stroke(context.back_color, 1);
+        let target = &mut framebuffer.translated(bounds.top_left);
+        let p = Point::new;
+        let line = |target: &mut _, from, to, style| Line::new(from, to).into_styled(style).draw(target).unwrap();
+        let rect = |target: &mut _, x, y, width, height| {
+            Rectangle::new(p(x, y), Size::new(width, height)).into_styled(style).draw(target).unwrap()
+        };
+        match self.icon {
+            Icon::TurnLeft => {
+                Triangle::new(p(0, 6), p(6, 0), p(6, 12)).into_styled(style).draw(target).unwrap();
+                rect(target, 6, 4, 6, 5);
+            }
+            Icon::TurnRight => {
+                Triangle::new(p(11, 6), p(5, 0), p(5, 12)).into_styled(style).draw(target).unwrap();
+                rect(target, 0, 4, 6, 5);
+            }
+            Icon::LowBeam | Icon::HighBeam => {
+                // Lamp on the left, beams to the right, dipped for low beam
+                Sector::with_center(p(6, 6), 11, Angle::from_degrees(90.0), Angle::from_degrees(180.0))
+                    .into_styled(style)
+                    .draw(target)
+                    .unwrap();
+                let dip = if self.icon == Icon::LowBeam { 2 } else { 0 };
+                for y in [2, 6, 10] {
+                    line(target, p(8, y), p(11, y + dip), style);
+                }
+            }
+            Icon::OilPressure => {
+                rect(target, 1, 5, 7, 5);
+                line(target, p(0, 4), p(2, 6), style);
+                line(target, p(8, 6), p(11, 4), style);
+                Circle::new(p(9, 8), 3).into_styled(style).draw(target).unwrap();
+            }
+            Icon::Battery => {
+                rect(target, 0, 3, 12, 8);
+                rect(target, 2, 1, 2, 2);
+                rect(target, 8, 1, 2, 2);
+                line(target, p(2, 6), p(4, 6), cut);
+                line(target, p(7, 6), p(9, 6), cut);
+                line(target, p(8, 5), p(8, 7), cut);
+            }
+            Icon::CheckEngine => {
+                rect(target, 3, 3, 7, 7);
+                rect(target, 4, 1, 4, 2);
+                rect(target, 0, 5, 3, 3);
+                rect(target, 10, 4, 2, 5);
+            }
+            Icon::Abs => {
+                let ring = PrimitiveStyle::with_stroke(color, 1);
+                Arc::with_center(p(6, 6), 12, Angle::from_degrees(120.0), Angle::from_degrees(120.0))
+                    .into_styled(ring)
+                    .draw(target)
+                    .unwrap();
+                Arc::with_center(p(6, 6), 12, Angle::from_degrees(-60.0), Angle::from_degrees(120.0))
+                    .into_styled(ring)
+                    .draw(target)
+                    .unwrap();
+                Text::with_text_style(
+                    "ABS",
+                    p(6, 6),
+                    MonoTextStyle::new(&FONT_4X6, color),
+                    TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Middle).build(),
+                )
+                .draw(target)
+                .unwrap();
+            }
+            Icon::Seatbelt => {
+                Circle::new(p(4, 0), 4).into_styled(style).draw(target).unwrap();
+                rect(target, 3, 5, 6, 7);
+                line(target, p(3, 5), p(8, 11), cut);
+            }
+            Icon::CoolantTemp => {
+                rect(target, 5, 0, 2, 8);
+                Circle::new(p(4, 6), 4).into_styled(style).draw(target).unwrap();
+                line(target, p(7, 2), p(9, 2), style);
+                line(target, p(7, 5), p(9, 5), style);
+                line(target, p(0, 11), p(11, 11), style);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Trip counters, integrated from speed and fuel flow samples. There are two
+//! trips, A and B, reset independently.
+
+/// Samples closer together than this are skipped, a frame comes in far more often.
+pub const MIN_STEP_MS: u64 = 50;
+/// Across a longer gap (the bus went quiet, the car was off) only the clock runs on,
+/// nothing is known about the distance covered in between.
+pub const MAX_STEP_MS: u64 = 2000;
+/// Slower than this counts as standing still.
+pub const MOVING_KMH: f32 = 1.0;
+/// Below this speed consumption per distance runs off to infinity and is not shown.
+pub const MIN_ECONOMY_KMH: f32 = 5.0;
+/// Distance needed before an average consumption is shown.
+pub const MIN_ECONOMY_KM: f32 = 0.1;
+
+/// Petrol: stoichiometric air to fuel ratio and density in g/l.
+const AIR_FUEL_RATIO: f32 = 14.7;
+const FUEL_DENSITY: f32 = 745.0;
+
+/// Fuel flow in l/h estimated from the mass air flow in g/s, for cars that do not send it.
+/// Assumes a petrol engine running stoichiometric, so it reads high under full load enrichment.
+pub fn fuel_rate_from_maf(maf: f32) -> f32 {
+    maf / AIR_FUEL_RATIO / FUEL_DENSITY * 3600.0
+}
+
+/// Consumption in l/100km from a fuel flow in l/h, `None` when crawling.
+pub fn consumption(speed: f32, fuel_rate: f32) -> Option<f32> {
+    (speed >= MIN_ECONOMY_KMH).then(|| fuel_rate / speed * 100.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TripId {
+    A,
+    B,
+}
+
+impl TripId {
+    pub fn name(self) -> &'static str {
+        match self {
+            TripId::A => "TRIP A",
+            TripId::B => "TRIP B",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EconomyUnit {
+    LitresPer100Km,
+    /// US gallons.
+    Mpg,
+}
+
+impl EconomyUnit {
+    /// Converts from l/100km.
+    pub fn convert(self, litres_per_100km: f32) -> f32 {
+        match self {
+            EconomyUnit::LitresPer100Km => litres_per_100km,
+            EconomyUnit::Mpg => 235.215 / litres_per_100km,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EconomyUnit::LitresPer100Km => "L/100",
+            EconomyUnit::Mpg => "mpg",
+        }
+    }
+}
+
+/// Bytes in `Trip::to_bytes`.
+pub const TRIP_RECORD_LEN: usize = 36;
+
+/// The totals since the last reset. Distance and fuel are summed in f64, at 1000 km a
+/// 20 ms step at walking pace would vanish in the rounding of an f32.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Trip {
+    distance_m: f64,
+    fuel_ml: f64,
+    elapsed_ms: u64,
+    moving_ms: u64,
+    max_speed: f32,
+}
+
+impl Trip {
+    pub fn reset(&mut self) {
+        *self = Trip::default();
+    }
+
+    pub fn distance_km(&self) -> f32 {
+        (self.distance_m / 1000.0) as f32
+    }
+
+    pub fn fuel_used(&self) -> f32 {
+        (self.fuel_ml / 1000.0) as f32
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ms
+    }
+
+    pub fn moving_ms(&self) -> u64 {
+        self.moving_ms
+    }
+
+    /// Fastest in km/h.
+    pub fn max_speed(&self) -> f32 {
+        self.max_speed
+    }
+
+    /// Average over the moving time in km/h, stops in traffic do not drag it down.
+    pub fn average_speed(&self) -> Option<f32> {
+        (self.moving_ms > 0).then(|| (self.distance_m * 3600.0 / self.moving_ms as f64) as f32)
+    }
+
+    /// Average consumption in l/100km.
+    pub fn average_consumption(&self) -> Option<f32> {
+        (self.distance_km() >= MIN_ECONOMY_KM).then(|| (self.fuel_ml / self.distance_m * 100.0) as f32)
+    }
+
+    /// Persisted form, little endian.
+    pub fn to_bytes(&self) -> [u8; TRIP_RECORD_LEN] {
+        let mut bytes = [0u8; TRIP_RECORD_LEN];
+        bytes[..8].copy_from_slice(&self.distance_m.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.fuel_ml.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.elapsed_ms.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.moving_ms.to_le_bytes());
+        bytes[32..].copy_from_slice(&self.max_speed.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; TRIP_RECORD_LEN] = bytes.try_into().ok()?;
+        let word = |range: core::ops::Range<usize>| <[u8; 8]>::try_from(&bytes[range]).unwrap();
+        Some(Trip {
+            distance_m: f64::from_le_bytes(word(0..8)),
+            fuel_ml: f64::from_le_bytes(word(8..16)),
+            elapsed_ms: u64::from_le_bytes(word(16..24)),
+            moving_ms: u64::from_le_bytes(word(24..32)),
+            max_speed: f32::from_le_bytes(bytes[32..].try_into().unwrap()),
+        })
+    }
+
+    /// Adds a step of `dt_ms` between two samples, trapezoidal in speed and fuel flow.
+    fn add(&mut self, previous: &Sample, sample: &Sample, dt_ms: u64) {
+        self.elapsed_ms += dt_ms;
+        if dt_ms > MAX_STEP_MS {
+            return;
+        }
+        let (Some(from), Some(to)) = (previous.speed, sample.speed) else {
+            return;
+        };
+        let hours = dt_ms as f64 / 3_600_000.0;
+        self.distance_m += (from + to) as f64 / 2.0 * hours * 1000.0;
+        if from >= MOVING_KMH || to >= MOVING_KMH {
+            self.moving_ms += dt_ms;
+        }
+        self.max_speed = self.max_speed.max(from).max(to);
+        if let (Some(from), Some(to)) = (previous.fuel_rate, sample.fuel_rate) {
+            self.fuel_ml += (from + to) as f64 / 2.0 * hours * 1000.0;
+        }
+    }
+}
+
+/// Speed in km/h and fuel flow in l/h at one moment, either may be unknown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub at_ms: u64,
+    pub speed: Option<f32>,
+    pub fuel_rate: Option<f32>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TripComputer {
+    trips: [Trip; 2],
+    /// Never reset, its distance is the odometer.
+    total: Trip,
+    last: Option<Sample>,
+}
+
+impl TripComputer {
+    pub fn add_sample(&mut self, sample: Sample) {
+        let Some(previous) = self.last else {
+            self.last = Some(sample);
+            return;
+        };
+        let dt_ms = sample.at_ms.saturating_sub(previous.at_ms);
+        if dt_ms < MIN_STEP_MS {
+            return;
+        }
+        for trip in self.trips.iter_mut().chain([&mut self.total]) {
+            trip.add(&previous, &sample, dt_ms);
+        }
+        self.last = Some(sample);
+    }
+
+    pub fn trip(&self, id: TripId) -> &Trip {
+        &self.trips[id as usize]
+    }
+
+    pub fn reset(&mut self, id: TripId) {
+        self.trips[id as usize].reset();
+    }
+
+    /// Everything since the dashboard was first switched on.
+    pub fn total(&self) -> &Trip {
+        &self.total
+    }
+
+    pub fn odometer_km(&self) -> f32 {
+        self.total.distance_km()
+    }
+
+    /// Puts back counters read from flash.
+    pub fn restore(&mut self, id: Option<TripId>, trip: Trip) {
+        match id {
+            Some(id) => self.trips[id as usize] = trip,
+            None => self.total = trip,
+        }
+    }
+
+    /// Consumption right now in l/100km, from the last sample.
+    pub fn instant_consumption(&self) -> Option<f32> {
+        let sample = self.last?;
+        consumption(sample.speed?, sample.fuel_rate?)
+    }
+}
+
+/// Hours and minutes, `h:mm`.
+pub fn format_duration(ms: u64, text: &mut impl core::fmt::Write) -> core::fmt::Result {
+    let minutes = ms / 60_000;
+    write!(text, "{}:{:02}", minutes / 60, minutes % 60)
+}
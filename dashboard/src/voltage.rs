@@ -0,0 +1,74 @@
+//! Battery voltage from calibrated ADC readings: through the resistor divider,
+//! with readings that stray from the rest thrown out before averaging.
+use heapless::HistoryBuffer;
+
+/// Readings averaged over, at 10 a second this is the last 1.6 s.
+pub const WINDOW: usize = 16;
+/// No voltage is reported from fewer readings than this.
+pub const MIN_SAMPLES: usize = 4;
+/// Readings further than this from the median, at the pin, are left out. That is
+/// about 0.3 V of battery voltage with the default divider, injectors and the
+/// starter motor make spikes like that.
+pub const OUTLIER_MV: u16 = 50;
+
+/// The resistors between the battery and the ADC pin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divider {
+    /// Battery to pin.
+    pub top_ohms: f32,
+    /// Pin to ground.
+    pub bottom_ohms: f32,
+}
+
+impl Divider {
+    /// How many times the battery voltage is larger than what the pin sees.
+    pub fn ratio(&self) -> f32 {
+        (self.top_ohms + self.bottom_ohms) / self.bottom_ohms
+    }
+}
+
+impl Default for Divider {
+    /// 100k over 22k puts 16 V at 2.9 V, inside the 11 dB range.
+    fn default() -> Self {
+        Divider { top_ohms: 100_000.0, bottom_ohms: 22_000.0 }
+    }
+}
+
+/// The last `WINDOW` readings, averaged without the outliers.
+pub struct VoltageFilter {
+    pub divider: Divider,
+    /// Readings at or above this are clipped by the ADC and mean nothing.
+    pub full_scale_mv: u16,
+    samples: HistoryBuffer<u16, WINDOW>,
+}
+
+impl VoltageFilter {
+    pub fn new(divider: Divider, full_scale_mv: u16) -> Self {
+        VoltageFilter { divider, full_scale_mv, samples: HistoryBuffer::new() }
+    }
+
+    /// Adds a calibrated reading of the pin in millivolts.
+    pub fn push(&mut self, pin_mv: u16) {
+        self.samples.write(pin_mv);
+    }
+
+    /// The battery voltage in volts, `None` until there are enough readings or when
+    /// the ADC is clipping.
+    pub fn voltage(&self) -> Option<f32> {
+        if self.samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut sorted: heapless::Vec<u16, WINDOW> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let median = sorted[sorted.len() / 2];
+        if median >= self.full_scale_mv {
+            return None;
+        }
+        let (sum, count) = sorted
+            .iter()
+            .filter(|sample| sample.abs_diff(median) <= OUTLIER_MV)
+            .fold((0u32, 0u32), |(sum, count), sample| (sum + *sample as u32, count + 1));
+        // The median itself always counts, so this never divides by zero
+        Some(sum as f32 / count as f32 / 1000.0 * self.divider.ratio())
+    }
+}
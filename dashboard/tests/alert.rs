@@ -0,0 +1,144 @@
+mod common;
+
+use common::frame_buf;
+use dashboard::{
+    alert::{self, default_rules, AlertEngine, Condition, Priority, Rule, Source},
+    car_state::{CarField, CarState},
+    screen::Screen,
+};
+
+fn coolant_rule() -> Rule {
+    Rule {
+        message: "COOLANT HOT",
+        source: Source::Field(CarField::CoolantTemp),
+        condition: Condition::Above(110.0),
+        hysteresis: 5.0,
+        debounce_ms: 1000,
+        priority: Priority::Critical,
+    }
+}
+
+fn coolant(celsius: f32) -> CarState {
+    let mut state = CarState::default();
+    state.set(CarField::CoolantTemp, celsius);
+    state
+}
+
+#[test]
+fn alert_waits_out_the_debounce_time() {
+    let mut engine = AlertEngine::new(vec![coolant_rule()]);
+    engine.update(&coolant(112.0), 0);
+    engine.update(&coolant(112.0), 999);
+    assert!(!engine.alerts[0].active);
+    engine.update(&coolant(112.0), 1000);
+    assert!(engine.alerts[0].active);
+}
+
+#[test]
+fn a_short_spike_does_not_alert() {
+    let mut engine = AlertEngine::new(vec![coolant_rule()]);
+    engine.update(&coolant(112.0), 0);
+    engine.update(&coolant(100.0), 500);
+    engine.update(&coolant(112.0), 900);
+    engine.update(&coolant(112.0), 1500);
+    assert!(!engine.alerts[0].active);
+}
+
+#[test]
+fn hysteresis_keeps_the_alert_until_well_below_the_limit() {
+    let mut engine = AlertEngine::new(vec![coolant_rule()]);
+    engine.update(&coolant(112.0), 0);
+    engine.update(&coolant(112.0), 1000);
+    engine.update(&coolant(107.0), 2000);
+    engine.update(&coolant(107.0), 5000);
+    assert!(engine.alerts[0].active);
+    engine.update(&coolant(104.0), 6000);
+    engine.update(&coolant(104.0), 7000);
+    assert!(!engine.alerts[0].active);
+}
+
+#[test]
+fn banner_shows_the_most_urgent_unacknowledged_alert() {
+    let mut engine = AlertEngine::new(default_rules());
+    let mut state = CarState::default();
+    state.set(CarField::EngineRpm, 7000.0);
+    state.set(CarField::VehicleSpeed, 120.0);
+    state.set(CarField::CoolantTemp, 90.0);
+    state.set_voltage(11.2);
+    for now_ms in (0..=6000).step_by(100) {
+        engine.update(&state, now_ms);
+    }
+    let message = |engine: &AlertEngine| engine.banner().map(|index| engine.alerts[index].rule.message);
+    assert_eq!(message(&engine), Some("OVER REV"));
+    state.set(CarField::OilPressureWarning, 1.0);
+    engine.update(&state, 6100);
+    engine.update(&state, 6600);
+    assert_eq!(message(&engine), Some("OIL PRESSURE"));
+    assert!(engine.acknowledge());
+    assert_eq!(message(&engine), Some("OVER REV"));
+    assert!(engine.acknowledge());
+    assert_eq!(message(&engine), Some("LOW BATTERY"));
+    assert!(engine.acknowledge());
+    assert_eq!(message(&engine), None);
+    assert!(!engine.acknowledge());
+    assert_eq!(engine.active().count(), 3);
+}
+
+#[test]
+fn no_data_only_raises_the_lost_alerts() {
+    let mut engine = AlertEngine::new(default_rules());
+    engine.update(&CarState::default(), 0);
+    assert_eq!(engine.active().count(), 0);
+    for now_ms in (0..=10_000).step_by(500) {
+        engine.update(&CarState::default(), now_ms);
+    }
+    let active: Vec<&str> = engine.active().map(|alert| alert.rule.message).collect();
+    assert_eq!(active, ["SPEED LOST", "RPM LOST", "COOLANT LOST"]);
+}
+
+/// Once the banner goes away the screen has to look as if it was never there.
+#[test]
+fn cleared_banner_leaves_no_trace() {
+    let render = |alarm: bool| {
+        let mut screen = Screen::new();
+        let mut frame_buf = frame_buf();
+        screen.draw_static(&mut frame_buf);
+        let mut state = CarState::default();
+        state.set(CarField::VehicleSpeed, 50.0);
+        for index in 0..90u64 {
+            let oil = if alarm && (10..40).contains(&index) { 1.0 } else { 0.0 };
+            state.set(CarField::OilPressureWarning, oil);
+            screen.update(&state, index * 33);
+            screen.draw(&mut frame_buf);
+            if index == 30 {
+                assert_eq!(screen.alerts.banner().is_some(), alarm);
+            }
+        }
+        frame_buf.data.to_vec()
+    };
+    assert!(render(true) == render(false));
+}
+
+#[test]
+fn history_keeps_when_alerts_went_off_and_cleared() {
+    let mut engine = AlertEngine::new(vec![coolant_rule()]);
+    for (now_ms, celsius) in [(0, 112.0), (1000, 112.0), (2000, 100.0), (3000, 100.0), (4000, 115.0), (5000, 115.0)] {
+        engine.update(&coolant(celsius), now_ms);
+    }
+    let history: Vec<_> = engine.history.iter().map(|entry| (entry.raised_ms, entry.cleared_ms)).collect();
+    assert_eq!(history, [(1000, Some(3000)), (5000, None)]);
+}
+
+#[test]
+fn history_drops_the_oldest_entries() {
+    let mut engine = AlertEngine::new(vec![coolant_rule()]);
+    for round in 0..alert::HISTORY_LEN as u64 + 3 {
+        let start = round * 4000;
+        engine.update(&coolant(112.0), start);
+        engine.update(&coolant(112.0), start + 1000);
+        engine.update(&coolant(100.0), start + 2000);
+        engine.update(&coolant(100.0), start + 3000);
+    }
+    assert_eq!(engine.history.len(), alert::HISTORY_LEN);
+    assert_eq!(engine.history.front().unwrap().raised_ms, 3 * 4000 + 1000);
+}
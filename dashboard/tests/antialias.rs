@@ -0,0 +1,52 @@
+mod common;
+
+use common::frame_buf;
+use dashboard::{
+    antialias::{self, blend, PointF},
+    gauge::DashboardContext,
+    screen::ScreenGauge,
+};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+
+#[test]
+fn blend_goes_from_background_to_colour() {
+    assert_eq!(blend(Rgb565::BLACK, Rgb565::WHITE, 0.0), Rgb565::BLACK);
+    assert_eq!(blend(Rgb565::BLACK, Rgb565::WHITE, 1.0), Rgb565::WHITE);
+    let half = blend(Rgb565::BLACK, Rgb565::WHITE, 0.5);
+    assert_eq!((half.r(), half.g(), half.b()), (16, 32, 16));
+}
+
+#[test]
+fn line_edges_are_partially_covered() {
+    let mut frame_buf = frame_buf();
+    antialias::line(
+        &mut frame_buf,
+        PointF::new(10.0, 10.0),
+        PointF::new(100.0, 40.0),
+        3.0,
+        Rgb565::WHITE,
+        Rgb565::BLACK,
+    );
+    let partial = frame_buf
+        .data
+        .iter()
+        .filter(|pixel| **pixel != Rgb565::BLACK && **pixel != Rgb565::WHITE)
+        .count();
+    assert!(frame_buf.data.contains(&Rgb565::WHITE));
+    assert!(partial > 100, "only {} blended pixels", partial);
+}
+
+#[test]
+fn needle_moves_in_fractions_of_a_degree() {
+    let context = DashboardContext::<240, 240>::new();
+    let render = |value: f32| {
+        let mut gauge = ScreenGauge::new_speedo();
+        gauge.settle_at(value);
+        let mut frame_buf = frame_buf();
+        gauge.draw_clear_mask(&mut frame_buf, &context);
+        gauge.draw_dynamic(&mut frame_buf, &context);
+        frame_buf.data.to_vec()
+    };
+    // 0.2 km/h is about a quarter of a degree on the speedo
+    assert_ne!(render(100.0), render(100.2));
+}
@@ -0,0 +1,51 @@
+use dashboard::bitrate::{Bitrate, Probe, MIN_PROBE_FRAMES};
+
+fn clean(frames: usize) -> Probe {
+    Probe { frames, errors: 0, error_count_before: 0, error_count_after: 0 }
+}
+
+#[test]
+fn clean_traffic_is_a_match() {
+    assert!(clean(MIN_PROBE_FRAMES).is_match());
+    assert!(clean(100).is_match());
+    // The counter going down is fine, it only grows on errors
+    assert!(Probe { error_count_before: 8, error_count_after: 7, ..clean(5) }.is_match());
+}
+
+#[test]
+fn a_frame_or_two_is_not_enough() {
+    assert!(!clean(0).is_match());
+    assert!(!clean(MIN_PROBE_FRAMES - 1).is_match());
+}
+
+#[test]
+fn errors_rule_a_bitrate_out() {
+    assert!(!Probe { errors: 1, ..clean(50) }.is_match());
+    // Errors the driver did not report still show in the receive error counter
+    assert!(!Probe { error_count_before: 0, error_count_after: 1, ..clean(50) }.is_match());
+}
+
+#[test]
+fn records_round_trip() {
+    for bitrate in Bitrate::ALL {
+        assert_eq!(Bitrate::from_record(&bitrate.to_record()), Some(bitrate));
+        assert_eq!(Bitrate::from_kbps(bitrate.kbps()), Some(bitrate));
+    }
+    // Whatever follows the record is not looked at
+    let mut longer = Bitrate::K250.to_record().to_vec();
+    longer.push(0x42);
+    assert_eq!(Bitrate::from_record(&longer), Some(Bitrate::K250));
+}
+
+#[test]
+fn erased_or_damaged_records_read_as_none() {
+    assert_eq!(Bitrate::from_record(&[0xFF, 0xFF, 0xFF]), None);
+    assert_eq!(Bitrate::from_record(&[]), None);
+    assert_eq!(Bitrate::from_record(&Bitrate::K500.to_record()[..2]), None);
+    let [magic, code, check] = Bitrate::K500.to_record();
+    // A flipped bit in the code no longer matches its complement
+    assert_eq!(Bitrate::from_record(&[magic, code ^ 0x01, check]), None);
+    assert_eq!(Bitrate::from_record(&[!magic, code, check]), None);
+    // Consistent, but not a bitrate: 3 × 125k
+    assert_eq!(Bitrate::from_record(&[magic, 3, !3]), None);
+}
@@ -0,0 +1,68 @@
+use dashboard::{
+    bitrate::Bitrate,
+    bus_health::{BusMonitor, ErrorState, Recovery, MAX_RECOVERY_MS, STABLE_MS},
+    frame::CanFrame,
+};
+use embedded_can::{Frame, StandardId};
+
+#[test]
+fn error_state_follows_the_counters() {
+    let mut monitor = BusMonitor::new(Bitrate::K500);
+    assert_eq!(monitor.on_counters(8, 0, false), None);
+    assert_eq!(monitor.on_counters(8, 130, false), Some(ErrorState::Passive));
+    assert_eq!(monitor.on_counters(0, 0, true), Some(ErrorState::BusOff));
+    assert_eq!(monitor.on_counters(0, 0, true), None);
+    assert_eq!(monitor.on_counters(0, 0, false), Some(ErrorState::Active));
+    monitor.on_counters(0, 0, true);
+    assert_eq!(monitor.health().bus_off_count, 2);
+}
+
+#[test]
+fn load_and_error_rate_are_worked_out_per_window() {
+    let mut monitor = BusMonitor::new(Bitrate::K500);
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[0; 8]).unwrap();
+    assert_eq!(monitor.update(0), None);
+    for _ in 0..500 {
+        monitor.on_frame(&frame);
+    }
+    for _ in 0..5 {
+        monitor.on_error();
+    }
+    assert_eq!(monitor.update(999), None);
+    let health = monitor.update(1000).unwrap();
+    assert_eq!(health.frames_per_s, 500.0);
+    // 500 frames of 135 bits out of 500 kbit
+    assert!((health.bus_load - 13.5).abs() < 0.01, "{}", health.bus_load);
+    assert!((health.error_rate - 0.99).abs() < 0.01, "{}", health.error_rate);
+    // An idle window
+    assert_eq!(monitor.update(2000).unwrap().frames_per_s, 0.0);
+}
+
+/// When recoveries start while the bus stays off, polling every 10 ms.
+fn attempts(recovery: &mut Recovery, from_ms: u64, until_ms: u64) -> Vec<u64> {
+    (from_ms..until_ms).step_by(10).filter(|now_ms| recovery.poll(ErrorState::BusOff, *now_ms)).collect()
+}
+
+#[test]
+fn recovery_backs_off_while_the_bus_stays_off() {
+    let mut recovery = Recovery::default();
+    // Each wait starts at the first poll still seeing the bus off after an attempt
+    assert_eq!(attempts(&mut recovery, 0, 1600), [100, 310, 720, 1530]);
+    // Capped, plus the one poll
+    let later = attempts(&mut recovery, 1600, 30_000);
+    let gaps: Vec<_> = later.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    assert!(gaps.iter().all(|gap| *gap <= MAX_RECOVERY_MS + 10), "{:?}", gaps);
+    assert_eq!(gaps.last(), Some(&(MAX_RECOVERY_MS + 10)));
+}
+
+#[test]
+fn a_stable_bus_resets_the_backoff() {
+    let mut recovery = Recovery::default();
+    attempts(&mut recovery, 0, 1600);
+    assert!(!recovery.poll(ErrorState::Active, 2000));
+    assert!(!recovery.poll(ErrorState::Active, 2000 + STABLE_MS - 1));
+    assert_eq!(recovery.attempts(), 4);
+    assert!(!recovery.poll(ErrorState::Active, 2000 + STABLE_MS));
+    assert_eq!(recovery.attempts(), 0);
+    assert_eq!(attempts(&mut recovery, 20_000, 20_200), [20_100]);
+}
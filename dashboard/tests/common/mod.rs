@@ -0,0 +1,116 @@
+//! Golden-image helpers shared by the rendering tests.
+//!
+//! Reference images live in `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to
+//! (re)write them after an intended visual change, and review the PNGs in the diff.
+#![allow(dead_code)]
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use dashboard::framebuffer::HeapBuffer;
+use embedded_graphics::{
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::*,
+};
+use embedded_graphics_framebuf::FrameBuf;
+
+pub const WIDTH: usize = 240;
+pub const HEIGHT: usize = 240;
+const BUFFER_SIZE: usize = WIDTH * HEIGHT;
+
+pub type TestFrameBuf = FrameBuf<Rgb565, HeapBuffer<Rgb565, BUFFER_SIZE>>;
+
+pub fn frame_buf() -> TestFrameBuf {
+    let data = vec![Rgb565::BLACK; BUFFER_SIZE].into_boxed_slice();
+    TestFrameBuf::new(HeapBuffer::new(data.try_into().unwrap()), WIDTH, HEIGHT)
+}
+
+fn to_rgb(frame_buf: &TestFrameBuf) -> Vec<u8> {
+    frame_buf
+        .data
+        .iter()
+        .flat_map(|pixel| {
+            let rgb = Rgb888::from(*pixel);
+            [rgb.r(), rgb.g(), rgb.b()]
+        })
+        .collect()
+}
+
+fn write_png(path: &Path, rgb: &[u8]) {
+    let writer = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(writer, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(rgb).unwrap();
+}
+
+fn read_png(path: &Path) -> Option<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut rgb = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgb).ok()?;
+    if info.color_type != png::ColorType::Rgb || info.width as usize != WIDTH || info.height as usize != HEIGHT {
+        return None;
+    }
+    rgb.truncate(info.buffer_size());
+    Some(rgb)
+}
+
+/// Differing pixels in red over a dimmed copy of the reference.
+fn diff_image(expected: &[u8], actual: &[u8]) -> Vec<u8> {
+    expected
+        .chunks(3)
+        .zip(actual.chunks(3))
+        .flat_map(|(expected, actual)| {
+            if expected == actual {
+                let grey = ((expected[0] as u16 + expected[1] as u16 + expected[2] as u16) / 9) as u8;
+                [grey, grey, grey]
+            } else {
+                [255, 0, 0]
+            }
+        })
+        .collect()
+}
+
+/// Compares the framebuffer against `tests/snapshots/<name>.png`. On a mismatch the
+/// actual render and a diff image are written next to the test binaries.
+pub fn assert_snapshot(name: &str, frame_buf: &TestFrameBuf) {
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.png", name));
+    let actual = to_rgb(frame_buf);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        write_png(&reference, &actual);
+        return;
+    }
+    let Some(expected) = read_png(&reference) else {
+        panic!(
+            "No usable reference image at {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            reference.display()
+        );
+    };
+    if expected == actual {
+        return;
+    }
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("snapshots");
+    fs::create_dir_all(&out).unwrap();
+    let actual_path = out.join(format!("{}.actual.png", name));
+    let diff_path = out.join(format!("{}.diff.png", name));
+    write_png(&actual_path, &actual);
+    write_png(&diff_path, &diff_image(&expected, &actual));
+    let differing = expected
+        .chunks(3)
+        .zip(actual.chunks(3))
+        .filter(|(expected, actual)| expected != actual)
+        .count();
+    panic!(
+        "{} differs from its reference in {} pixels, see {} and {}",
+        name,
+        differing,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{frame_buf, HEIGHT, WIDTH};
+use dashboard::{
+    car_state::{CarField, CarState},
+    damage::{copy_regions, region, Damage, Tracked},
+    screen::Screen,
+};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*, primitives::Rectangle};
+
+fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+    Rectangle::new(Point::new(x, y), Size::new(width, height))
+}
+
+#[test]
+fn overlapping_rects_merge() {
+    let mut damage = Damage::new();
+    damage.add(rect(0, 0, 10, 10));
+    damage.add(rect(5, 5, 10, 10));
+    assert_eq!(damage.rects(), &[rect(0, 0, 15, 15)]);
+}
+
+#[test]
+fn distant_rects_stay_apart() {
+    let mut damage = Damage::new();
+    damage.add(rect(0, 0, 10, 10));
+    damage.add(rect(100, 100, 10, 10));
+    assert_eq!(damage.rects().len(), 2);
+    assert_eq!(damage.area(), 200);
+}
+
+#[test]
+fn too_many_rects_get_merged() {
+    let mut damage = Damage::new();
+    for index in 0..20 {
+        damage.add(rect(index * 12, index * 12, 4, 4));
+    }
+    assert!(damage.rects().len() <= dashboard::damage::MAX_RECTS);
+    for index in 0..20 {
+        let point = Point::new(index * 12, index * 12);
+        assert!(damage.rects().iter().any(|rect| rect.contains(point)));
+    }
+}
+
+/// Copying only the damaged regions every frame has to give the same picture as the
+/// framebuffer, while moving far fewer pixels than full frames.
+#[test]
+fn flushing_damage_keeps_the_display_in_sync() {
+    let mut screen = Screen::new();
+    let mut frame_buf = frame_buf();
+    let mut display = vec![Rgb565::BLACK; WIDTH * HEIGHT];
+    let mut damage = Damage::new();
+    let mut state = CarState::default();
+    let mut flushed = 0;
+    let frames = 60;
+    screen.draw_static(&mut Tracked::new(&mut frame_buf, &mut damage));
+    for index in 0..frames {
+        state.set(CarField::VehicleSpeed, (index * 3) as f32);
+        state.set(CarField::EngineRpm, (800 + index * 40) as f32);
+        screen.update(&state, index as u64 * 33);
+        screen.draw(&mut Tracked::new(&mut frame_buf, &mut damage));
+        for area in damage.rects() {
+            let pixels: Vec<_> = region(&frame_buf.data[..], WIDTH, *area).collect();
+            let mut pixels = pixels.into_iter();
+            for y in area.rows() {
+                for x in area.columns() {
+                    display[y as usize * WIDTH + x as usize] = pixels.next().unwrap();
+                }
+            }
+            flushed += area.size.width * area.size.height;
+        }
+        damage.clear();
+    }
+    assert!(display[..] == frame_buf.data[..]);
+    // The first frame is a full one, after that only the needle and readouts move
+    let full = (WIDTH * HEIGHT) as u32;
+    assert!(flushed < full * frames / 4, "{} pixels flushed over {} frames", flushed, frames);
+}
+
+#[test]
+fn copy_regions_only_touches_the_damage() {
+    let from: Vec<u16> = (0..100).collect();
+    let mut to = vec![0u16; 100];
+    copy_regions(&from, &mut to, 10, &[rect(2, 3, 3, 2)]);
+    for (index, value) in to.iter().enumerate() {
+        let (x, y) = (index % 10, index / 10);
+        let inside = (2..5).contains(&x) && (3..5).contains(&y);
+        assert_eq!(*value, if inside { index as u16 } else { 0 });
+    }
+}
@@ -0,0 +1,121 @@
+#[path = "../build/dbc.rs"]
+#[allow(dead_code)]
+mod dbc_parser;
+
+use dashboard::dbc::{ByteOrder, MessageDef, Multiplex, SignalDef};
+use dbc_parser::{Dbc, Message, Signal};
+
+const FIXTURE: &str = include_str!("data/fixture.dbc");
+
+fn leak(text: &str) -> &'static str {
+    Box::leak(text.to_string().into_boxed_str())
+}
+
+/// What `build.rs` would generate for `signal`.
+fn signal_def(signal: &Signal) -> SignalDef {
+    SignalDef {
+        name: leak(&signal.name),
+        start_bit: signal.start_bit,
+        length: signal.length,
+        byte_order: if signal.big_endian { ByteOrder::BigEndian } else { ByteOrder::LittleEndian },
+        signed: signal.signed,
+        factor: signal.factor as f32,
+        offset: signal.offset as f32,
+        min: signal.min as f32,
+        max: signal.max as f32,
+        unit: leak(&signal.unit),
+        multiplex: match signal.multiplex {
+            None => Multiplex::None,
+            Some(None) => Multiplex::Multiplexor,
+            Some(Some(value)) => Multiplex::Multiplexed(value),
+        },
+    }
+}
+
+fn message_def(message: &Message) -> MessageDef {
+    MessageDef {
+        id: message.id,
+        extended: message.extended,
+        name: leak(&message.name),
+        dlc: message.dlc,
+        cycle_ms: message.cycle_ms,
+        signals: Box::leak(message.signals.iter().map(signal_def).collect::<Vec<_>>().into_boxed_slice()),
+    }
+}
+
+fn fixture(name: &str) -> MessageDef {
+    let dbc = Dbc::parse(FIXTURE).unwrap();
+    message_def(dbc.messages.values().find(|message| message.name == name).unwrap())
+}
+
+fn decode(message: &MessageDef, signal: &str, data: &[u8]) -> Option<f32> {
+    let signal = message.signals.iter().find(|def| def.name == signal).unwrap();
+    message.decode(signal, data)
+}
+
+#[test]
+fn the_fixture_parses() {
+    let dbc = Dbc::parse(FIXTURE).unwrap();
+    assert_eq!(dbc.messages.len(), 3);
+    let muxed = fixture("Muxed");
+    assert_eq!((muxed.id, muxed.extended), (0x400, true));
+    assert_eq!(fixture("IntelData").cycle_ms, Some(20));
+    assert_eq!(fixture("MotorolaData").cycle_ms, None);
+    assert_eq!(dbc.bindings.get(&(100, "Rpm".to_string())).map(String::as_str), Some("engine_rpm"));
+}
+
+#[test]
+fn intel_signals_with_scaling() {
+    let intel = fixture("IntelData");
+    let data = [0x48, 0x26, 0xF6, 200, 0, 0, 0, 0];
+    assert_eq!(decode(&intel, "Rpm", &data), Some(2450.0));
+    // -10 raw, times 0.5, minus 10
+    assert_eq!(decode(&intel, "Temp", &data), Some(-15.0));
+    // Clamped to the DBC range
+    assert_eq!(decode(&intel, "Level", &data), Some(100.0));
+    // Too short for the signal
+    assert_eq!(decode(&intel, "Level", &data[..3]), None);
+}
+
+#[test]
+fn motorola_signals_run_across_bytes_msb_first() {
+    let motorola = fixture("MotorolaData");
+    let data = [0x22, 0x60, 0x0F, 0xFE, 0, 0, 0, 0];
+    assert_eq!(decode(&motorola, "Speed", &data), Some(88.0));
+    // The low nibble of byte 2, then byte 3: 0xFFE, a 12 bit -2, and an unbounded range
+    assert_eq!(decode(&motorola, "Offset", &data), Some(-2.0));
+    assert_eq!(decode(&motorola, "Offset", &[0, 0, 0x07, 0xFF]), Some(2047.0));
+}
+
+#[test]
+fn multiplexed_signals_only_decode_when_selected() {
+    let muxed = fixture("Muxed");
+    let first = [0, 0x34, 0x12, 0, 0, 0, 0, 0];
+    assert_eq!(decode(&muxed, "Plain", &first), Some(4660.0));
+    assert_eq!(decode(&muxed, "Signed", &first), None);
+    let second = [1, 0xFF, 0xFF, 0, 0, 0, 0, 0];
+    assert_eq!(decode(&muxed, "Plain", &second), None);
+    assert_eq!(decode(&muxed, "Signed", &second), Some(-1.0));
+}
+
+#[test]
+fn malformed_lines_are_rejected() {
+    let message = "BO_ 100 IntelData: 8 ECU\n";
+    for (source, expected) in [
+        ("BO_ 100 IntelData 8 ECU\n".to_string(), "line 1: BO_ without ':'"),
+        ("BO_ 100 IntelData: ECU\n".to_string(), "BO_ without a valid dlc"),
+        (" SG_ Rpm : 0|16@1+ (1,0) [0|1] \"\" DASH\n".to_string(), "SG_ outside of a BO_"),
+        (format!("{} SG_ Rpm : 0|16@2+ (1,0) [0|1] \"\" DASH\n", message), "line 2: Invalid byte order in Rpm"),
+        (format!("{} SG_ Rpm : 0|16@1* (1,0) [0|1] \"\" DASH\n", message), "Invalid sign in Rpm"),
+        (format!("{} SG_ Rpm : 0|0@1+ (1,0) [0|1] \"\" DASH\n", message), "unsupported length of 0 bits"),
+        (format!("{} SG_ Rpm : 0|65@1+ (1,0) [0|1] \"\" DASH\n", message), "unsupported length of 65 bits"),
+        (format!("{} SG_ Rpm : 0|16@1+ [0|1] \"\" DASH\n", message), "Missing (factor,offset) in Rpm"),
+        (format!("{} SG_ Rpm : 0|16@1+ (x,0) [0|1] \"\" DASH\n", message), "Invalid number x in Rpm"),
+        (format!("{} SG_ Rpm mX : 0|16@1+ (1,0) [0|1] \"\" DASH\n", message), "Invalid multiplex indicator mX"),
+        (format!("{}\nBA_ \"CarStateField\" SG_ 100 Speed \"vehicle_speed\";\n", message), "bound to unknown signal Speed"),
+        ("BA_ \"GenMsgCycleTime\" BO_ 300 20;\n".to_string(), "GenMsgCycleTime for unknown message 300"),
+    ] {
+        let error = Dbc::parse(&source).err().unwrap_or_else(|| panic!("{:?} parsed", source));
+        assert!(error.contains(expected), "{:?} gave {:?}", source, error);
+    }
+}
@@ -0,0 +1,82 @@
+use dashboard::{
+    car_state::CarState,
+    dtc::{parse_reply, ClearConfirmation, Dtc, DtcKind, DtcReply, CLEAR_CONFIRM_MS},
+    frame::CanFrame,
+    isotp::IsoTpMessage,
+};
+use embedded_can::{Frame, Id, StandardId};
+
+fn reply(ecu: u16, data: &[u8]) -> IsoTpMessage {
+    IsoTpMessage { id: Id::Standard(StandardId::new(0x7E8 + ecu).unwrap()), data: data.to_vec() }
+}
+
+/// The service bytes of the requests sent until `until_ms`, one poll every 5 ms like the firmware.
+fn requests(state: &mut CarState, from_ms: u64, until_ms: u64) -> Vec<u8> {
+    (from_ms..until_ms)
+        .step_by(5)
+        .filter_map(|now_ms| state.next_obd_request::<CanFrame>(now_ms))
+        .map(|frame| frame.data()[1])
+        .collect()
+}
+
+#[test]
+fn codes_are_shown_with_their_system_letter() {
+    let codes: Vec<_> = [0x0301, 0x4035, 0x9234, 0xC100].map(|raw| format!("{}", Dtc(raw))).to_vec();
+    assert_eq!(codes, ["P0301", "C0035", "B1234", "U0100"]);
+    assert_eq!(Dtc(0x0301).description(), Some("Cylinder 1 misfire"));
+    assert_eq!(Dtc(0x1234).description(), None);
+}
+
+#[test]
+fn replies_parse_with_and_without_the_count_byte() {
+    let codes = |kind, raw: &[u16]| DtcReply::Codes(kind, raw.iter().map(|raw| Dtc(*raw)).collect());
+    // CAN: mode, count, codes
+    assert_eq!(parse_reply(&[0x43, 0x02, 0x03, 0x01, 0x04, 0x20]), Some(codes(DtcKind::Stored, &[0x0301, 0x0420])));
+    // Without the count, padded with an empty code
+    assert_eq!(parse_reply(&[0x47, 0x01, 0x71, 0x00, 0x00]), Some(codes(DtcKind::Pending, &[0x0171])));
+    assert_eq!(parse_reply(&[0x4A, 0x00]), Some(codes(DtcKind::Permanent, &[])));
+    assert_eq!(parse_reply(&[0x44]), Some(DtcReply::Cleared));
+    assert_eq!(parse_reply(&[0x7F, 0x04, 0x22]), Some(DtcReply::ClearRejected(0x22)));
+    assert_eq!(parse_reply(&[0x41, 0x0D, 0x50]), None);
+}
+
+#[test]
+fn codes_are_read_after_the_vin_and_before_the_pids() {
+    let mut state = CarState::default();
+    assert_eq!(requests(&mut state, 0, 120), [0x09, 0x03, 0x07, 0x0A, 0x01]);
+}
+
+#[test]
+fn codes_from_several_ecus_are_merged() {
+    let mut state = CarState::default();
+    state.process_isotp(&reply(0, &[0x43, 0x02, 0x03, 0x01, 0x04, 0x20]));
+    state.process_isotp(&reply(1, &[0x43, 0x01, 0x03, 0x01]));
+    state.process_isotp(&reply(0, &[0x47, 0x01, 0x01, 0x71]));
+    let codes: Vec<_> = state.dtcs().codes.iter().map(|(kind, dtc)| (*kind, dtc.0)).collect();
+    assert_eq!(codes, [(DtcKind::Stored, 0x0301), (DtcKind::Stored, 0x0420), (DtcKind::Pending, 0x0171)]);
+}
+
+#[test]
+fn clearing_takes_two_long_presses() {
+    let mut gesture = ClearConfirmation::default();
+    assert!(!gesture.press(1000));
+    assert!(gesture.armed(1000 + CLEAR_CONFIRM_MS - 1));
+    // Too late, this one arms again
+    assert!(!gesture.press(1000 + CLEAR_CONFIRM_MS));
+    assert!(gesture.press(2000 + CLEAR_CONFIRM_MS));
+    assert!(!gesture.armed(2000 + CLEAR_CONFIRM_MS));
+}
+
+#[test]
+fn a_confirmed_clear_sends_mode_04_and_reads_the_codes_again() {
+    let mut state = CarState::default();
+    requests(&mut state, 0, 100);
+    state.process_isotp(&reply(0, &[0x43, 0x01, 0x03, 0x01]));
+    assert!(!state.confirm_clear_dtcs(1000));
+    assert!(!requests(&mut state, 1000, 1100).contains(&0x04));
+    assert!(state.confirm_clear_dtcs(2000));
+    assert_eq!(requests(&mut state, 2000, 2020)[0], 0x04);
+    state.process_isotp(&reply(0, &[0x44]));
+    assert!(state.dtcs().is_empty());
+    assert_eq!(&requests(&mut state, 3000, 3200)[..3], [0x03, 0x07, 0x0A]);
+}
@@ -0,0 +1,58 @@
+use dashboard::{dbc, filter::{decoded_ids, plan, FilterPlan}};
+use embedded_can::{ExtendedId, Id, StandardId};
+
+fn standard(ids: &[u16]) -> Vec<Id> {
+    ids.iter().map(|id| Id::Standard(StandardId::new(*id).unwrap())).collect()
+}
+
+/// Counts by brute force what the plan claims about itself.
+fn check(ids: &[Id]) -> u32 {
+    let plan = plan(ids);
+    for id in ids {
+        assert!(plan.accepts(*id), "{:?} rejects wanted {:?}", plan, id);
+    }
+    let accepted = (0..=0x7FF)
+        .map(|raw| Id::Standard(StandardId::new(raw).unwrap()))
+        .filter(|id| plan.accepts(*id))
+        .count();
+    assert_eq!(accepted as u32 - plan.wanted as u32, plan.unwanted_accepted);
+    plan.unwanted_accepted
+}
+
+#[test]
+fn obd_responses_fit_one_filter_exactly() {
+    let plan = plan(&standard(&[0x7E8, 0x7E9, 0x7EA, 0x7EB, 0x7EC, 0x7ED, 0x7EE, 0x7EF]));
+    assert!(matches!(plan.filter, FilterPlan::Single(_)));
+    assert_eq!(plan.unwanted_accepted, 0);
+}
+
+#[test]
+fn two_clusters_use_both_filters() {
+    let ids = standard(&[0x0C9, 0x0C8, 0x7E8, 0x7E9]);
+    let plan = plan(&ids);
+    assert!(matches!(plan.filter, FilterPlan::Dual(_, _)));
+    assert_eq!(check(&ids), 0);
+}
+
+#[test]
+fn decoded_ids_are_all_accepted() {
+    let ids = decoded_ids();
+    // The DBC messages with bound signals and the eight OBD-II response ids, whatever `CAN_DBC` points at
+    assert_eq!(ids.len(), dbc::CAR_STATE_UPDATES.len() + (0x7E8..=0x7EF).count());
+    let unwanted = check(&ids);
+    // A single filter would have to let through most of the id space
+    assert!(unwanted < 200, "{} unwanted ids accepted", unwanted);
+}
+
+#[test]
+fn large_sets_still_accept_everything_wanted() {
+    let ids = standard(&(0..40).map(|n| 0x100 + n * 37).collect::<Vec<_>>());
+    check(&ids);
+}
+
+#[test]
+fn mixed_id_kinds_accept_all() {
+    let mut ids = standard(&[0x7E8]);
+    ids.push(Id::Extended(ExtendedId::new(0x18DA_F110).unwrap()));
+    assert_eq!(plan(&ids).filter, FilterPlan::AcceptAll);
+}
@@ -0,0 +1,122 @@
+use dashboard::{
+    alert::{default_rules, AlertEngine, Condition, Priority, Rule, Source},
+    car_state::{CarField, CarState},
+    dbc,
+    frame::CanFrame,
+    freshness::{Freshness, Stamp},
+    isotp::IsoTpMessage,
+};
+use embedded_can::{Frame, Id, StandardId};
+
+fn id(raw: u16) -> Id {
+    Id::Standard(StandardId::new(raw).unwrap())
+}
+
+/// ABS_VehicleSpeed, sent every 20 ms.
+fn speed_frame(kmh: f32) -> CanFrame {
+    let raw = ((kmh * 100.0) as u16).to_be_bytes();
+    CanFrame::new(id(1001), &[raw[0], raw[1], 0, 0, 0, 0, 0, 0]).unwrap()
+}
+
+fn speed_at(now_ms: u64) -> CarState {
+    let mut state = CarState::default();
+    state.set_time(now_ms);
+    state.process_message(speed_frame(88.0));
+    state
+}
+
+#[test]
+fn values_go_late_then_lost() {
+    let stamp = Stamp { updated_ms: 1000, period_ms: 500 };
+    assert_eq!(stamp.freshness(2500), Freshness::Fresh);
+    assert_eq!(stamp.freshness(2501), Freshness::Late);
+    assert_eq!(stamp.freshness(6000), Freshness::Late);
+    assert_eq!(stamp.freshness(6001), Freshness::Lost);
+}
+
+#[test]
+fn fast_signals_get_some_slack() {
+    let stamp = Stamp { updated_ms: 0, period_ms: 20 };
+    assert_eq!(stamp.freshness(250), Freshness::Fresh);
+    assert_eq!(stamp.freshness(251), Freshness::Late);
+    assert_eq!(stamp.freshness(1001), Freshness::Lost);
+}
+
+#[test]
+fn broadcast_signals_use_the_dbc_cycle_time() {
+    let state = speed_at(5000);
+    assert_eq!(state.vehicle_speed(), Some(88.0));
+    assert_eq!(state.freshness(CarField::VehicleSpeed, 5200), Freshness::Fresh);
+    assert_eq!(state.freshness(CarField::VehicleSpeed, 5300), Freshness::Late);
+    assert_eq!(state.freshness(CarField::VehicleSpeed, 6100), Freshness::Lost);
+    // The temperatures take turns in one message, each comes every third
+    let temperatures = dbc::lookup(id(1440)).unwrap();
+    let coolant = temperatures.bindings.iter().find(|binding| binding.field == CarField::CoolantTemp).unwrap();
+    assert_eq!(temperatures.message.signal_period_ms(coolant.signal), Some(300));
+}
+
+#[test]
+fn obd_readings_use_the_pid_period() {
+    let mut state = CarState::default();
+    state.set_time(1000);
+    // Coolant temperature, asked for every 2 s
+    state.process_isotp(&IsoTpMessage { id: id(0x7E8), data: vec![0x41, 0x05, 130] });
+    assert_eq!(state.coolant_temp(), Some(90.0));
+    assert_eq!(state.freshness(CarField::CoolantTemp, 7000), Freshness::Fresh);
+    assert_eq!(state.freshness(CarField::CoolantTemp, 8000), Freshness::Late);
+    assert_eq!(state.freshness(CarField::CoolantTemp, 22_000), Freshness::Lost);
+}
+
+#[test]
+fn values_set_by_hand_never_go_stale() {
+    let mut state = speed_at(0);
+    state.set(CarField::VehicleSpeed, 50.0);
+    state.set(CarField::EngineRpm, 800.0);
+    assert_eq!(state.freshness(CarField::VehicleSpeed, 1_000_000), Freshness::Fresh);
+    assert_eq!(state.freshness(CarField::EngineRpm, 1_000_000), Freshness::Fresh);
+}
+
+#[test]
+fn signals_that_never_come_are_lost_a_second_after_start_up() {
+    let state = speed_at(900);
+    assert_eq!(state.freshness(CarField::EngineRpm, 1000), Freshness::Fresh);
+    assert_eq!(state.freshness(CarField::EngineRpm, 1001), Freshness::Lost);
+    assert_eq!(state.freshness(CarField::VehicleSpeed, 1001), Freshness::Fresh);
+}
+
+#[test]
+fn alerts_do_not_hold_on_to_lost_values() {
+    let mut engine = AlertEngine::new(vec![Rule {
+        message: "COOLANT HOT",
+        source: Source::Field(CarField::CoolantTemp),
+        condition: Condition::Above(110.0),
+        hysteresis: 5.0,
+        debounce_ms: 1000,
+        priority: Priority::Critical,
+    }]);
+    let mut state = CarState::default();
+    state.set_time(0);
+    // 125 °C, asked for every 2 s
+    state.process_isotp(&IsoTpMessage { id: id(0x7E8), data: vec![0x41, 0x05, 165] });
+    engine.update(&state, 0);
+    engine.update(&state, 1000);
+    assert!(engine.alerts[0].active);
+    // Nothing for more than ten periods
+    engine.update(&state, 21_000);
+    engine.update(&state, 22_000);
+    assert!(!engine.alerts[0].active);
+}
+
+#[test]
+fn a_lost_sensor_raises_an_alert_until_it_is_back() {
+    let mut engine = AlertEngine::new(default_rules());
+    let speed_lost = engine.alerts.iter().position(|alert| alert.rule.message == "SPEED LOST").unwrap();
+    let state = speed_at(0);
+    engine.update(&state, 500);
+    assert!(!engine.alerts[speed_lost].active);
+    engine.update(&state, 1500);
+    assert!(engine.alerts[speed_lost].active);
+    assert_eq!(engine.banner(), Some(speed_lost));
+    engine.update(&speed_at(1600), 1600);
+    assert!(!engine.alerts[speed_lost].active);
+}
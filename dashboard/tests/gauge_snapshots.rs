@@ -0,0 +1,65 @@
+mod common;
+
+use common::{assert_snapshot, frame_buf};
+use dashboard::{gauge::DashboardContext, scale::Scale, screen::ScreenGauge};
+
+/// Renders the speedo the way the firmware does, with the needle settled on `value`.
+fn render(name: &str, value: f32) {
+    render_scale(name, Scale::speedo(), value);
+}
+
+fn render_scale(name: &str, scale: Scale, value: f32) {
+    let context = DashboardContext::<240, 240>::new();
+    let mut gauge = ScreenGauge::new(scale);
+    gauge.settle_at(value);
+    let mut frame_buf = frame_buf();
+    gauge.draw_static(&mut frame_buf, &context);
+    gauge.draw_clear_mask(&mut frame_buf, &context);
+    gauge.draw_dynamic(&mut frame_buf, &context);
+    assert_snapshot(name, &frame_buf);
+}
+
+#[test]
+fn gauge_at_zero() {
+    render("gauge_zero", 0.0);
+}
+
+#[test]
+fn gauge_at_mid_scale() {
+    render("gauge_mid_scale", 125.0);
+}
+
+#[test]
+fn gauge_at_redline() {
+    render("gauge_redline", 200.0);
+}
+
+#[test]
+fn gauge_at_max() {
+    render("gauge_max", 255.0);
+}
+
+#[test]
+fn gauge_above_max_pins_to_max() {
+    render("gauge_above_max", 300.0);
+}
+
+#[test]
+fn gauge_below_zero_pins_to_zero() {
+    render("gauge_below_zero", -20.0);
+}
+
+#[test]
+fn tachometer_in_warning_band() {
+    render_scale("tachometer_warning", Scale::tachometer(), 6000.0);
+}
+
+#[test]
+fn boost_gauge_in_vacuum() {
+    render_scale("boost_vacuum", Scale::boost(), -0.6);
+}
+
+#[test]
+fn temperature_gauge() {
+    render_scale("temperature", Scale::temperature(), 92.0);
+}
@@ -0,0 +1,145 @@
+use dashboard::{
+    frame::CanFrame,
+    isotp::{Channel, Event, FlowControl, IsoTpError, IsoTpMessage},
+};
+use embedded_can::{Frame, Id, StandardId};
+
+fn id(raw: u16) -> Id {
+    Id::Standard(StandardId::new(raw).unwrap())
+}
+
+fn channel(flow_control: FlowControl) -> Channel {
+    Channel::new(id(0x7E8), id(0x7E0), flow_control)
+}
+
+fn frame(data: &[u8]) -> CanFrame {
+    CanFrame::new(id(0x7E8), data).unwrap()
+}
+
+fn transmitted(event: Option<Event<CanFrame>>) -> CanFrame {
+    match event {
+        Some(Event::Transmit(frame)) => frame,
+        other => panic!("expected a frame to send, got {:?}", other),
+    }
+}
+
+fn message(event: Option<Event<CanFrame>>) -> IsoTpMessage {
+    match event {
+        Some(Event::Message(message)) => message,
+        other => panic!("expected a message, got {:?}", other),
+    }
+}
+
+fn error(event: Option<Event<CanFrame>>) -> IsoTpError {
+    match event {
+        Some(Event::Error(error)) => error,
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+/// The first frame of a 20 byte message, 0 to 19.
+fn first_frame() -> CanFrame {
+    frame(&[0x10, 20, 0, 1, 2, 3, 4, 5])
+}
+
+#[test]
+fn a_single_frame_is_a_message() {
+    let mut channel = channel(FlowControl::default());
+    let message = message(channel.on_frame(&frame(&[0x03, 0x41, 0x0D, 0x58, 0, 0, 0, 0]), 0));
+    assert_eq!(message, IsoTpMessage { id: id(0x7E8), data: vec![0x41, 0x0D, 0x58] });
+}
+
+#[test]
+fn a_first_frame_is_answered_with_flow_control_and_reassembled() {
+    let mut channel = channel(FlowControl { block_size: 0, st_min: 5 });
+    let flow_control = transmitted(channel.on_frame(&first_frame(), 0));
+    assert_eq!(flow_control.id(), id(0x7E0));
+    assert_eq!(flow_control.data(), &[0x30, 0, 5, 0, 0, 0, 0, 0]);
+    assert!(channel.on_frame(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), 10).is_none());
+    let message = message(channel.on_frame(&frame(&[0x22, 13, 14, 15, 16, 17, 18, 19]), 20));
+    assert_eq!(message.data, (0..20).collect::<Vec<u8>>());
+}
+
+#[test]
+fn flow_control_is_sent_again_after_each_block() {
+    let mut channel = channel(FlowControl { block_size: 1, st_min: 0 });
+    transmitted(channel.on_frame(&first_frame(), 0));
+    let flow_control = transmitted(channel.on_frame(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), 10));
+    assert_eq!(flow_control.data()[..3], [0x30, 1, 0]);
+    message(channel.on_frame(&frame(&[0x22, 13, 14, 15, 16, 17, 18, 19]), 20));
+}
+
+#[test]
+fn sending_keeps_to_block_size_and_st_min() {
+    let mut channel = channel(FlowControl::default());
+    let payload: Vec<u8> = (0..30).collect();
+    let first: CanFrame = channel.send(&payload, 0).unwrap();
+    assert_eq!(first.data(), &[0x10, 30, 0, 1, 2, 3, 4, 5]);
+    // Nothing more until the peer sends flow control
+    assert!(channel.poll::<CanFrame>(50).is_none());
+    // Blocks of 2 frames, 10 ms apart
+    let consecutive = transmitted(channel.on_frame(&frame(&[0x30, 2, 10]), 100));
+    assert_eq!(consecutive.data(), &[0x21, 6, 7, 8, 9, 10, 11, 12]);
+    assert!(channel.poll::<CanFrame>(105).is_none());
+    assert_eq!(transmitted(channel.poll(110)).data()[0], 0x22);
+    assert!(channel.poll::<CanFrame>(200).is_none());
+    assert_eq!(transmitted(channel.on_frame(&frame(&[0x30, 2, 10]), 300)).data()[0], 0x23);
+    let last = transmitted(channel.poll(310));
+    assert_eq!(last.data(), &[0x24, 27, 28, 29, 0, 0, 0, 0]);
+    assert!(!channel.is_sending());
+}
+
+#[test]
+fn too_many_waits_give_up() {
+    let mut channel = channel(FlowControl::default());
+    channel.send::<CanFrame>(&[0; 20], 0).unwrap();
+    for now_ms in 1..=10 {
+        assert!(channel.on_frame(&frame(&[0x31, 0, 0]), now_ms).is_none());
+    }
+    assert_eq!(error(channel.on_frame(&frame(&[0x31, 0, 0]), 11)), IsoTpError::Timeout);
+    assert!(!channel.is_sending());
+}
+
+#[test]
+fn an_overflow_aborts_the_send() {
+    let mut channel = channel(FlowControl::default());
+    channel.send::<CanFrame>(&[0; 20], 0).unwrap();
+    assert_eq!(error(channel.on_frame(&frame(&[0x32, 0, 0]), 1)), IsoTpError::Overflow);
+    assert!(!channel.is_sending());
+}
+
+#[test]
+fn a_wrong_sequence_number_drops_the_message() {
+    let mut channel = channel(FlowControl::default());
+    transmitted(channel.on_frame(&first_frame(), 0));
+    assert_eq!(error(channel.on_frame(&frame(&[0x22, 6, 7, 8, 9, 10, 11, 12]), 10)), IsoTpError::WrongSequence);
+    // The rest of the message is ignored
+    assert!(channel.on_frame(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), 20).is_none());
+}
+
+#[test]
+fn a_peer_going_quiet_times_out() {
+    // N_Cr, waiting for the next consecutive frame
+    let mut channel = channel(FlowControl::default());
+    transmitted(channel.on_frame(&first_frame(), 0));
+    assert!(channel.poll::<CanFrame>(1000).is_none());
+    assert_eq!(error(channel.poll(1001)), IsoTpError::Timeout);
+    assert!(channel.on_frame(&frame(&[0x21, 6, 7, 8, 9, 10, 11, 12]), 1010).is_none());
+
+    // N_Bs, waiting for flow control
+    channel.send::<CanFrame>(&[0; 20], 2000).unwrap();
+    assert!(channel.poll::<CanFrame>(3000).is_none());
+    assert_eq!(error(channel.poll(3001)), IsoTpError::Timeout);
+    assert!(!channel.is_sending());
+}
+
+#[test]
+fn payloads_have_to_fit() {
+    let mut channel = channel(FlowControl::default());
+    assert_eq!(channel.send::<CanFrame>(&[], 0).unwrap_err(), IsoTpError::Empty);
+    assert_eq!(channel.send::<CanFrame>(&[0; 4096], 0).unwrap_err(), IsoTpError::TooLong);
+    let single: CanFrame = channel.send(&[0x01, 0x0D], 0).unwrap();
+    assert_eq!(single.data(), &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]);
+    channel.send::<CanFrame>(&[0; 8], 0).unwrap();
+    assert_eq!(channel.send::<CanFrame>(&[0; 8], 0).unwrap_err(), IsoTpError::Busy);
+}
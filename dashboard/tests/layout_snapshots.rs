@@ -0,0 +1,196 @@
+mod common;
+
+use common::{assert_snapshot, frame_buf};
+use dashboard::{
+    bus_health::{BusHealth, ErrorState},
+    car_state::{CarField, CarState},
+    layout,
+    screen::{Screen, Transition},
+    frame::CanFrame,
+    isotp::IsoTpMessage,
+    ring::RingStats,
+    trip::{EconomyUnit, TripId},
+};
+use embedded_can::{Frame, Id, StandardId};
+
+/// Renders the default layout after two seconds at 30 fps, long enough for the needle to settle.
+fn render(name: &str, state: &CarState) {
+    render_frames(name, state, 60);
+}
+
+fn render_frames(name: &str, state: &CarState, frames: u64) {
+    let mut screen = Screen::new();
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    for index in 0..frames {
+        screen.update(state, index * 33);
+        screen.draw(&mut frame_buf);
+    }
+    assert_snapshot(name, &frame_buf);
+}
+
+#[test]
+fn layout_without_data() {
+    // Nothing came for two seconds, so the sensors count as lost
+    render("layout_no_data", &CarState::default());
+}
+
+#[test]
+fn layout_cruising() {
+    let mut state = CarState::default();
+    state.set(CarField::VehicleSpeed, 88.0);
+    state.set(CarField::EngineRpm, 2450.0);
+    state.set(CarField::CoolantTemp, 90.0);
+    state.set(CarField::FuelLevel, 60.0);
+    render("layout_cruising", &state);
+}
+
+#[test]
+fn layout_warning_lights() {
+    let mut state = CarState::default();
+    state.set(CarField::VehicleSpeed, 30.0);
+    state.set(CarField::CoolantTemp, 120.0);
+    for field in [
+        CarField::TurnLeft,
+        CarField::TurnRight,
+        CarField::LowBeam,
+        CarField::HighBeam,
+        CarField::OilPressureWarning,
+        CarField::ChargeWarning,
+        CarField::CheckEngine,
+        CarField::AbsWarning,
+        CarField::SeatbeltWarning,
+    ] {
+        state.set(field, 1.0);
+    }
+    // 1617 ms in, the indicators are in the lit half of a flash
+    render_frames("layout_warning_lights", &state, 50);
+}
+
+#[test]
+fn layout_stale_values() {
+    let mut state = CarState::default();
+    state.set(CarField::CoolantTemp, 90.0);
+    state.set(CarField::FuelLevel, 60.0);
+    state.process_message(CanFrame::new(StandardId::new(1001).unwrap(), &[0x22, 0x60, 0, 0, 0, 0, 0, 0]).unwrap());
+    state.set_time(800);
+    state.process_message(CanFrame::new(StandardId::new(201).unwrap(), &[0x48, 0x26, 0, 0, 0, 0, 0, 0]).unwrap());
+    // The speed has been gone for over a second and shows `--`, the revs are greyed out
+    let mut screen = Screen::new();
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    screen.update(&state, 1100);
+    // SPEED LOST is up, out of the way of the readouts
+    assert!(screen.alerts.acknowledge());
+    screen.update(&state, 1133);
+    screen.draw(&mut frame_buf);
+    assert_snapshot("layout_stale_values", &frame_buf);
+}
+
+#[test]
+fn trip_page() {
+    let mut state = CarState::default();
+    state.set(CarField::FuelRate, 5.4);
+    for (index, speed) in [0.0, 40.0, 72.0, 96.0, 80.0, 0.0, 60.0].iter().enumerate() {
+        state.set(CarField::VehicleSpeed, *speed);
+        for step in 0..600 {
+            state.update_trips((index * 600 + step) as u64 * 100);
+        }
+    }
+    let mut screen = Screen::new();
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    screen.update(&state, 0);
+    screen.draw(&mut frame_buf);
+    // Switching pages has to leave nothing of the gauges behind
+    screen.show(layout::trip_layout(TripId::A, EconomyUnit::LitresPer100Km), Transition::Cut);
+    screen.update(&state, 33);
+    screen.draw(&mut frame_buf);
+    assert_snapshot("trip_page", &frame_buf);
+}
+
+#[test]
+fn diagnostics_page() {
+    let mut state = CarState::default();
+    for _ in 0..1234 {
+        state.process_message(CanFrame::new(StandardId::new(0x123).unwrap(), &[0; 8]).unwrap());
+    }
+    state.set_voltage(12.6);
+    // Something on the gauges, or a LOST alert would cover the page
+    for field in [CarField::VehicleSpeed, CarField::EngineRpm, CarField::CoolantTemp] {
+        state.set(field, 0.0);
+    }
+    state.set_ring_stats(RingStats { pushed: 1300, dropped: 12, coalesced: 54, high_water: 32 });
+    state.set_render_fps(30);
+    state.set_bus_health(BusHealth {
+        frames_per_s: 1840.0,
+        error_rate: 0.4,
+        bus_load: 38.2,
+        tx_errors: 0,
+        rx_errors: 136,
+        state: ErrorState::Passive,
+        ..BusHealth::default()
+    });
+    let mut screen = Screen::with_layout(layout::diagnostics_layout());
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    screen.update(&state, 0);
+    screen.draw(&mut frame_buf);
+    assert_snapshot("diagnostics_page", &frame_buf);
+}
+
+#[test]
+fn alert_history_page() {
+    let mut screen = Screen::with_layout(layout::alert_history_layout());
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    let mut hot = CarState::default();
+    hot.set(CarField::VehicleSpeed, 100.0);
+    hot.set(CarField::EngineRpm, 3000.0);
+    hot.set(CarField::CoolantTemp, 125.0);
+    let mut over_rev = hot.clone();
+    over_rev.set(CarField::EngineRpm, 7500.0);
+    // Coolant goes hot after an hour and 5 minutes, the engine over-revs at 1:20 and
+    // the coolant comes back down, leaving only the over-rev active
+    for (now_ms, state) in [
+        (3_900_000, &hot),
+        (3_902_000, &hot),
+        (4_800_000, &over_rev),
+        (4_800_200, &over_rev),
+        (4_810_000, &over_rev),
+        (4_812_000, &over_rev),
+    ] {
+        screen.update(state, now_ms);
+    }
+    let mut recovered = over_rev.clone();
+    recovered.set(CarField::CoolantTemp, 90.0);
+    for now_ms in [4_820_000, 4_822_000] {
+        screen.update(&recovered, now_ms);
+    }
+    // Out of the way of the list
+    screen.alerts.acknowledge();
+    screen.update(&recovered, 4_822_033);
+    screen.draw(&mut frame_buf);
+    assert_snapshot("alert_history_page", &frame_buf);
+}
+
+#[test]
+fn trouble_codes_page() {
+    let mut state = CarState::default();
+    let engine = Id::Standard(StandardId::new(0x7E8).unwrap());
+    for data in [
+        vec![0x43, 0x03, 0x03, 0x01, 0x04, 0x20, 0x12, 0x34],
+        vec![0x47, 0x01, 0x01, 0x71],
+        vec![0x4A, 0x02, 0x03, 0x01, 0xC1, 0x00],
+    ] {
+        state.process_isotp(&IsoTpMessage { id: engine, data });
+    }
+    // The first long press, the page asks for the second
+    state.confirm_clear_dtcs(0);
+    let mut screen = Screen::with_layout(layout::trouble_codes_layout());
+    let mut frame_buf = frame_buf();
+    screen.draw_static(&mut frame_buf);
+    screen.update(&state, 33);
+    screen.draw(&mut frame_buf);
+    assert_snapshot("trouble_codes_page", &frame_buf);
+}
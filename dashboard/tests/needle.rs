@@ -0,0 +1,52 @@
+use dashboard::needle::{Needle, Response};
+
+/// Positions of a needle starting at 0 after a step to 100, sampled every `frame_ms` for `total_ms`.
+fn step_response(response: Response, frame_ms: u32, total_ms: u32) -> Vec<f32> {
+    let mut needle = Needle::new(response, 0.0);
+    (0..total_ms / frame_ms)
+        .map(|_| {
+            needle.step(100.0, frame_ms as f32 / 1000.0);
+            needle.position
+        })
+        .collect()
+}
+
+fn peak(positions: &[f32]) -> f32 {
+    positions.iter().copied().fold(f32::MIN, f32::max)
+}
+
+#[test]
+fn snap_shows_the_value_straight_away() {
+    assert_eq!(step_response(Response::Snap, 16, 16), [100.0]);
+}
+
+#[test]
+fn default_response_overshoots_a_little_and_settles() {
+    let positions = step_response(Response::default(), 16, 1000);
+    let overshoot = peak(&positions) - 100.0;
+    assert!((2.0..20.0).contains(&overshoot), "overshoot {}", overshoot);
+    assert!((positions.last().unwrap() - 100.0).abs() < 1.0);
+}
+
+#[test]
+fn critically_damped_never_overshoots() {
+    let positions = step_response(Response::spring(12.0, 1.0), 16, 2000);
+    assert!(peak(&positions) <= 100.0);
+    assert!(positions.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!((positions.last().unwrap() - 100.0).abs() < 0.1);
+}
+
+#[test]
+fn speed_does_not_depend_on_the_frame_rate() {
+    let fast = step_response(Response::default(), 10, 300);
+    let slow = step_response(Response::default(), 50, 300);
+    let (fast, slow) = (fast.last().unwrap(), slow.last().unwrap());
+    assert!((fast - slow).abs() < 1.0, "{} at 100 fps, {} at 20 fps", fast, slow);
+}
+
+#[test]
+fn a_stall_does_not_throw_the_needle() {
+    let mut needle = Needle::new(Response::spring(40.0, 0.3), 0.0);
+    needle.step(100.0, 5.0);
+    assert!(needle.position.is_finite() && (0.0..200.0).contains(&needle.position));
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::frame_buf;
+use dashboard::{
+    car_state::{CarField, CarState},
+    page::{Page, PageManager},
+    screen::{Transition, TRANSITION_MS},
+    trip::{EconomyUnit, TripId},
+};
+
+fn cruising() -> CarState {
+    let mut state = CarState::default();
+    state.set(CarField::VehicleSpeed, 88.0);
+    state.set(CarField::EngineRpm, 2450.0);
+    state
+}
+
+#[test]
+fn next_and_previous_wrap_around() {
+    let mut pages = PageManager::new(Page::Gauges, EconomyUnit::LitresPer100Km);
+    let mut screen = pages.screen();
+    pages.previous(&mut screen);
+    assert_eq!(pages.current(), Page::AlertHistory);
+    pages.next(&mut screen);
+    assert_eq!(pages.current(), Page::Gauges);
+    pages.next(&mut screen);
+    assert_eq!(pages.current(), Page::Trip(TripId::A));
+}
+
+#[test]
+fn pages_round_trip_through_their_index_and_name() {
+    for page in Page::ALL {
+        assert_eq!(Page::from_index(page.index()), page);
+        assert_eq!(Page::from_name(page.name()), Some(page));
+    }
+    assert_eq!(Page::from_index(200), Page::Gauges);
+}
+
+#[test]
+fn a_wipe_ends_where_a_cut_would() {
+    let state = cruising();
+    let mut pages = PageManager::new(Page::Diagnostics, EconomyUnit::LitresPer100Km);
+    let mut screen = pages.screen();
+    let mut wiped = frame_buf();
+    screen.draw_static(&mut wiped);
+    screen.update(&state, 0);
+    screen.draw(&mut wiped);
+    pages.previous(&mut screen);
+    let mut now_ms = 1000;
+    while screen.in_transition() {
+        now_ms += 33;
+        screen.update(&state, now_ms);
+        screen.draw(&mut wiped);
+    }
+    assert!(now_ms >= 1033 + TRANSITION_MS);
+
+    // The trip page, straight onto a cleared screen with the same frames behind it
+    let mut cut = PageManager::new(Page::Gauges, EconomyUnit::LitresPer100Km).screen();
+    let mut expected = frame_buf();
+    cut.draw_static(&mut expected);
+    cut.show(Page::Trip(TripId::B).layout(EconomyUnit::LitresPer100Km), Transition::Cut);
+    cut.update(&state, now_ms);
+    cut.draw(&mut expected);
+    assert!(wiped.data.iter().eq(expected.data.iter()));
+}
@@ -0,0 +1,165 @@
+use dashboard::{
+    car_state::CarState,
+    frame::CanFrame,
+    replay::{AscLog, CandumpLog, LogRecord, ReplayMode, ReplaySource},
+    source::{CanEvent, FrameSource},
+};
+use embassy_futures::block_on;
+use embedded_can::{ExtendedId, Frame, Id, StandardId};
+use embedded_hal_async::delay::DelayNs;
+
+const DRIVE_LOG: &str = include_str!("data/drive.log");
+const DRIVE_ASC: &str = include_str!("data/drive.asc");
+/// Time between the first and the last frame of the recorded drive.
+const DRIVE_DURATION_US: u64 = 2_006_000;
+
+/// Records how long the replay asked to wait instead of waiting.
+#[derive(Default)]
+struct FakeDelay {
+    waited_ns: u64,
+}
+
+impl DelayNs for FakeDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.waited_ns += ns as u64;
+    }
+}
+
+fn standard(id: u16) -> Id {
+    Id::Standard(StandardId::new(id).unwrap())
+}
+
+fn replay_drive<L: Iterator<Item = LogRecord> + Clone>(log: L) -> (CarState, u64) {
+    let mut delay = FakeDelay::default();
+    let mut state = CarState::default();
+    let mut source = ReplaySource::new(log, &mut delay, ReplayMode::Once);
+    block_on(async {
+        while let Some(event) = source.next_event().await {
+            state.process_event(event);
+        }
+    });
+    drop(source);
+    (state, delay.waited_ns / 1000)
+}
+
+fn assert_end_of_drive(state: &CarState) {
+    assert_eq!(state.vehicle_speed(), Some(90.0));
+    assert_eq!(state.engine_rpm(), Some(3800.0));
+    assert_eq!(state.coolant_temp(), Some(74.0));
+    assert_eq!(state.intake_air_temp(), Some(25.0));
+    assert!((state.fuel_level().unwrap() - 62.5).abs() < 0.5);
+    assert_eq!(state.vin(), Some("WVWZZZ1KZAW000001"));
+}
+
+#[test]
+fn candump_parses_frames_and_skips_the_rest() {
+    let records: Vec<_> = CandumpLog::new(DRIVE_LOG).collect();
+    // 74 lines, one of them a CAN FD frame
+    assert_eq!(records.len(), 73);
+    assert_eq!(records[0].timestamp_us, 0);
+    assert_eq!(records[0].frame.id(), standard(0x3E9));
+    assert_eq!(records[0].frame.data(), &[0x00, 0x00, 0x80, 0, 0, 0, 0, 0]);
+    assert!(records.iter().any(|record| record.frame.is_remote_frame()));
+    assert_eq!(records.last().unwrap().timestamp_us, DRIVE_DURATION_US);
+}
+
+#[test]
+fn candump_extended_ids_and_short_frames() {
+    let log = "(0.5) vcan0 18DAF110#0322F190\n(1.25) vcan0 7E0#\n";
+    let records: Vec<_> = CandumpLog::new(log).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].frame.id(), Id::Extended(ExtendedId::new(0x18DA_F110).unwrap()));
+    assert_eq!(records[0].frame.data(), &[0x03, 0x22, 0xF1, 0x90]);
+    assert_eq!(records[1].timestamp_us, 750_000);
+    assert_eq!(records[1].frame.dlc(), 0);
+}
+
+#[test]
+fn asc_matches_candump() {
+    let candump: Vec<_> = CandumpLog::new(DRIVE_LOG)
+        .filter(|record| !record.frame.is_remote_frame())
+        .collect();
+    let asc: Vec<_> = AscLog::new(DRIVE_ASC).collect();
+    assert_eq!(candump, asc);
+}
+
+#[test]
+fn asc_decimal_base_and_relative_timestamps() {
+    let log = "base dec  timestamps relative\n\
+               0.100000 1  1001            Rx   d 2 1 194\n\
+               0.050000 2  419430400x      Tx   d 1 255\n\
+               0.050000 1  2015            Rx   r 8\n";
+    let records: Vec<_> = AscLog::new(log).collect();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].frame, CanFrame::new(standard(1001), &[1, 194]).unwrap());
+    assert_eq!(records[1].timestamp_us, 50_000);
+    assert_eq!(records[1].frame.id(), Id::Extended(ExtendedId::new(419_430_400).unwrap()));
+    assert_eq!(records[2].timestamp_us, 100_000);
+    assert!(records[2].frame.is_remote_frame());
+}
+
+#[test]
+fn standard_ids_out_of_range_are_skipped() {
+    // 0x10123 would be 0x123 if cut down to 16 bits
+    let log = "   0.100000 1  10123           Rx   d 1 00\n\
+                  0.200000 1  123             Rx   d 1 00\n";
+    let records: Vec<_> = AscLog::new(log).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].frame.id(), standard(0x123));
+}
+
+#[test]
+fn candump_drive_through_car_state() {
+    let (state, waited_us) = replay_drive(CandumpLog::new(DRIVE_LOG));
+    assert_end_of_drive(&state);
+    assert_eq!(state.message_count(), 73);
+    assert_eq!(waited_us, DRIVE_DURATION_US);
+}
+
+#[test]
+fn asc_drive_through_car_state() {
+    let (state, waited_us) = replay_drive(AscLog::new(DRIVE_ASC));
+    assert_end_of_drive(&state);
+    assert_eq!(waited_us, DRIVE_DURATION_US);
+}
+
+#[test]
+fn loop_mode_starts_over() {
+    let mut delay = FakeDelay::default();
+    let mut source = ReplaySource::new(CandumpLog::new(DRIVE_LOG), &mut delay, ReplayMode::Loop);
+    let mut frames = 0;
+    let mut messages = 0;
+    block_on(async {
+        while frames < 2 * 73 + 1 {
+            match source.next_event().await.unwrap() {
+                CanEvent::Frame(_) => frames += 1,
+                CanEvent::Message(_) => messages += 1,
+            }
+        }
+    });
+    drop(source);
+    // VIN and intake air temperature twice
+    assert_eq!(messages, 4);
+    assert_eq!(delay.waited_ns / 1000, 2 * DRIVE_DURATION_US);
+}
+
+#[test]
+fn a_reply_cut_off_at_the_end_is_not_finished_by_the_next_pass() {
+    // The VIN reply, with its first frame at the end of the log and the rest at the start
+    let log = "(0.000) vcan0 7E8#215A5A5A314B5A41\n\
+               (0.010) vcan0 7E8#2257303030303031\n\
+               (0.500) vcan0 7E8#1014490201575657\n";
+    let mut delay = FakeDelay::default();
+    let mut source = ReplaySource::new(CandumpLog::new(log), &mut delay, ReplayMode::Loop);
+    let mut frames = 0;
+    let mut messages = 0;
+    block_on(async {
+        while frames < 2 * 3 {
+            match source.next_event().await.unwrap() {
+                CanEvent::Frame(_) => frames += 1,
+                CanEvent::Message(_) => messages += 1,
+            }
+        }
+    });
+    assert_eq!(messages, 0);
+}
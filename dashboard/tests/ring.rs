@@ -0,0 +1,167 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+};
+
+use dashboard::{
+    car_state::CarState,
+    frame::CanFrame,
+    ring::{DropPolicy, FrameRing, RingStats},
+};
+use embedded_can::{Frame, Id, StandardId};
+
+fn frame(id: u16, value: u8) -> CanFrame {
+    CanFrame::new(StandardId::new(id).unwrap(), &[value]).unwrap()
+}
+
+fn id_of(frame: &CanFrame) -> u16 {
+    match frame.id() {
+        Id::Standard(id) => id.as_raw(),
+        Id::Extended(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn frames_come_out_in_order_with_their_timestamps() {
+    let mut ring = FrameRing::<CanFrame, 4>::new(DropPolicy::DropOldest);
+    let (mut producer, mut consumer) = ring.split();
+    for round in 0..3u8 {
+        for value in 0..3 {
+            producer.push(frame(0x100, value), (round * 10 + value) as u64);
+        }
+        for value in 0..3 {
+            let stamped = consumer.pop().unwrap();
+            assert_eq!(stamped.frame.data(), &[value]);
+            assert_eq!(stamped.at_us, (round * 10 + value) as u64);
+        }
+        assert_eq!(consumer.pop(), None);
+    }
+    assert_eq!(producer.stats(), RingStats { pushed: 9, dropped: 0, coalesced: 0, high_water: 3 });
+}
+
+#[test]
+fn a_full_ring_drops_the_oldest() {
+    let mut ring = FrameRing::<CanFrame, 4>::new(DropPolicy::DropOldest);
+    let (mut producer, mut consumer) = ring.split();
+    for value in 0..6 {
+        producer.push(frame(0x100, value), 0);
+    }
+    let values: Vec<u8> = std::iter::from_fn(|| consumer.pop()).map(|stamped| stamped.frame.data()[0]).collect();
+    assert_eq!(values, [2, 3, 4, 5]);
+    assert_eq!(consumer.stats().dropped, 2);
+    assert_eq!(consumer.stats().high_water, 4);
+}
+
+#[test]
+fn coalescing_keeps_the_latest_frame_per_id() {
+    let mut ring = FrameRing::<CanFrame, 4>::new(DropPolicy::CoalesceById);
+    let (mut producer, mut consumer) = ring.split();
+    producer.push(frame(0x100, 0), 0);
+    producer.push(frame(0x200, 0), 1);
+    producer.push(frame(0x300, 0), 2);
+    producer.push(frame(0x400, 0), 3);
+    producer.push(frame(0x200, 1), 4);
+    producer.push(frame(0x200, 2), 5);
+    // Nothing queued with this ID, so the oldest goes
+    producer.push(frame(0x500, 0), 6);
+    let queued: Vec<(u16, u8, u64)> = std::iter::from_fn(|| consumer.pop())
+        .map(|stamped| (id_of(&stamped.frame), stamped.frame.data()[0], stamped.at_us))
+        .collect();
+    assert_eq!(queued, [(0x200, 2, 5), (0x300, 0, 2), (0x400, 0, 3), (0x500, 0, 6)]);
+    assert_eq!(consumer.stats(), RingStats { pushed: 7, dropped: 1, coalesced: 2, high_water: 4 });
+}
+
+#[test]
+fn a_slow_consumer_never_sees_frames_out_of_order() {
+    const FRAMES: u32 = 200_000;
+    for policy in [DropPolicy::DropOldest, DropPolicy::CoalesceById] {
+        let mut ring = FrameRing::<CanFrame, 16>::new(policy);
+        let (mut producer, mut consumer) = ring.split();
+        let received = thread::scope(|scope| {
+            let producing = scope.spawn(move || {
+                for count in 0..FRAMES {
+                    let id = 0x100 + (count % 8) as u16;
+                    let frame = CanFrame::new(StandardId::new(id).unwrap(), &count.to_le_bytes()).unwrap();
+                    producer.push(frame, count as u64);
+                }
+            });
+            let mut received = 0;
+            let mut last = None;
+            loop {
+                let finished = producing.is_finished();
+                let Some(stamped) = consumer.pop() else {
+                    if finished {
+                        break;
+                    }
+                    thread::yield_now();
+                    continue;
+                };
+                let count = u32::from_le_bytes(stamped.frame.data().try_into().unwrap());
+                // Never torn: the timestamp and the data were written together
+                assert_eq!(stamped.at_us, count as u64);
+                if policy == DropPolicy::DropOldest {
+                    assert!(last.is_none_or(|last| count > last), "{} after {:?}", count, last);
+                }
+                last = Some(count);
+                received += 1;
+            }
+            received
+        });
+        let stats = ring.stats();
+        assert_eq!(received + stats.dropped + stats.coalesced, FRAMES, "{:?}", policy);
+    }
+}
+
+#[test]
+fn the_latest_frame_of_each_id_always_gets_through() {
+    const IDS: u32 = 3;
+    const BURST: u32 = 10;
+    const BURSTS: u32 = 100_000;
+    // Few IDs in a small ring, so most pushes coalesce and many of them into the frame being popped
+    let mut ring = FrameRing::<CanFrame, 4>::new(DropPolicy::CoalesceById);
+    let (mut producer, mut consumer) = ring.split();
+    // Frames pushed so far, and how many of those the consumer has checked
+    let pushed = AtomicU32::new(0);
+    let checked = AtomicU32::new(0);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for burst in 1..=BURSTS {
+                for count in (burst - 1) * BURST..burst * BURST {
+                    let frame = CanFrame::new(StandardId::new((count % IDS) as u16).unwrap(), &count.to_le_bytes()).unwrap();
+                    producer.push(frame, count as u64);
+                }
+                pushed.store(burst * BURST, Ordering::Release);
+                while checked.load(Ordering::Acquire) < burst * BURST {
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut latest = [None; IDS as usize];
+        while checked.load(Ordering::Relaxed) < BURSTS * BURST {
+            let target = pushed.load(Ordering::Acquire);
+            if let Some(stamped) = consumer.pop() {
+                let count = u32::from_le_bytes(stamped.frame.data().try_into().unwrap());
+                let id = id_of(&stamped.frame) as usize;
+                assert!(latest[id].is_none_or(|last| count > last), "{} after {:?}", count, latest[id]);
+                latest[id] = Some(count);
+            } else if target > checked.load(Ordering::Relaxed) {
+                // The burst is in and the ring is empty: whatever else was given up, the last frame of each ID arrived
+                for id in 0..IDS {
+                    let last = target - 1 - (target - 1 - id) % IDS;
+                    assert_eq!(latest[id as usize], Some(last), "ID {} after {} frames", id, target);
+                }
+                checked.store(target, Ordering::Release);
+            } else {
+                thread::yield_now();
+            }
+        }
+    });
+}
+
+#[test]
+fn ring_stats_are_kept_in_the_car_state() {
+    let mut state = CarState::default();
+    assert_eq!(state.ring_stats().dropped, 0);
+    state.set_ring_stats(RingStats { pushed: 10, dropped: 3, coalesced: 1, high_water: 16 });
+    assert_eq!(state.ring_stats().dropped, 3);
+}
@@ -0,0 +1,123 @@
+use dashboard::{
+    bitrate::Bitrate,
+    car_state::CarState,
+    frame::CanFrame,
+    isotp::IsoTpMessage,
+    obd::Pid,
+    scheduler::{frame_bits, BusBudget, TxScheduler, ONE_SHOT_QUEUE},
+};
+use embedded_can::{ExtendedId, Frame, Id, StandardId};
+
+fn frame(id: u16) -> CanFrame {
+    CanFrame::new(StandardId::new(id).unwrap(), &[0; 8]).unwrap()
+}
+
+fn id_of(frame: &CanFrame) -> u16 {
+    match frame.id() {
+        Id::Standard(id) => id.as_raw(),
+        Id::Extended(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn frames_are_counted_with_worst_case_stuffing() {
+    assert_eq!(frame_bits(&frame(0x7DF)), 135);
+    assert_eq!(frame_bits(&CanFrame::new(ExtendedId::MAX, &[0; 8]).unwrap()), 160);
+    assert_eq!(frame_bits(&CanFrame::new(StandardId::ZERO, &[]).unwrap()), 55);
+}
+
+#[test]
+fn the_interframe_space_is_counted() {
+    // SOF to the end of the CRC: 1 + 11 + 3 + 4 + 64 + 15 = 98 bits, one stuff bit in every 4 after
+    // the first, then the CRC delimiter, ACK slot and delimiter, 7 bits EOF and 3 bits interframe space
+    let stuffed = 98;
+    let expected = stuffed + (stuffed - 1) / 4 + 1 + 2 + 7 + 3;
+    assert_eq!(expected, 135);
+    assert_eq!(frame_bits(&frame(0x7DF)), expected);
+}
+
+#[test]
+fn the_poller_is_held_to_the_bus_budget() {
+    let budget = BusBudget::new(Bitrate::K125);
+    let mut scheduler = TxScheduler::new(budget);
+    let sent = (0..10_000).filter_map(|now_ms| scheduler.poll(now_ms, |_| Some(frame(0x7DF)))).count();
+    // 5 % of 125 kbit/s is 6 bits a millisecond, plus the burst saved up at the start
+    let allowed = (6 * 10_000 + 600) / 135;
+    assert!(sent <= allowed, "{} frames sent, {} allowed", sent, allowed);
+    assert!(sent >= allowed * 9 / 10, "{} frames sent, {} allowed", sent, allowed);
+    assert!(scheduler.stats().throttled > 0);
+}
+
+#[test]
+fn nothing_is_sent_in_listen_only_mode() {
+    let mut scheduler = TxScheduler::new(BusBudget::new(Bitrate::K500));
+    scheduler.set_listen_only(true);
+    assert!(scheduler.send_once(frame(0x200)));
+    for now_ms in 0..1000 {
+        let polled = scheduler.poll(now_ms, |_| panic!("the poller must not be asked"));
+        assert_eq!(polled, None);
+    }
+    scheduler.set_listen_only(false);
+    assert_eq!(scheduler.poll(1000, |_| None).map(|frame| id_of(&frame)), Some(0x200));
+}
+
+#[test]
+fn one_shots_go_before_the_poller() {
+    let mut scheduler = TxScheduler::new(BusBudget::new(Bitrate::K500));
+    scheduler.send_once(frame(0x7E0));
+    scheduler.send_once(frame(0x7E1));
+    let mut sent = Vec::new();
+    for now_ms in (0..=30).step_by(10) {
+        if let Some(frame) = scheduler.poll(now_ms, |_| Some(frame(0x7DF))) {
+            sent.push((now_ms, id_of(&frame)));
+        }
+    }
+    assert_eq!(sent, [(0, 0x7E0), (10, 0x7E1), (20, 0x7DF), (30, 0x7DF)]);
+}
+
+#[test]
+fn one_shots_are_held_to_the_bus_budget() {
+    let mut scheduler = TxScheduler::new(BusBudget::new(Bitrate::K125));
+    // Use up the burst
+    while scheduler.poll(0, |_| Some(frame(0x7DF))).is_some() {}
+    assert!(scheduler.send_once(frame(0x7E0)));
+    assert_eq!(scheduler.poll(0, |_| None), None);
+    // 6 bits a millisecond at 125k, a frame's worth after a while
+    assert_eq!(scheduler.poll(30, |_| None).map(|frame| id_of(&frame)), Some(0x7E0));
+}
+
+#[test]
+fn a_full_one_shot_queue_refuses_more() {
+    let mut scheduler = TxScheduler::<CanFrame>::new(BusBudget::new(Bitrate::K500));
+    scheduler.set_listen_only(true);
+    for _ in 0..ONE_SHOT_QUEUE {
+        assert!(scheduler.send_once(frame(0x7E0)));
+    }
+    assert!(!scheduler.send_once(frame(0x7E0)));
+    assert_eq!(scheduler.stats().dropped, 1);
+}
+
+#[test]
+fn fast_signals_are_polled_more_often() {
+    let mut state = CarState::default();
+    let mut counts = [0; Pid::ALL.len()];
+    for now_ms in (0..10_000).step_by(5) {
+        let Some(request) = state.next_obd_request::<CanFrame>(now_ms) else {
+            continue;
+        };
+        // An ECU that answers everything straight away
+        let [_, mode, code, ..] = *request.data() else { unreachable!() };
+        let Some(pid) = Pid::from_code(code).filter(|_| mode == 0x01) else {
+            continue;
+        };
+        counts[pid as usize] += 1;
+        let reply = IsoTpMessage {
+            id: Id::Standard(StandardId::new(0x7E8).unwrap()),
+            data: vec![0x41, code, 0, 0],
+        };
+        state.process_isotp(&reply);
+    }
+    assert!(counts[Pid::EngineRpm as usize] >= 95, "{:?}", counts);
+    assert!(counts[Pid::VehicleSpeed as usize] >= 95, "{:?}", counts);
+    assert!((2..=3).contains(&counts[Pid::FuelLevel as usize]), "{:?}", counts);
+}
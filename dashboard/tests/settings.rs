@@ -0,0 +1,44 @@
+use dashboard::{
+    bitrate::Bitrate,
+    settings::{self, Settings},
+    store::{KvStore, RamFlash},
+    trip::{EconomyUnit, Sample, TripComputer, TripId},
+};
+
+#[test]
+fn blank_flash_gives_the_defaults() {
+    let mut store = KvStore::mount(RamFlash::new(2)).unwrap();
+    assert_eq!(Settings::load(&mut store).unwrap(), Settings::default());
+}
+
+#[test]
+fn settings_survive_a_reboot() {
+    let mut store = KvStore::mount(RamFlash::new(2)).unwrap();
+    let settings = Settings { economy_unit: EconomyUnit::Mpg, brightness: 40, bitrate: Some(Bitrate::K250), page: 2 };
+    settings.save(&mut store).unwrap();
+    let mut store = KvStore::mount(store.into_flash()).unwrap();
+    assert_eq!(Settings::load(&mut store).unwrap(), settings);
+}
+
+#[test]
+fn trips_are_written_only_when_they_changed() {
+    let mut trips = TripComputer::default();
+    for at_ms in (0..=60_000).step_by(100) {
+        trips.add_sample(Sample { at_ms, speed: Some(60.0), fuel_rate: Some(6.0) });
+    }
+    trips.reset(TripId::B);
+    let mut store = KvStore::mount(RamFlash::new(2)).unwrap();
+    let mut saved = TripComputer::default();
+    settings::save_trips(&mut store, &trips, &mut saved).unwrap();
+    // Trip B is no different from a blank one
+    assert_eq!(store.get(settings::TRIP_B_KEY, &mut [0; 36]).unwrap(), None);
+
+    let flash = store.into_flash();
+    let mut store = KvStore::mount(flash.clone()).unwrap();
+    settings::save_trips(&mut store, &trips, &mut saved).unwrap();
+    let mut restored = TripComputer::default();
+    settings::load_trips(&mut store, &mut restored).unwrap();
+    assert_eq!(store.into_flash().data, flash.data);
+    assert_eq!(restored.trip(TripId::A), trips.trip(TripId::A));
+    assert!((restored.odometer_km() - 1.0).abs() < 0.001);
+}
@@ -0,0 +1,127 @@
+use dashboard::store::{crc32, KvStore, PowerLoss, RamFlash, StoreError};
+
+fn value(key: u16, generation: u32) -> [u8; 40] {
+    let mut value = [0u8; 40];
+    value[..2].copy_from_slice(&key.to_le_bytes());
+    value[2..6].copy_from_slice(&generation.to_le_bytes());
+    value
+}
+
+fn read(store: &mut KvStore<RamFlash>, key: u16) -> Option<[u8; 40]> {
+    let mut bytes = [0u8; 40];
+    store.get(key, &mut bytes).unwrap().map(|len| {
+        assert_eq!(len, 40);
+        bytes
+    })
+}
+
+/// The generation stored under `key`.
+fn generation(store: &mut KvStore<RamFlash>, key: u16) -> Option<u32> {
+    read(store, key).map(|value| u32::from_le_bytes(value[2..6].try_into().unwrap()))
+}
+
+#[test]
+fn crc_matches_the_standard_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn values_come_back_after_a_remount() {
+    let mut store = KvStore::mount(RamFlash::new(4)).unwrap();
+    assert_eq!(read(&mut store, 7), None);
+    store.set(7, &value(7, 1)).unwrap();
+    store.set(8, &value(8, 1)).unwrap();
+    store.set(7, &value(7, 2)).unwrap();
+    let mut store = KvStore::mount(store.into_flash()).unwrap();
+    assert_eq!(generation(&mut store, 7), Some(2));
+    assert_eq!(generation(&mut store, 8), Some(1));
+    let mut short = [0u8; 8];
+    assert_eq!(store.get(7, &mut short), Err(StoreError::TooLarge));
+    assert_eq!(store.set(9, &[0; 121]), Err(StoreError::TooLarge));
+}
+
+#[test]
+fn erases_are_spread_over_all_sectors() {
+    let mut store = KvStore::mount(RamFlash::new(4)).unwrap();
+    for generation in 0..5000 {
+        for key in 1..=3 {
+            store.set(key, &value(key, generation)).unwrap();
+        }
+    }
+    let mut store = KvStore::mount(store.into_flash()).unwrap();
+    for key in 1..=3 {
+        assert_eq!(generation(&mut store, key), Some(4999));
+    }
+    let erases = store.into_flash().erases;
+    let (least, most) = (erases.iter().min().unwrap(), erases.iter().max().unwrap());
+    assert!(*least >= 40 && most - least <= 1, "erases per sector {:?}", erases);
+}
+
+/// Cuts the power `after` bytes into writing generation 1 of every key, at whatever
+/// point in the ring that falls, then checks every key reads back whole.
+fn interrupted_write(after: usize) {
+    let mut store = KvStore::mount(RamFlash::new(3)).unwrap();
+    // Fill up so the cut can land in a sector switch or the reclaim after it
+    for generation in 0..40 {
+        store.set(1, &value(1, 0)).unwrap();
+        store.set(2, &value(2, generation)).unwrap();
+    }
+    let mut flash = store.into_flash();
+    flash.power_fails_after = Some(after);
+    let mut store = KvStore::mount(flash).unwrap();
+    let result = (1..=2).try_for_each(|key| store.set(key, &value(key, 100)));
+    let mut flash = store.into_flash();
+    flash.power_fails_after = None;
+
+    let mut store = KvStore::mount(flash).unwrap();
+    assert_eq!(generation(&mut store, 1).unwrap() % 100, 0, "cut after {} bytes", after);
+    assert!([39, 100].contains(&generation(&mut store, 2).unwrap()), "cut after {} bytes", after);
+    if result.is_ok() {
+        assert_eq!(generation(&mut store, 2), Some(100));
+    } else {
+        assert_eq!(result, Err(StoreError::Flash(PowerLoss)));
+    }
+    // And the store still takes writes afterwards
+    store.set(2, &value(2, 200)).unwrap();
+    assert_eq!(generation(&mut store, 2), Some(200));
+}
+
+#[test]
+fn a_write_cut_short_keeps_the_old_value() {
+    for after in 0..=100 {
+        interrupted_write(after);
+    }
+}
+
+#[test]
+fn power_loss_while_switching_sectors_loses_nothing() {
+    // 85 records of 48 bytes fit a sector. Key 4 goes first, then key 3 fills up all
+    // but the last record of the second sector: two writes on the third sector
+    // starts, and key 4 is copied over from the first before that is erased
+    let mut store = KvStore::mount(RamFlash::new(3)).unwrap();
+    store.set(4, &value(4, 0)).unwrap();
+    for generation in 0..85 + 84 - 1 {
+        store.set(3, &value(3, generation)).unwrap();
+    }
+    let flash = store.into_flash();
+    let mut uncut = KvStore::mount(flash.clone()).unwrap();
+    for generation in 200..210 {
+        uncut.set(3, &value(3, generation)).unwrap();
+    }
+    assert_eq!(uncut.into_flash().erases, [1, 0, 0]);
+    for after in (0..600).step_by(4) {
+        let mut flash = flash.clone();
+        flash.power_fails_after = Some(after);
+        let mut store = KvStore::mount(flash).unwrap();
+        for generation in 200..210 {
+            if store.set(3, &value(3, generation)).is_err() {
+                break;
+            }
+        }
+        let mut flash = store.into_flash();
+        flash.power_fails_after = None;
+        let mut store = KvStore::mount(flash).unwrap();
+        assert_eq!(generation(&mut store, 4), Some(0), "cut after {} bytes", after);
+        assert!(generation(&mut store, 3).is_some());
+    }
+}
@@ -0,0 +1,47 @@
+use dashboard::telltale::{Colour, Icon, Telltale, BLINK_PERIOD_MS, COOLANT_HOT};
+
+#[test]
+fn colours_follow_iso_2575() {
+    assert_eq!(Icon::OilPressure.colour(), Colour::Red);
+    assert_eq!(Icon::CheckEngine.colour(), Colour::Amber);
+    assert_eq!(Icon::TurnLeft.colour(), Colour::Green);
+    assert_eq!(Icon::HighBeam.colour(), Colour::Blue);
+}
+
+#[test]
+fn indicator_flashes_from_the_moment_it_is_switched_on() {
+    let mut indicator = Telltale::new(Icon::TurnLeft);
+    indicator.update(Some(0.0), 0);
+    assert!(!indicator.is_lit());
+    let start = 1000;
+    let lit_at = |indicator: &mut Telltale, ms: u64| {
+        indicator.update(Some(1.0), start + ms);
+        indicator.is_lit()
+    };
+    assert!(lit_at(&mut indicator, 0));
+    assert!(lit_at(&mut indicator, BLINK_PERIOD_MS / 2 - 1));
+    assert!(!lit_at(&mut indicator, BLINK_PERIOD_MS / 2));
+    assert!(lit_at(&mut indicator, BLINK_PERIOD_MS));
+    // Switching off goes dark straight away
+    assert!(indicator.update(Some(0.0), start + BLINK_PERIOD_MS + 10));
+    assert!(!indicator.is_lit());
+}
+
+#[test]
+fn warnings_stay_lit() {
+    let mut oil = Telltale::new(Icon::OilPressure);
+    assert!(oil.update(Some(1.0), 0));
+    assert!(!oil.update(Some(1.0), BLINK_PERIOD_MS / 2));
+    assert!(oil.is_lit());
+}
+
+#[test]
+fn coolant_lights_when_hot_and_not_without_data() {
+    let mut coolant = Telltale::new(Icon::CoolantTemp);
+    coolant.update(None, 0);
+    assert!(!coolant.is_lit());
+    coolant.update(Some(90.0), 10);
+    assert!(!coolant.is_lit());
+    coolant.update(Some(COOLANT_HOT), 20);
+    assert!(coolant.is_lit());
+}
@@ -0,0 +1,109 @@
+use dashboard::{
+    car_state::{CarField, CarState},
+    isotp::IsoTpMessage,
+    trip::{self, EconomyUnit, Sample, TripComputer, TripId},
+};
+use embedded_can::{Id, StandardId};
+
+/// Feeds `speed` and `fuel_rate` every 100 ms from `start_ms` for `duration_ms`.
+fn drive(computer: &mut TripComputer, start_ms: u64, duration_ms: u64, speed: f32, fuel_rate: f32) {
+    for at_ms in (start_ms..=start_ms + duration_ms).step_by(100) {
+        computer.add_sample(Sample { at_ms, speed: Some(speed), fuel_rate: Some(fuel_rate) });
+    }
+}
+
+fn close(actual: f32, expected: f32, tolerance: f32) -> bool {
+    (actual - expected).abs() <= tolerance
+}
+
+#[test]
+fn a_minute_at_60_covers_a_kilometre() {
+    let mut computer = TripComputer::default();
+    drive(&mut computer, 0, 60_000, 60.0, 6.0);
+    let trip = computer.trip(TripId::A);
+    assert!(close(trip.distance_km(), 1.0, 0.001), "{} km", trip.distance_km());
+    assert!(close(trip.fuel_used(), 0.1, 0.0001), "{} l", trip.fuel_used());
+    assert!(close(trip.average_consumption().unwrap(), 10.0, 0.01));
+    assert!(close(trip.average_speed().unwrap(), 60.0, 0.01));
+    assert_eq!((trip.elapsed_ms(), trip.moving_ms()), (60_000, 60_000));
+    assert_eq!(trip.max_speed(), 60.0);
+    assert!(close(computer.instant_consumption().unwrap(), 10.0, 0.01));
+}
+
+#[test]
+fn standing_still_only_adds_to_the_elapsed_time() {
+    let mut computer = TripComputer::default();
+    drive(&mut computer, 0, 60_000, 60.0, 6.0);
+    drive(&mut computer, 60_100, 60_000, 0.0, 0.8);
+    let trip = computer.trip(TripId::A);
+    assert!(trip.elapsed_ms() > 120_000);
+    assert!(trip.moving_ms() < 60_200);
+    assert!(close(trip.average_speed().unwrap(), 60.0, 0.5));
+    // Idling still burns fuel
+    assert!(trip.fuel_used() > 0.11);
+    assert_eq!(computer.instant_consumption(), None);
+}
+
+#[test]
+fn nothing_is_made_up_across_a_gap() {
+    let mut computer = TripComputer::default();
+    drive(&mut computer, 0, 1000, 100.0, 8.0);
+    let before = *computer.trip(TripId::A);
+    drive(&mut computer, 61_000, 0, 100.0, 8.0);
+    let after = computer.trip(TripId::A);
+    assert_eq!(after.distance_km(), before.distance_km());
+    assert_eq!(after.elapsed_ms(), 61_000);
+}
+
+#[test]
+fn trips_reset_separately() {
+    let mut computer = TripComputer::default();
+    drive(&mut computer, 0, 10_000, 50.0, 5.0);
+    computer.reset(TripId::A);
+    drive(&mut computer, 10_100, 10_000, 50.0, 5.0);
+    let (a, b) = (computer.trip(TripId::A), computer.trip(TripId::B));
+    assert!(close(b.distance_km(), 2.0 * a.distance_km(), 0.002), "A {} km, B {} km", a.distance_km(), b.distance_km());
+}
+
+#[test]
+fn economy_units() {
+    assert!(close(EconomyUnit::Mpg.convert(10.0), 23.52, 0.01));
+    assert_eq!(EconomyUnit::LitresPer100Km.convert(7.5), 7.5);
+    assert_eq!(trip::consumption(3.0, 1.0), None);
+}
+
+#[test]
+fn car_state_estimates_fuel_from_air_flow() {
+    let mut state = CarState::default();
+    state.set(CarField::MafAirFlow, 10.0);
+    assert!(close(state.fuel_rate().unwrap(), 3.29, 0.01));
+    state.set(CarField::FuelRate, 4.0);
+    assert_eq!(state.fuel_rate(), Some(4.0));
+
+    state.set(CarField::VehicleSpeed, 36.0);
+    for at_ms in (0..=10_000).step_by(50) {
+        state.update_trips(at_ms);
+    }
+    assert!(close(state.trips().trip(TripId::B).distance_km(), 0.1, 0.0001));
+    state.reset_trip(TripId::B);
+    assert_eq!(state.trips().trip(TripId::B).distance_km(), 0.0);
+}
+
+#[test]
+fn a_speed_that_stopped_coming_is_not_driven_on() {
+    let reply = |data: &[u8]| IsoTpMessage { id: Id::Standard(StandardId::new(0x7E8).unwrap()), data: data.to_vec() };
+    let mut state = CarState::default();
+    // 90 km/h and 2000 rpm for a second, then only the revs keep coming
+    for at_ms in (0..=10_000).step_by(100) {
+        state.set_time(at_ms);
+        if at_ms <= 1000 {
+            state.process_isotp(&reply(&[0x41, 0x0D, 90]));
+        }
+        state.process_isotp(&reply(&[0x41, 0x0C, 0x1F, 0x40]));
+        state.update_trips(at_ms);
+    }
+    let trip = state.trips().trip(TripId::A);
+    // The last speed counts until it is late, three 100 ms periods on: 1.3 s at 90 km/h
+    assert!(close(trip.distance_km(), 0.0325, 0.0001), "{} km", trip.distance_km());
+    assert_eq!(trip.elapsed_ms(), 10_000);
+}
@@ -0,0 +1,57 @@
+use dashboard::voltage::{Divider, VoltageFilter, MIN_SAMPLES, WINDOW};
+
+/// 11 dB attenuation on the ESP32-S3.
+const FULL_SCALE_MV: u16 = 3100;
+
+/// What the calibrated ADC reads for `volts` at the battery, off by `error_mv`.
+fn pin_mv(volts: f32, error_mv: i32) -> u16 {
+    ((volts * 1000.0 / Divider::default().ratio()).round() as i32 + error_mv).max(0) as u16
+}
+
+#[test]
+fn nothing_until_enough_readings() {
+    let mut filter = VoltageFilter::new(Divider::default(), FULL_SCALE_MV);
+    for _ in 1..MIN_SAMPLES {
+        filter.push(pin_mv(12.6, 0));
+        assert_eq!(filter.voltage(), None);
+    }
+    filter.push(pin_mv(12.6, 0));
+    assert!(filter.voltage().is_some());
+}
+
+#[test]
+fn within_50_mv_from_9_to_16_volts() {
+    for tenths in 90..=160 {
+        let volts = tenths as f32 / 10.0;
+        let mut filter = VoltageFilter::new(Divider::default(), FULL_SCALE_MV);
+        for index in 0..WINDOW {
+            // A few millivolts of noise, and two injector spikes
+            let error = match index {
+                3 => 180,
+                11 => -250,
+                _ => [-3, 1, 4, -2, 0][index % 5],
+            };
+            filter.push(pin_mv(volts, error));
+        }
+        let measured = filter.voltage().unwrap();
+        assert!((measured - volts).abs() < 0.05, "{} V measured as {} V", volts, measured);
+    }
+}
+
+#[test]
+fn a_zero_reading_does_not_break_anything() {
+    let mut filter = VoltageFilter::new(Divider::default(), FULL_SCALE_MV);
+    for _ in 0..WINDOW {
+        filter.push(0);
+    }
+    assert_eq!(filter.voltage(), Some(0.0));
+}
+
+#[test]
+fn clipping_reports_nothing() {
+    let mut filter = VoltageFilter::new(Divider::default(), FULL_SCALE_MV);
+    for _ in 0..WINDOW {
+        filter.push(FULL_SCALE_MV);
+    }
+    assert_eq!(filter.voltage(), None);
+}
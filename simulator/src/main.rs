@@ -0,0 +1,177 @@
+//! Renders the dashboard on the host, into PNG frames or an animated PNG.
+//!
+//! ```text
+//! cargo run -- --frames 120 --out frames
+//! cargo run -- --frames 120 --apng drive.png
+//! cargo run -- --frames 120 --page trip-a --out trip
+//! ```
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use dashboard::{
+    car_state::CarState,
+    frame::CanFrame,
+    framebuffer::HeapBuffer,
+    isotp::{Event, FlowControl, IsoTpLayer},
+    page::{Page, PageManager},
+    screen,
+    trip::EconomyUnit,
+};
+use embedded_can::{Frame, StandardId};
+use embedded_graphics::{
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::*,
+};
+use embedded_graphics_framebuf::FrameBuf;
+
+const BUFFER_SIZE: usize = screen::WIDTH * screen::HEIGHT;
+type SimFrameBuf = FrameBuf<Rgb565, HeapBuffer<Rgb565, BUFFER_SIZE>>;
+
+struct Options {
+    frames: usize,
+    fps: u16,
+    out: Option<PathBuf>,
+    apng: Option<PathBuf>,
+    page: Page,
+}
+
+impl Options {
+    fn parse() -> Result<Self, Box<dyn Error>> {
+        let mut options = Options {
+            frames: 120,
+            fps: 30,
+            out: None,
+            apng: None,
+            page: Page::Gauges,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--frames" => options.frames = value()?.parse()?,
+                "--fps" => options.fps = value()?.parse()?,
+                "--out" => options.out = Some(value()?.into()),
+                "--apng" => options.apng = Some(value()?.into()),
+                "--page" => {
+                    let name = value()?;
+                    options.page = Page::from_name(&name).ok_or_else(|| {
+                        let names: Vec<_> = Page::ALL.iter().map(|page| page.name()).collect();
+                        format!("Unknown page {}, use one of {}", name, names.join(", "))
+                    })?;
+                }
+                _ => return Err(format!("Unknown argument {}, use --frames, --fps, --out, --apng or --page", arg).into()),
+            }
+        }
+        if options.out.is_none() && options.apng.is_none() {
+            options.out = Some("frames".into());
+        }
+        Ok(options)
+    }
+}
+
+/// A made up drive: accelerate to 200 km/h, then brake back down to 40.
+fn speed_at(progress: f32) -> f32 {
+    if progress < 0.6 {
+        200.0 * progress / 0.6
+    } else {
+        200.0 - 160.0 * (progress - 0.6) / 0.4
+    }
+}
+
+/// OBD-II Mode 01 replies from the engine ECU, as they would come off the bus.
+fn obd_replies(speed: f32) -> [CanFrame; 3] {
+    let id = StandardId::new(0x7E8).unwrap();
+    let rpm = ((800.0 + speed * 25.0) * 4.0) as u16;
+    let [rpm_a, rpm_b] = rpm.to_be_bytes();
+    [
+        CanFrame::new(id, &[0x03, 0x41, 0x0D, speed as u8, 0, 0, 0, 0]).unwrap(),
+        CanFrame::new(id, &[0x04, 0x41, 0x0C, rpm_a, rpm_b, 0, 0, 0]).unwrap(),
+        // Warm, 90 °C
+        CanFrame::new(id, &[0x03, 0x41, 0x05, 130, 0, 0, 0, 0]).unwrap(),
+    ]
+}
+
+fn to_rgb(frame_buf: &SimFrameBuf) -> Vec<u8> {
+    frame_buf
+        .data
+        .iter()
+        .flat_map(|pixel| {
+            let rgb = Rgb888::from(*pixel);
+            [rgb.r(), rgb.g(), rgb.b()]
+        })
+        .collect()
+}
+
+fn encoder<'a>(writer: BufWriter<File>) -> png::Encoder<'a, BufWriter<File>> {
+    let mut encoder = png::Encoder::new(writer, screen::WIDTH as u32, screen::HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+}
+
+fn write_png(path: &Path, rgb: &[u8]) -> Result<(), Box<dyn Error>> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut writer = encoder(writer).write_header()?;
+    writer.write_image_data(rgb)?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::parse()?;
+    let mut frame_buf = SimFrameBuf::new(
+        HeapBuffer::new(vec![Rgb565::BLACK; BUFFER_SIZE].into_boxed_slice().try_into().unwrap()),
+        screen::WIDTH,
+        screen::HEIGHT,
+    );
+    let mut screen = PageManager::new(options.page, EconomyUnit::LitresPer100Km).screen();
+    let mut car_state = CarState::default();
+    // Every frame ends up in the output
+    car_state.set_render_fps(options.fps as u32);
+    let mut isotp = IsoTpLayer::obd(FlowControl::default());
+
+    let mut animation = match &options.apng {
+        Some(path) => {
+            let writer = BufWriter::new(File::create(path)?);
+            let mut encoder = encoder(writer);
+            encoder.set_animated(options.frames as u32, 0)?;
+            encoder.set_frame_delay(1, options.fps)?;
+            Some(encoder.write_header()?)
+        }
+        None => None,
+    };
+    if let Some(out) = &options.out {
+        fs::create_dir_all(out)?;
+    }
+
+    screen.draw_static(&mut frame_buf);
+    for index in 0..options.frames {
+        let now_ms = index as u64 * 1000 / options.fps as u64;
+        let progress = index as f32 / options.frames.max(1) as f32;
+        car_state.set_time(now_ms);
+        for frame in obd_replies(speed_at(progress)) {
+            if let Some(Event::Message(message)) = isotp.on_frame(&frame, now_ms) {
+                car_state.process_isotp(&message);
+            }
+            car_state.process_message(frame);
+        }
+        car_state.update_trips(now_ms);
+        screen.update(&car_state, now_ms);
+        screen.draw(&mut frame_buf);
+
+        let rgb = to_rgb(&frame_buf);
+        if let Some(out) = &options.out {
+            write_png(&out.join(format!("frame_{:04}.png", index)), &rgb)?;
+        }
+        if let Some(writer) = animation.as_mut() {
+            writer.write_image_data(&rgb)?;
+        }
+    }
+    if let Some(writer) = animation {
+        writer.finish()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use dashboard::filter::{self, CodeMask, FilterPlan};
+use esp_hal::twai::filter::{DualStandardFilter, SingleExtendedFilter, SingleStandardFilter};
+use esp_hal::twai::{ExtendedId, StandardId, TwaiConfiguration};
+use esp_hal::DriverMode;
+use log::info;
+
+fn standard(value: u32) -> StandardId {
+    StandardId::new(value as u16).unwrap()
+}
+
+/// Lets only the frames `CarState` decodes into the receive FIFO.
+pub(crate) fn set_acceptance_filter<Dm: DriverMode>(config: &mut TwaiConfiguration<'_, Dm>) {
+    let plan = filter::plan(&filter::decoded_ids());
+    info!(
+        "Acceptance filter {:?} for {} ids, {} unwanted ids get through",
+        plan.filter, plan.wanted, plan.unwanted_accepted
+    );
+    match plan.filter {
+        FilterPlan::AcceptAll => {}
+        FilterPlan::Single(CodeMask { code, mask }) => config.set_filter(SingleStandardFilter::new_from_code_mask(
+            standard(code),
+            standard(mask),
+            false,
+            false,
+            [0, 0],
+            [0, 0],
+        )),
+        FilterPlan::Dual(first, second) => config.set_filter(DualStandardFilter::new_from_code_mask(
+            standard(first.code),
+            standard(first.mask),
+            false,
+            false,
+            0,
+            0,
+            standard(second.code),
+            standard(second.mask),
+            false,
+            false,
+        )),
+        FilterPlan::Extended(CodeMask { code, mask }) => config.set_filter(SingleExtendedFilter::new_from_code_mask(
+            ExtendedId::new(code).unwrap(),
+            ExtendedId::new(mask).unwrap(),
+            false,
+            false,
+        )),
+    }
+}
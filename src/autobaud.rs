@@ -0,0 +1,135 @@
+use dashboard::bitrate::{Bitrate, Probe};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{with_deadline, Duration, Instant};
+use esp_hal::peripherals::{GPIO21, GPIO33, TWAI0};
+use esp_hal::twai::{BaudRate, TwaiConfiguration, TwaiMode};
+use log::{info, warn};
+
+/// Auto-baud runs on the app core, the settings are stored from the main one.
+pub(crate) static DETECTED_BITRATE: Signal<CriticalSectionRawMutex, Bitrate> = Signal::new();
+/// Long enough to catch a few frames of the slower periodic messages.
+const PROBE_WINDOW: Duration = Duration::from_millis(250);
+/// Times every rate is tried before giving up, about 4 s with the ignition off.
+const DETECT_ROUNDS: usize = 4;
+/// Used when auto-baud hears nothing, the diagnostic bus on the OBD port is 500k on most cars.
+const FALLBACK_BITRATE: Bitrate = Bitrate::K500;
+
+/// The bitrate the bus runs at, and whether it still has to prove itself.
+pub(crate) struct Selection {
+    pub bitrate: Bitrate,
+    /// Auto-baud heard nothing and this is `FALLBACK_BITRATE`. It is only stored once
+    /// `Confirmation` sees clean traffic, until then the next boot detects again.
+    pub guessed: bool,
+}
+
+pub(crate) struct CanPins {
+    pub twai: TWAI0<'static>,
+    pub rx: GPIO33<'static>,
+    pub tx: GPIO21<'static>,
+}
+
+pub(crate) fn baud_rate(bitrate: Bitrate) -> BaudRate {
+    match bitrate {
+        Bitrate::K125 => BaudRate::B125K,
+        Bitrate::K250 => BaudRate::B250K,
+        Bitrate::K500 => BaudRate::B500K,
+        Bitrate::M1 => BaudRate::B1000K,
+    }
+}
+
+/// The bitrate to run the bus at: `CAN_BITRATE` (in kbit/s) at build time if set,
+/// otherwise what an earlier boot detected, otherwise auto-baud, otherwise a guess.
+/// Auto-baud listens for seconds on a quiet bus, it waits asynchronously so the
+/// display flusher on the same executor keeps going.
+pub(crate) async fn select_bitrate(pins: &mut CanPins, stored: Option<Bitrate>) -> Selection {
+    if let Some(bitrate) = option_env!("CAN_BITRATE").and_then(|kbps| kbps.parse().ok()).and_then(Bitrate::from_kbps) {
+        info!("Using configured bitrate of {}k", bitrate.kbps());
+        return Selection { bitrate, guessed: false };
+    }
+    if let Some(bitrate) = stored {
+        info!("Using stored bitrate of {}k", bitrate.kbps());
+        return Selection { bitrate, guessed: false };
+    }
+    match detect_bitrate(pins).await {
+        Some(bitrate) => {
+            DETECTED_BITRATE.signal(bitrate);
+            Selection { bitrate, guessed: false }
+        }
+        None => {
+            warn!("Auto-baud found no traffic, is the ignition on? Trying {}k", FALLBACK_BITRATE.kbps());
+            Selection { bitrate: FALLBACK_BITRATE, guessed: true }
+        }
+    }
+}
+
+/// Listens at every rate in turn until one of them sees clean traffic. Listen-only
+/// mode never acknowledges or sends error frames, so guessing wrong does not disturb the car.
+async fn detect_bitrate(pins: &mut CanPins) -> Option<Bitrate> {
+    for _ in 0..DETECT_ROUNDS {
+        for bitrate in Bitrate::ALL {
+            let probe = probe(pins, bitrate).await;
+            info!("Auto-baud {}k: {:?}", bitrate.kbps(), probe);
+            if probe.is_match() {
+                info!("Auto-baud locked onto {}k", bitrate.kbps());
+                return Some(bitrate);
+            }
+        }
+    }
+    None
+}
+
+/// Keeps checking a guessed bitrate on the running bus, the same way a probe window
+/// would, and stores it once it sees enough clean frames in a row.
+pub(crate) struct Confirmation {
+    bitrate: Bitrate,
+    probe: Option<Probe>,
+}
+
+impl Confirmation {
+    pub fn new(selection: &Selection) -> Self {
+        Confirmation { bitrate: selection.bitrate, probe: selection.guessed.then(Probe::default) }
+    }
+
+    pub fn on_frame(&mut self) {
+        let Some(probe) = &mut self.probe else { return };
+        probe.frames += 1;
+        if probe.is_match() {
+            info!("Traffic at {}k, storing it", self.bitrate.kbps());
+            DETECTED_BITRATE.signal(self.bitrate);
+            self.probe = None;
+        }
+    }
+
+    /// Errors at the guessed rate start the count over.
+    pub fn on_error(&mut self) {
+        if let Some(probe) = &mut self.probe {
+            *probe = Probe::default();
+        }
+    }
+}
+
+async fn probe(pins: &mut CanPins, bitrate: Bitrate) -> Probe {
+    let mut twai = TwaiConfiguration::new(
+        pins.twai.reborrow(),
+        pins.rx.reborrow(),
+        pins.tx.reborrow(),
+        baud_rate(bitrate),
+        TwaiMode::ListenOnly,
+    )
+    .into_async()
+    .start();
+    let mut probe = Probe {
+        error_count_before: twai.receive_error_count(),
+        ..Probe::default()
+    };
+    let deadline = Instant::now() + PROBE_WINDOW;
+    while let Ok(result) = with_deadline(deadline, twai.receive_async()).await {
+        match result {
+            Ok(_) => probe.frames += 1,
+            Err(_) => probe.errors += 1,
+        }
+    }
+    probe.error_count_after = twai.receive_error_count();
+    twai.stop();
+    probe
+}
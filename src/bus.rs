@@ -0,0 +1,63 @@
+//! Error counters and bus-off recovery for the TWAI controller. The split driver
+//! halves do not expose these, so they are read from the registers directly.
+//! `frame_transmitter` counts what it sends here and `frame_received` collects it.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use dashboard::bus_health::BusMonitor;
+use dashboard::scheduler::frame_bits;
+use esp_hal::peripherals::TWAI0;
+use esp_hal::twai::EspTwaiFrame;
+
+static SENT_FRAMES: AtomicU32 = AtomicU32::new(0);
+static SENT_BITS: AtomicU32 = AtomicU32::new(0);
+static TX_ERRORS: AtomicU32 = AtomicU32::new(0);
+static ARBITRATION_LOST: AtomicU32 = AtomicU32::new(0);
+
+pub(crate) fn record_sent(frame: &EspTwaiFrame) {
+    SENT_FRAMES.fetch_add(1, Ordering::Relaxed);
+    SENT_BITS.fetch_add(frame_bits(frame), Ordering::Relaxed);
+}
+
+pub(crate) fn record_tx_error() {
+    TX_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The bit position arbitration was last lost at. Reading it lets the controller capture
+/// the next loss, the value stays until then.
+pub(crate) fn arbitration_lost_capture() -> u8 {
+    TWAI0::regs().arb_lost_cap().read().arb_lost_cap().bits()
+}
+
+/// Call with the captures from before and after a transmit. A frame that lost at the same
+/// bit as the one before is missed, without the interrupt (the driver's) there is no better.
+pub(crate) fn record_arbitration(before: u8, after: u8) {
+    if before != after {
+        ARBITRATION_LOST.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Hands what the transmit side counted to `monitor`.
+pub(crate) fn collect(monitor: &mut BusMonitor) {
+    monitor.on_sent(SENT_FRAMES.swap(0, Ordering::Relaxed), SENT_BITS.swap(0, Ordering::Relaxed));
+    for _ in 0..TX_ERRORS.swap(0, Ordering::Relaxed) {
+        monitor.on_error();
+    }
+    monitor.on_arbitration_lost(ARBITRATION_LOST.swap(0, Ordering::Relaxed));
+}
+
+/// Transmit and receive error counters, and whether the controller went bus-off.
+pub(crate) fn error_counters() -> (u8, u8, bool) {
+    let regs = TWAI0::regs();
+    (
+        regs.tx_err_cnt().read().tx_err_cnt().bits(),
+        regs.rx_err_cnt().read().rx_err_cnt().bits(),
+        regs.status().read().bus_off_st().bit_is_set(),
+    )
+}
+
+/// Going bus-off puts the controller in reset mode. Leaving it starts the recovery: the
+/// controller waits for 128 times 11 recessive bits, then is back with cleared counters.
+pub(crate) fn start_recovery() {
+    TWAI0::regs().mode().modify(|_, w| w.reset_mode().clear_bit());
+}
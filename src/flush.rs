@@ -0,0 +1,104 @@
+//! Streams finished frames to the GC9A01 from the app core, so the main core can
+//! draw the next frame meanwhile.
+//!
+//! Framebuffers are passed by value: the renderer sends a finished one in a
+//! `FlushRequest`, and only gets it back through the fence once every byte went
+//! out. A buffer is never drawn into while it is being sent.
+use dashboard::damage::{self, Damage};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, Receiver, Sender};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::IntoStorage, primitives::Rectangle};
+use esp_hal::gpio::Output;
+use esp_hal::spi::master::SpiDmaBus;
+use esp_hal::time::{Duration, Instant};
+use esp_hal::Async;
+use log::warn;
+
+use crate::game::{MyFrameBuf, LCD_H_RES};
+
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+/// Pixels are converted to the display's big-endian byte order this many bytes at a time.
+const CHUNK_BYTES: usize = 1024;
+
+pub(crate) struct FlushRequest {
+    pub frame_buf: MyFrameBuf,
+    pub damage: Damage,
+}
+
+pub(crate) struct Flushed {
+    pub frame_buf: MyFrameBuf,
+    pub transfer_time: Duration,
+}
+
+pub(crate) type FlushChannel = Channel<CriticalSectionRawMutex, FlushRequest, 1>;
+pub(crate) type FlushSender<'ch> = Sender<'ch, CriticalSectionRawMutex, FlushRequest, 1>;
+pub(crate) type FlushReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, FlushRequest, 1>;
+pub(crate) type FenceChannel = Channel<CriticalSectionRawMutex, Flushed, 1>;
+pub(crate) type FenceSender<'ch> = Sender<'ch, CriticalSectionRawMutex, Flushed, 1>;
+pub(crate) type FenceReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, Flushed, 1>;
+
+/// The display's SPI link after mipidsi initialised the panel.
+pub(crate) struct Lcd {
+    pub spi: SpiDmaBus<'static, Async>,
+    pub dc: Output<'static>,
+    pub cs: Output<'static>,
+}
+
+impl Lcd {
+    async fn command(&mut self, command: u8, parameters: &[u8]) -> Result<(), esp_hal::spi::Error> {
+        self.dc.set_low();
+        self.spi.write_async(&[command]).await?;
+        self.dc.set_high();
+        if !parameters.is_empty() {
+            self.spi.write_async(parameters).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_region(&mut self, data: &[Rgb565], area: Rectangle) -> Result<(), esp_hal::spi::Error> {
+        let Some(end) = area.bottom_right() else {
+            return Ok(());
+        };
+        let [x0_high, x0_low] = (area.top_left.x as u16).to_be_bytes();
+        let [x1_high, x1_low] = (end.x as u16).to_be_bytes();
+        let [y0_high, y0_low] = (area.top_left.y as u16).to_be_bytes();
+        let [y1_high, y1_low] = (end.y as u16).to_be_bytes();
+        self.command(CASET, &[x0_high, x0_low, x1_high, x1_low]).await?;
+        self.command(RASET, &[y0_high, y0_low, y1_high, y1_low]).await?;
+        self.command(RAMWR, &[]).await?;
+
+        let mut chunk = [0u8; CHUNK_BYTES];
+        let mut len = 0;
+        for pixel in damage::region(data, LCD_H_RES, area) {
+            chunk[len..len + 2].copy_from_slice(&pixel.into_storage().to_be_bytes());
+            len += 2;
+            if len == CHUNK_BYTES {
+                self.spi.write_async(&chunk).await?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.spi.write_async(&chunk[..len]).await?;
+        }
+        Ok(())
+    }
+}
+
+#[task]
+pub(crate) async fn display_flusher(mut lcd: Lcd, requests: FlushReceiver<'static>, fence: FenceSender<'static>) {
+    loop {
+        let FlushRequest { frame_buf, damage } = requests.receive().await;
+        let start = Instant::now();
+        lcd.cs.set_low();
+        for area in damage.rects() {
+            if let Err(e) = lcd.write_region(&frame_buf.data[..], *area).await {
+                warn!("Error flushing {:?}: {:?}", area, e);
+            }
+        }
+        lcd.cs.set_high();
+        fence.send(Flushed { frame_buf, transfer_time: start.elapsed() }).await;
+    }
+}
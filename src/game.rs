@@ -1,53 +1,22 @@
-use core::cell::RefCell;
+use core::{alloc::Layout, cell::RefCell, mem, ptr};
 
-use alloc::{boxed::Box, format, sync::Arc};
-use bevy_ecs::{resource::Resource, schedule::Schedule, system::{NonSendMut, Res, ResMut}, world::World};
+use alloc::{boxed::Box, sync::Arc};
+use bevy_ecs::{event::{Event, EventWriter, Events}, resource::Resource, schedule::{IntoScheduleConfigs, Schedule}, system::{NonSendMut, Res, ResMut}, world::World};
+use embassy_futures::block_on;
 use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
-use embedded_graphics::{mono_font::{ascii::{FONT_10X20, FONT_6X9}, MonoTextStyle}, pixelcolor::Rgb565, prelude::*, primitives::{Circle, PrimitiveStyle, Rectangle}, text::Text};
-use embedded_graphics_framebuf::{backends::FrameBufferBackend, FrameBuf};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics_framebuf::FrameBuf;
 use embedded_hal_bus::spi::ExclusiveDevice;
-use esp_hal::{delay::Delay, gpio::Output, spi::master::SpiDmaBus, time::Instant, timer::systimer::SystemTimer, Blocking};
-use heapless::String;
+use esp_alloc::MemoryCapability;
+use esp_hal::{delay::Delay, gpio::{Input, Output}, spi::master::SpiDmaBus, time::{Duration, Instant}, timer::systimer::SystemTimer, Blocking};
 use log::info;
 use mipidsi::{interface::SpiInterface, models::GC9A01};
 
-use crate::{car_state::CarState, gauge::{DashboardContext, Gauge}};
+use dashboard::{car_state::CarState, damage::{self, Damage, Tracked}, framebuffer::HeapBuffer, page::{Page, PageManager}, screen::{self, Screen}};
 
-/// A wrapper around a boxed array that implements FrameBufferBackend.
-/// This allows the framebuffer to be allocated on the heap.
-pub struct HeapBuffer<C: PixelColor, const N: usize>(Box<[C; N]>);
-
-impl<C: PixelColor, const N: usize> HeapBuffer<C, N> {
-    pub fn new(data: Box<[C; N]>) -> Self {
-        Self(data)
-    }
-}
-
-impl<C: PixelColor, const N: usize> core::ops::Deref for HeapBuffer<C, N> {
-    type Target = [C; N];
-    fn deref(&self) -> &Self::Target {
-        &*self.0
-    }
-}
-
-impl<C: PixelColor, const N: usize> core::ops::DerefMut for HeapBuffer<C, N> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut *self.0
-    }
-}
-
-impl<C: PixelColor, const N: usize> FrameBufferBackend for HeapBuffer<C, N> {
-    type Color = C;
-    fn set(&mut self, index: usize, color: Self::Color) {
-        self.0[index] = color;
-    }
-    fn get(&self, index: usize) -> Self::Color {
-        self.0[index]
-    }
-    fn nr_elements(&self) -> usize {
-        N
-    }
-}
+use crate::autobaud::DETECTED_BITRATE;
+use crate::flush::{FenceChannel, FenceReceiver, FlushChannel, FlushRequest, FlushSender, Flushed};
+use crate::storage::Persistence;
 
 // --- Type Alias for the Concrete Display ---
 // Use the DMA-enabled SPI bus type.
@@ -62,150 +31,221 @@ pub(crate) type GaugeDisplay = mipidsi::Display<
 >;
 
 // --- LCD Resolution and FrameBuffer Type Aliases ---
-const LCD_H_RES: usize = 240;
-const LCD_V_RES: usize = 240;
+pub(crate) const LCD_H_RES: usize = screen::WIDTH;
+const LCD_V_RES: usize = screen::HEIGHT;
 const LCD_BUFFER_SIZE: usize = LCD_H_RES * LCD_V_RES;
 
 // We want our pixels stored as Rgb565.
 type FbBuffer = HeapBuffer<Rgb565, LCD_BUFFER_SIZE>;
 // Define a type alias for the complete FrameBuf.
-type MyFrameBuf = FrameBuf<Rgb565, FbBuffer>;
-
+pub(crate) type MyFrameBuf = FrameBuf<Rgb565, FbBuffer>;
+
+/// A black framebuffer in PSRAM.
+fn new_frame_buf() -> MyFrameBuf {
+    let layout = Layout::new::<[Rgb565; LCD_BUFFER_SIZE]>();
+    let data = unsafe {
+        let data = esp_alloc::HEAP.alloc_caps(MemoryCapability::External.into(), layout);
+        assert!(!data.is_null(), "no PSRAM left for a framebuffer");
+        // All zero is black in Rgb565
+        ptr::write_bytes(data, 0, layout.size());
+        Box::from_raw(data as *mut [Rgb565; LCD_BUFFER_SIZE])
+    };
+    MyFrameBuf::new(HeapBuffer::new(data), LCD_H_RES, LCD_V_RES)
+}
 
+/// The back buffer of the pair, the front one is with `display_flusher` or waiting in the fence.
 #[derive(Resource)]
 struct FrameBufferResource {
     frame_buf: MyFrameBuf,
+    /// What was drawn since the last flush.
+    damage: Damage,
 }
 
 impl FrameBufferResource {
     fn new() -> Self {
-        // Allocate the framebuffer data on the heap.
-        let fb_data: Box<[Rgb565; LCD_BUFFER_SIZE]> = Box::new([Rgb565::BLACK; LCD_BUFFER_SIZE]);
-        let heap_buffer = HeapBuffer::new(fb_data);
-        let frame_buf = MyFrameBuf::new(heap_buffer, LCD_H_RES, LCD_V_RES);
-        Self { frame_buf }
+        Self { frame_buf: new_frame_buf(), damage: Damage::new() }
     }
 }
 
-/// Draws the game grid using the cell age for color.
-fn draw_grid<D: DrawTarget<Color = Rgb565>>(
-    display: &mut D,
-    game: &ResMut<AppStateResource>,
-    fps: u64,
-) -> Result<(), D::Error> {
-    let border_color = Rgb565::new(230, 230, 230);
-
-    Circle::with_center(Point::new(120, 120), 140)
-        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLUE))
-        .draw(display)?;
-    Rectangle::new(Point::new(10, 10), Size::new(7, 7))
-        .into_styled(PrimitiveStyle::with_fill(border_color))
-        .draw(display)?;
+#[derive(Resource)]
+struct FlushResource {
+    requests: FlushSender<'static>,
+    /// Hands the front buffer back once it is on the display.
+    fence: FenceReceiver<'static>,
+}
 
-    let cloned = game.state.lock(|state| {
-        state.borrow().clone()
-    });
+/// How often the measured frame rate gets logged.
+const FPS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Holding the button this long resets the trip on screen.
+const LONG_PRESS: Duration = Duration::from_millis(1500);
 
-    Text::new(
-        format!("msgs rcv: {}", cloned.message_count()).as_str(),
-        Point::new(65, 120),
-        MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE),
-    )
-    .draw(display)?;
-    Text::new(
-        format!("voltage: {}", cloned.voltage()).as_str(),
-        Point::new(65, 80),
-        MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE),
-    )
-    .draw(display)?;
-
-    Text::new(
-        format!("fps: {}", fps).as_str(),
-        Point::new(65, 170),
-        MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE),
-    )
-    .draw(display)?;
-
-    Ok(())
+/// Asks `page_system` to move to another page.
+#[derive(Event, Debug, Clone, Copy)]
+enum PageEvent {
+    Next,
+    Previous,
 }
 
 #[derive(Resource)]
 struct AppStateResource {
     state: Arc<Mutex<CriticalSectionRawMutex,RefCell<CarState>>>,
-    last_frame: Instant,
-    gauge: Gauge<'static,240,240,10,162,255>,
-    gauge_context: DashboardContext<'static,240,240>,
+    screen: Screen,
+    pages: PageManager,
+    report_start: Instant,
+    flushed_pixels: u32,
+    /// Drawing into the back buffer, summed over the frames that changed something.
+    draw_time: Duration,
+    /// Streaming the front buffer out, summed over the flushes that came back.
+    transfer_time: Duration,
+    /// Frames sent to the flusher since `report_start`, the ones that count for the frame rate.
+    transfers: u32,
+}
+
+// Not Sync, so it is a NonSend resource.
+struct Button {
+    input: Input<'static>,
+    pressed_at: Option<Instant>,
+}
+
+/// A short press acknowledges the alert on the banner, or goes to the next page when
+/// there is none. A long press on a trip page resets that trip, two on the trouble codes page
+/// clear the codes, elsewhere it goes back a page.
+fn button_system(mut button: NonSendMut<Button>, mut game: ResMut<AppStateResource>, mut pages: EventWriter<PageEvent>) {
+    let pressed = button.input.is_low();
+    match (pressed, button.pressed_at) {
+        (true, None) => button.pressed_at = Some(Instant::now()),
+        (false, Some(since)) => {
+            button.pressed_at = None;
+            let game = game.as_mut();
+            if since.elapsed() >= LONG_PRESS {
+                match game.pages.current() {
+                    Page::Trip(trip) => game.state.lock(|state| state.borrow_mut().reset_trip(trip)),
+                    Page::TroubleCodes => {
+                        let now_ms = Instant::now().duration_since_epoch().as_millis();
+                        game.state.lock(|state| state.borrow_mut().confirm_clear_dtcs(now_ms));
+                    }
+                    _ => {
+                        pages.write(PageEvent::Previous);
+                    }
+                }
+            } else if game.screen.alerts.acknowledge() {
+                info!("Alert acknowledged");
+            } else {
+                pages.write(PageEvent::Next);
+            }
+        }
+        _ => {}
+    }
 }
 
-// We wrap it as a NonSend resource so that Bevy doesn’t require Sync.
-struct DisplayResource {
-    display: GaugeDisplay,
+/// Switches pages. The events are drained here, so they need no update system.
+fn page_system(mut events: ResMut<Events<PageEvent>>, mut game: ResMut<AppStateResource>) {
+    let game = game.as_mut();
+    for event in events.drain() {
+        match event {
+            PageEvent::Next => game.pages.next(&mut game.screen),
+            PageEvent::Previous => game.pages.previous(&mut game.screen),
+        }
+        info!("Showing the {} page", game.pages.current().name());
+        if game.pages.current() == Page::TroubleCodes {
+            game.state.lock(|state| state.borrow_mut().read_dtcs());
+        }
+    }
+}
+
+/// Keeps the settings and the trip counters in flash up to date.
+fn persist_system(mut persistence: NonSendMut<Persistence>, game: Res<AppStateResource>) {
+    if let Some(bitrate) = DETECTED_BITRATE.try_take() {
+        persistence.settings.bitrate = Some(bitrate);
+    }
+    persistence.settings.page = game.pages.current().index();
+    persistence.save_settings();
+    let trips = game.state.lock(|state| state.borrow().trips().clone());
+    persistence.save_trips(&trips);
 }
 
 fn render_system(
-    mut display_res: NonSendMut<DisplayResource>,
+    flush: Res<FlushResource>,
     mut game: ResMut<AppStateResource>,
     mut fb_res: ResMut<FrameBufferResource>,
 ) {
     let now = Instant::now();
-    let duration = now - game.last_frame;
-    game.as_mut().last_frame = now;
-    let fps = 1000 / duration.as_millis();
-
-    // Clear the framebuffer.
-    // fb_res.frame_buf.clear(Rgb565::BLACK).unwrap();
-    // Draw the game grid (using the age-based color) and generation number.
-    // draw_grid(&mut fb_res.frame_buf, &game, fps).unwrap();
-    let value = game.state.lock(|state| {
-        let state = state.borrow();
-        // Update the gauge value based on the car state.
-        state.message_count().try_into().unwrap_or(0)
-    }) % 100;
-    game.gauge.update_indicated();
-    // let mut line = game.gauge.get_sline1();
-    // write!(&mut line,"{}fps",fps);
-    game.gauge.set_value(value);
-    // info!("FPS: {}, Value: {}", fps, value);
-
-    let dashboard_context = &game.gauge_context;
-
-
-    game.gauge.draw_clear_mask(&mut fb_res.frame_buf, &dashboard_context);
-    // game.gauge.draw_static(&mut fb_res.frame_buf,&dashboard_context);
-    game.gauge.draw_dynamic(&mut fb_res.frame_buf,&dashboard_context);
-    // Define the area covering the entire framebuffer.
-    let area = Rectangle::new(Point::zero(), fb_res.frame_buf.size());
-    // Flush the framebuffer to the physical display.
-    let after_draw = Instant::now();
-    let draw_duration = after_draw - now;
-    // info!("Draw duration: {}ms", draw_duration.as_millis());
-    display_res
-        .display
-        .fill_contiguous(&area, fb_res.frame_buf.data.iter().copied())
-        .unwrap();
-    let draw_duration = Instant::now() - after_draw;
-    // info!("Actual draw duration: {}ms", draw_duration.as_millis());
+    let cloned = game.state.lock(|state| {
+        state.borrow().clone()
+    });
+    let game = game.as_mut();
+    let fb_res = fb_res.as_mut();
+    game.screen.update(&cloned, now.duration_since_epoch().as_millis());
+    game.screen.draw(&mut Tracked::new(&mut fb_res.frame_buf, &mut fb_res.damage));
+
+    if !fb_res.damage.is_empty() {
+        game.draw_time += now.elapsed();
+        // The fence: the front buffer is only drawn into again once it is back from the flusher
+        let Flushed { frame_buf: mut front, transfer_time } = block_on(flush.fence.receive());
+        game.transfer_time += transfer_time;
+        game.transfers += 1;
+        // The front buffer is one frame behind, bring it up to date before it becomes the back buffer
+        damage::copy_regions(&fb_res.frame_buf.data[..], &mut front.data[..], LCD_H_RES, fb_res.damage.rects());
+        mem::swap(&mut fb_res.frame_buf, &mut front);
+        game.flushed_pixels += fb_res.damage.area();
+        block_on(flush.requests.send(FlushRequest { frame_buf: front, damage: mem::take(&mut fb_res.damage) }));
+    }
 
+    let elapsed = now - game.report_start;
+    if elapsed >= FPS_REPORT_INTERVAL {
+        let fps = (game.transfers as u64 * 1000 / elapsed.as_millis()) as u32;
+        game.state.lock(|state| state.borrow_mut().set_render_fps(fps));
+        info!(
+            "{} fps, {} pixels flushed per frame, draw {} us, transfer {} us",
+            fps,
+            game.flushed_pixels / game.transfers.max(1),
+            (game.draw_time / game.transfers.max(1)).as_micros(),
+            (game.transfer_time / game.transfers.max(1)).as_micros()
+        );
+        game.report_start = now;
+        game.flushed_pixels = 0;
+        game.draw_time = Duration::ZERO;
+        game.transfer_time = Duration::ZERO;
+        game.transfers = 0;
+    }
 }
 
 
-pub(crate) fn setup_game(display: GaugeDisplay, car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>, system_timer: SystemTimer<'static>)->(Schedule, World) {
+pub(crate) fn setup_game(
+    flush_channel: &'static FlushChannel,
+    fence_channel: &'static FenceChannel,
+    button: Input<'static>,
+    persistence: Persistence,
+    car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>,
+    system_timer: SystemTimer<'static>,
+)->(Schedule, World) {
     // --- Initialize Game Resources ---
+    let pages = PageManager::new(Page::from_index(persistence.settings.page), persistence.settings.economy_unit);
     let game = AppStateResource {
         state: car_state,
-        last_frame: Instant::now(),
-        gauge: crate::gauge::Gauge::new_speedo(["1","2","3","4","5","6","7","8","9","10","11","12","13"]),
-        gauge_context: DashboardContext::new()
+        screen: pages.screen(),
+        pages,
+        report_start: Instant::now(),
+        flushed_pixels: 0,
+        draw_time: Duration::ZERO,
+        transfer_time: Duration::ZERO,
+        transfers: 0,
     };
     let mut fb_res = FrameBufferResource::new();
+    // The second buffer starts out as the front one, black like the freshly cleared display.
+    // The fence is empty, so this cannot fail
+    let _ = fence_channel.try_send(Flushed { frame_buf: new_frame_buf(), transfer_time: Duration::ZERO });
 
-    game.gauge.draw_static(&mut fb_res.frame_buf, &game.gauge_context);
+    game.screen.draw_static(&mut Tracked::new(&mut fb_res.frame_buf, &mut fb_res.damage));
     let mut world = World::default();
     world.insert_resource(game);
-    world.insert_non_send_resource(DisplayResource { display });
+    world.insert_resource(FlushResource { requests: flush_channel.sender(), fence: fence_channel.receiver() });
     world.insert_resource(fb_res);
+    world.init_resource::<Events<PageEvent>>();
+    world.insert_non_send_resource(Button { input: button, pressed_at: None });
+    world.insert_non_send_resource(persistence);
 
     let mut schedule = Schedule::default();
-    schedule.add_systems(render_system);
+    schedule.add_systems((button_system, page_system, render_system, persist_system).chain());
     (schedule, world)
 }
\ No newline at end of file
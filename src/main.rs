@@ -7,26 +7,29 @@ use core::ptr::addr_of_mut;
 
 use alloc::boxed::Box;
 
+mod acceptance;
+mod autobaud;
+mod bus;
 // mod can;
-mod gauge;
+mod flush;
 mod game;
+mod storage;
 // mod demo_can;
-mod car_state;
 
 use alloc::sync::Arc;
-use circ_buffer::RingBuffer;
-use embassy_executor::task;
+use embassy_executor::{task, Spawner};
 use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
 use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::channel::{Channel, Receiver, Sender};
-use embassy_time::Timer;
+use embassy_sync::signal::Signal;
+use embassy_time::{block_for, with_timeout, Duration, Instant, Timer};
 use embedded_graphics::{
     pixelcolor::Rgb565,
     prelude::*,
 };
 use embedded_hal::delay::DelayNs;
 use embedded_hal_bus::spi::ExclusiveDevice;
-use esp_hal::analog::adc::{Adc, AdcConfig, AdcPin, Attenuation};
+use esp_hal::analog::adc::{Adc, AdcCalCurve, AdcConfig, AdcPin, Attenuation};
 use esp_hal::dma::{DmaRxBuf, DmaTxBuf};
 use esp_hal::peripherals::{ADC1, GPIO1};
 use esp_hal::timer::systimer::SystemTimer;
@@ -34,35 +37,150 @@ use esp_hal::{dma_buffers, Async};
 use esp_hal::system::{CpuControl, Stack};
 use esp_hal::timer::timg::TimerGroup;
 use esp_hal::timer::AnyTimer;
-use esp_hal::twai::{EspTwaiFrame, Twai};
+use esp_hal::twai::{EspTwaiFrame, TwaiRx, TwaiTx};
 use esp_hal::{
     Blocking,
-    gpio::{Level, Output, OutputConfig},
+    gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull},
     main,
     spi::master::Spi,
     time::Rate,
 };
 use esp_hal::{
     delay::Delay,
-    twai::{BaudRate, TwaiConfiguration, TwaiMode},
+    twai::{TwaiConfiguration, TwaiMode},
 };
 use esp_hal_embassy::Executor;
 use esp_println::{logger::init_logger_from_env, println};
-use log::{info, warn};
+use log::{debug, info, trace, warn};
 use mipidsi::options::{ColorOrder, Orientation, Rotation};
 use mipidsi::{Builder, models::GC9A01};
 use mipidsi::{interface::SpiInterface, options::ColorInversion};
 use static_cell::StaticCell;
 
-use crate::car_state::CarState;
+use dashboard::bitrate::Bitrate;
+use dashboard::bus_health::{BusMonitor, Recovery};
+use dashboard::car_state::CarState;
+use dashboard::isotp::{Event, FlowControl, IsoTpLayer, IsoTpMessage};
+#[cfg(feature = "replay")]
+use dashboard::replay::{CandumpLog, ReplayMode, ReplaySource};
+use dashboard::ring::{Consumer, DropPolicy, FrameRing, Producer};
+use dashboard::scheduler::{BusBudget, TxScheduler};
+use dashboard::source::{CanEvent, FrameSource};
+use dashboard::voltage::{Divider, VoltageFilter};
+use crate::acceptance::set_acceptance_filter;
+use crate::autobaud::{baud_rate, select_bitrate, CanPins, Confirmation, Selection};
+use crate::flush::{display_flusher, FenceChannel, FlushChannel, Lcd};
 use crate::game::{setup_game, GaugeDisplay};
+use crate::storage::Persistence;
 
 
 static mut APP_CORE_STACK: Stack<8192> = Stack::new();
-const CHANNEL_SIZE: usize = 16;
-type CanFrameChannel = Channel<CriticalSectionRawMutex, EspTwaiFrame, CHANNEL_SIZE>;
-type CanFrameSender<'ch> = Sender<'ch, CriticalSectionRawMutex, EspTwaiFrame, CHANNEL_SIZE>;
-type CanFrameReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, EspTwaiFrame, CHANNEL_SIZE>;
+/// Frames on their way from `frame_received` to `car_state_maintainer`. Reception never
+/// waits for the car state: when the ring is full the policy decides what is given up.
+const RX_RING_SIZE: usize = 32;
+/// Only the latest value of each signal is shown, so an older frame with the same ID can go.
+const RX_DROP_POLICY: DropPolicy = DropPolicy::CoalesceById;
+type RxRing = FrameRing<EspTwaiFrame, RX_RING_SIZE>;
+type RxProducer = Producer<'static, EspTwaiFrame, RX_RING_SIZE>;
+type RxConsumer = Consumer<'static, EspTwaiFrame, RX_RING_SIZE>;
+/// Reassembled ISO-TP messages are rare and cannot be coalesced, they get a channel of their own.
+const MESSAGE_CHANNEL_SIZE: usize = 4;
+type CanMessageChannel = Channel<CriticalSectionRawMutex, IsoTpMessage, MESSAGE_CHANNEL_SIZE>;
+type CanMessageSender<'ch> = Sender<'ch, CriticalSectionRawMutex, IsoTpMessage, MESSAGE_CHANNEL_SIZE>;
+type CanMessageReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, IsoTpMessage, MESSAGE_CHANNEL_SIZE>;
+/// Raised by `frame_received` whenever it queued a frame or a message.
+type RxReady = Signal<CriticalSectionRawMutex, ()>;
+const TX_CHANNEL_SIZE: usize = 8;
+type CanTxChannel = Channel<CriticalSectionRawMutex, EspTwaiFrame, TX_CHANNEL_SIZE>;
+type CanTxSender<'ch> = Sender<'ch, CriticalSectionRawMutex, EspTwaiFrame, TX_CHANNEL_SIZE>;
+type CanTxReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, EspTwaiFrame, TX_CHANNEL_SIZE>;
+/// ISO-TP frames from `frame_received` (flow control, our own consecutive frames) on their
+/// way to `tx_scheduler`, which sends them ahead of the polls and within the same budget.
+const ONE_SHOT_CHANNEL_SIZE: usize = 4;
+type OneShotChannel = Channel<CriticalSectionRawMutex, EspTwaiFrame, ONE_SHOT_CHANNEL_SIZE>;
+type OneShotSender<'ch> = Sender<'ch, CriticalSectionRawMutex, EspTwaiFrame, ONE_SHOT_CHANNEL_SIZE>;
+type OneShotReceiver<'ch> = Receiver<'ch, CriticalSectionRawMutex, EspTwaiFrame, ONE_SHOT_CHANNEL_SIZE>;
+/// How often the ISO-TP layer gets a chance to expire stalled transfers when the bus is quiet.
+const ISOTP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Build with `CAN_LISTEN_ONLY=1` to only listen: nothing is sent, not even acknowledgements.
+const LISTEN_ONLY: bool = option_env!("CAN_LISTEN_ONLY").is_some();
+/// A transmit still pending after this long is given up, the controller may be bus-off.
+const TX_TIMEOUT: Duration = Duration::from_millis(100);
+/// How often the transmit scheduler logs what it sent.
+const TX_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+/// The main loop runs at most this often. Every pass takes the car state lock, which keeps
+/// interrupts off on both cores, so it must not spin.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The receive side of `frame_received`, which already did the ISO-TP part.
+struct RxQueues {
+    frames: RxProducer,
+    messages: CanMessageSender<'static>,
+    ready: &'static RxReady,
+}
+
+impl RxQueues {
+    /// The TWAI does not timestamp frames, right after the driver hands one over is the closest there is.
+    fn push_frame(&mut self, frame: EspTwaiFrame) {
+        self.frames.push(frame, Instant::now().as_micros());
+        self.ready.signal(());
+    }
+
+    fn push_message(&mut self, message: IsoTpMessage) {
+        if self.messages.try_send(message).is_err() {
+            warn!("ISO-TP message dropped, the car state is not keeping up");
+        }
+        self.ready.signal(());
+    }
+}
+
+/// Traffic from the TWAI peripheral, messages first as they are what was asked for.
+struct TwaiSource {
+    frames: RxConsumer,
+    messages: CanMessageReceiver<'static>,
+    ready: &'static RxReady,
+    received_at_us: u64,
+}
+
+impl FrameSource for TwaiSource {
+    type Frame = EspTwaiFrame;
+
+    async fn next_event(&mut self) -> Option<CanEvent<EspTwaiFrame>> {
+        loop {
+            if let Ok(message) = self.messages.try_receive() {
+                self.received_at_us = Instant::now().as_micros();
+                return Some(CanEvent::Message(message));
+            }
+            if let Some(stamped) = self.frames.pop() {
+                self.received_at_us = stamped.at_us;
+                return Some(CanEvent::Frame(stamped.frame));
+            }
+            self.ready.wait().await;
+        }
+    }
+
+    fn received_at_ms(&self) -> Option<u64> {
+        Some(self.received_at_us / 1000)
+    }
+}
+
+/// Build with `--features replay` and `CAN_REPLAY_LOG=<candump .log>` to drive the
+/// dashboard from a recording instead of the bus.
+#[cfg(feature = "replay")]
+type ActiveSource = ReplaySource<CandumpLog<'static>, embassy_time::Delay>;
+#[cfg(not(feature = "replay"))]
+type ActiveSource = TwaiSource;
+
+#[cfg(feature = "replay")]
+fn frame_source(_source: TwaiSource) -> ActiveSource {
+    let log = CandumpLog::new(include_str!(env!("CAN_REPLAY_LOG")));
+    ReplaySource::new(log, embassy_time::Delay, ReplayMode::Loop)
+}
+
+#[cfg(not(feature = "replay"))]
+fn frame_source(source: TwaiSource) -> ActiveSource {
+    source
+}
 
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
@@ -70,61 +188,65 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {}
 }
 
-type VoltageAdcPin = AdcPin<GPIO1<'static>,ADC1<'static>>;
+type VoltageAdcPin = AdcPin<GPIO1<'static>, ADC1<'static>, AdcCalCurve<ADC1<'static>>>;
 type VoltageAdc = Adc<'static, ADC1<'static>, Blocking>;
+/// 11 dB reaches about 3.1 V at the pin, `BATTERY_DIVIDER` keeps 16 V below that.
+const VOLTAGE_ATTENUATION: Attenuation = Attenuation::_11dB;
+/// Measure the resistors, 1% parts are already off by more than the 50 mV we want.
+const BATTERY_DIVIDER: Divider = Divider { top_ohms: 100_000.0, bottom_ohms: 22_000.0 };
+
+/// What the ESP32-S3 ADC can measure at each attenuation, from the datasheet.
+fn full_scale_mv(attenuation: Attenuation) -> u16 {
+    match attenuation {
+        Attenuation::_0dB => 950,
+        Attenuation::_2p5dB => 1250,
+        Attenuation::_6dB => 1750,
+        Attenuation::_11dB => 3100,
+    }
+}
+
 #[main]
 fn main() -> ! {
     let peripherals = esp_hal::init(esp_hal::Config::default());
     // Increase heap size as needed.
     esp_alloc::heap_allocator!(size: 150000);
+    // The two framebuffers do not fit next to everything else, they go to PSRAM
+    esp_alloc::psram_allocator!(peripherals.PSRAM, esp_hal::psram);
     init_logger_from_env();
 
-    let can_frame_channel: CanFrameChannel = Channel::new();
-    let can_frame_channel = Box::leak(Box::new(can_frame_channel));
+    static RX_RING: StaticCell<RxRing> = StaticCell::new();
+    let (rx_frames, source_frames) = RX_RING.init(FrameRing::new(RX_DROP_POLICY)).split();
+    let can_message_channel: CanMessageChannel = Channel::new();
+    let can_message_channel = Box::leak(Box::new(can_message_channel));
+    let rx_ready: &'static RxReady = Box::leak(Box::new(Signal::new()));
+    let can_tx_channel: CanTxChannel = Channel::new();
+    let can_tx_channel = Box::leak(Box::new(can_tx_channel));
+    let one_shot_channel: OneShotChannel = Channel::new();
+    let one_shot_channel = Box::leak(Box::new(one_shot_channel));
+    let flush_channel: FlushChannel = Channel::new();
+    let flush_channel = Box::leak(Box::new(flush_channel));
+    let fence_channel: FenceChannel = Channel::new();
+    let fence_channel = Box::leak(Box::new(fence_channel));
     
     let mut cpu_control = CpuControl::new(peripherals.CPU_CTRL);
 
-    let car_state = Arc::new(Mutex::new(RefCell::new(CarState::default())));
+    let mut persistence = Persistence::open();
+    let mut state = CarState::default();
+    persistence.load_trips(state.trips_mut());
+    let car_state = Arc::new(Mutex::new(RefCell::new(state)));
     
     let systimer = SystemTimer::new(peripherals.SYSTIMER);
 
-    let can_rx = peripherals.GPIO33; // GREY -> yellow
-    let can_tx = peripherals.GPIO21; // VIOLET -> white
+    let can_pins = CanPins {
+        twai: peripherals.TWAI0,
+        rx: peripherals.GPIO33, // GREY -> yellow
+        tx: peripherals.GPIO21, // VIOLET -> white
+    };
 
     let timg0 = TimerGroup::new(peripherals.TIMG0);
     let timer0: AnyTimer = timg0.timer0.into();
     let timer1: AnyTimer = timg0.timer1.into();
     esp_hal_embassy::init([timer0, timer1]);
-    let car_state_async_side = car_state.clone();
-    let _guard = cpu_control
-        .start_app_core(unsafe { &mut *addr_of_mut!(APP_CORE_STACK) }, move || {
-
-            let can = TwaiConfiguration::new(
-                    peripherals.TWAI0,
-                    can_rx,
-                    can_tx,
-                    BaudRate::B125K,
-                    TwaiMode::Normal,
-                )
-                .into_async()
-                .start();
-            static EXECUTOR: StaticCell<Executor> = StaticCell::new();
-            let executor = EXECUTOR.init(Executor::new());
-            let receiver = can_frame_channel.receiver();
-            let sender = can_frame_channel.sender();
-            let mut adc_config = AdcConfig::default();
-            let mut adc_pin = adc_config.enable_pin(peripherals.GPIO1, Attenuation::_0dB);
-            let mut voltage_adc = Adc::new(peripherals.ADC1, adc_config);
-
-            let a= voltage_adc.read_oneshot(&mut adc_pin);
-            executor.run(|spawner| {
-                spawner.must_spawn(frame_received(can, sender));
-                spawner.must_spawn(car_state_maintainer(car_state_async_side.clone(), receiver));
-                spawner.must_spawn(voltage_calculator(adc_pin, voltage_adc, car_state_async_side.clone()));
-            });
-        })
-        .unwrap();
-
     // --- DMA Buffers for SPI ---
     let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(1024);
     let dma_rx_buf = DmaRxBuf::new(rx_descriptors, rx_buffer).unwrap();
@@ -168,60 +290,257 @@ fn main() -> ! {
         .unwrap();
 
     display.clear(Rgb565::BLACK).unwrap();
+    // From here on the frames are streamed by `display_flusher`, straight over the bus
+    let (di, _model, _reset) = display.release();
+    let (spi_device, lcd_dc, _) = di.release();
+    let (spi, cs_output) = spi_device.release();
 
-    // Backlight
+    // Backlight, only on or off until it gets PWM
     let mut backlight = Output::new(peripherals.GPIO2, Level::High, OutputConfig::default());
-    backlight.set_high();
+    if persistence.settings.brightness == 0 {
+        backlight.set_low();
+    }
+
+    let car_state_async_side = car_state.clone();
+    let stored_bitrate = persistence.settings.bitrate;
+    let _guard = cpu_control
+        .start_app_core(unsafe { &mut *addr_of_mut!(APP_CORE_STACK) }, move || {
+
+            // Binds the SPI interrupt to this core, where the flusher runs
+            let lcd = Lcd { spi: spi.into_async(), dc: lcd_dc, cs: cs_output };
+            static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+            let executor = EXECUTOR.init(Executor::new());
+            let rx = RxQueues { frames: rx_frames, messages: can_message_channel.sender(), ready: rx_ready };
+            let source =
+                TwaiSource { frames: source_frames, messages: can_message_channel.receiver(), ready: rx_ready, received_at_us: 0 };
+            let mut adc_config = AdcConfig::default();
+            // Curve calibration corrects the readings with the line fitted at the factory, stored in eFuse
+            let adc_pin = adc_config.enable_pin_with_cal::<_, AdcCalCurve<ADC1<'static>>>(peripherals.GPIO1, VOLTAGE_ATTENUATION);
+            let voltage_adc = Adc::new(peripherals.ADC1, adc_config);
+
+            executor.run(|spawner| {
+                // `render_system` waits on the flusher for every frame, so nothing on this core
+                // may hold it up, auto-baud included
+                spawner.must_spawn(display_flusher(lcd, flush_channel.receiver(), fence_channel.sender()));
+                spawner.must_spawn(car_state_maintainer(car_state_async_side.clone(), frame_source(source)));
+                spawner.must_spawn(voltage_calculator(adc_pin, voltage_adc, car_state_async_side.clone()));
+                // A recording already contains the replies, so the bus is left alone while replaying
+                if cfg!(not(feature = "replay")) {
+                    spawner.must_spawn(can_bus(
+                        spawner,
+                        can_pins,
+                        stored_bitrate,
+                        rx,
+                        can_tx_channel,
+                        one_shot_channel,
+                        car_state_async_side.clone(),
+                    ));
+                }
+            });
+        })
+        .unwrap();
+    persistence.share_flash_with(cpu_control);
 
-    let (mut schedule,mut world) = setup_game(display, car_state.clone(), systimer);
+    // The BOOT button, pressed is low
+    let button = Input::new(peripherals.GPIO0, InputConfig::default().with_pull(Pull::Up));
+    let (mut schedule,mut world) = setup_game(flush_channel, fence_channel, button, persistence, car_state.clone(), systimer);
     loop {
+        let frame_start = Instant::now();
         schedule.run(&mut world);
-        display_delay.delay_ms(10u32);
+        // Nothing to wait on here, the main core has no executor
+        if let Some(rest) = FRAME_INTERVAL.checked_sub(frame_start.elapsed()) {
+            block_for(rest);
+        }
     }
 }
 
+/// Picks the bitrate, which takes a few seconds when auto-baud has to listen for it,
+/// then brings the bus up and starts the tasks that use it.
 #[task]
-async fn car_state_maintainer(car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>, receiver: CanFrameReceiver<'static>) {
-    loop {
-        let msg= receiver.receive().await;
+async fn can_bus(
+    spawner: Spawner,
+    mut pins: CanPins,
+    stored_bitrate: Option<Bitrate>,
+    rx: RxQueues,
+    tx_channel: &'static CanTxChannel,
+    one_shot_channel: &'static OneShotChannel,
+    car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>,
+) {
+    let selection = select_bitrate(&mut pins, stored_bitrate).await;
+    let bitrate = selection.bitrate;
+    let mut can = TwaiConfiguration::new(
+            pins.twai,
+            pins.rx,
+            pins.tx,
+            baud_rate(bitrate),
+            if LISTEN_ONLY { TwaiMode::ListenOnly } else { TwaiMode::Normal },
+        )
+        .into_async();
+    set_acceptance_filter(&mut can);
+    let (twai_rx, twai_tx) = can.start().split();
+    spawner.must_spawn(frame_received(twai_rx, selection, rx, one_shot_channel.sender(), car_state.clone()));
+    spawner.must_spawn(frame_transmitter(twai_tx, tx_channel.receiver()));
+    spawner.must_spawn(tx_scheduler(bitrate, one_shot_channel.receiver(), tx_channel.sender(), car_state));
+}
+
+#[task]
+async fn car_state_maintainer(car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>, mut source: ActiveSource) {
+    while let Some(event) = source.next_event().await {
+        let now_ms = source.received_at_ms().unwrap_or_else(|| Instant::now().as_millis());
         car_state.lock(|state| {
-            state.borrow_mut().process_message(msg);
+            let mut state = state.borrow_mut();
+            state.set_time(now_ms);
+            state.process_event(event);
+            state.update_trips(now_ms);
         });
     }
+    info!("Frame source exhausted");
+}
+
+/// Also keeps an eye on the bus: error counters, bus-off and its recovery, and the
+/// `BusHealth` and ring stats in the car state.
+#[task]
+async fn frame_received(
+    mut twai: TwaiRx<'static, Async>,
+    selection: Selection,
+    mut rx: RxQueues,
+    one_shots: OneShotSender<'static>,
+    car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>,
+) {
+    let mut isotp = IsoTpLayer::obd(FlowControl::default());
+    let mut monitor = BusMonitor::new(selection.bitrate);
+    let mut confirmation = Confirmation::new(&selection);
+    let mut recovery = Recovery::default();
+    let mut dropped = 0;
+    loop {
+        if let Ok(result) = with_timeout(ISOTP_POLL_INTERVAL, twai.receive_async()).await {
+            match result {
+                Ok(message) => {
+                    trace!("Received TWAI message with data: {:?}", message);
+                    monitor.on_frame(&message);
+                    confirmation.on_frame();
+                    let now_ms = Instant::now().as_millis();
+                    if let Some(event) = isotp.on_frame(&message, now_ms) {
+                        handle_isotp_event(event, &mut rx, &one_shots).await;
+                    }
+                    rx.push_frame(message);
+                },
+                Err(e) => {
+                    // Counted, a noisy bus would flood the log
+                    debug!("Error reading message: {:?}", e);
+                    monitor.on_error();
+                    confirmation.on_error();
+                },
+            }
+        }
+        let now_ms = Instant::now().as_millis();
+        while let Some(event) = isotp.poll(now_ms) {
+            handle_isotp_event(event, &mut rx, &one_shots).await;
+        }
+
+        bus::collect(&mut monitor);
+        let (tx_errors, rx_errors, bus_off) = bus::error_counters();
+        if let Some(state) = monitor.on_counters(tx_errors, rx_errors, bus_off) {
+            warn!("CAN bus {}, TEC {} REC {}", state.label(), tx_errors, rx_errors);
+        }
+        if recovery.poll(monitor.health().state, now_ms) {
+            warn!("Bus-off recovery, attempt {}, next in {} ms", recovery.attempts(), recovery.delay_ms());
+            bus::start_recovery();
+        }
+        if let Some(health) = monitor.update(now_ms) {
+            let stats = rx.frames.stats();
+            if stats.dropped > dropped {
+                warn!("{} received frames dropped, the car state is not keeping up", stats.dropped - dropped);
+                dropped = stats.dropped;
+            }
+            car_state.lock(|state| {
+                let mut state = state.borrow_mut();
+                state.set_bus_health(health);
+                state.set_ring_stats(stats);
+            });
+        }
+    }
+}
+
+async fn handle_isotp_event(event: Event<EspTwaiFrame>, rx: &mut RxQueues, one_shots: &OneShotSender<'static>) {
+    match event {
+        Event::Message(message) => rx.push_message(message),
+        Event::Transmit(frame) => one_shots.send(frame).await,
+        Event::Error(e) => warn!("ISO-TP error: {:?}", e),
+    }
+}
+
+/// The only user of the transmit half, `frame_received` owns the receive half. Everything
+/// to send comes from `tx_scheduler`.
+#[task]
+async fn frame_transmitter(mut twai: TwaiTx<'static, Async>, receiver: CanTxReceiver<'static>) {
+    loop {
+        let frame = receiver.receive().await;
+        if LISTEN_ONLY {
+            continue;
+        }
+        let before = bus::arbitration_lost_capture();
+        match with_timeout(TX_TIMEOUT, twai.transmit_async(&frame)).await {
+            Ok(Ok(())) => {
+                bus::record_sent(&frame);
+                bus::record_arbitration(before, bus::arbitration_lost_capture());
+            }
+            Ok(Err(e)) => {
+                debug!("Error sending frame: {:?}", e);
+                bus::record_tx_error();
+            }
+            Err(_) => bus::record_tx_error(),
+        }
+    }
 }
 
+/// Sends the ISO-TP frames from `frame_received` and the OBD-II requests, all within `BusBudget`.
 #[task]
-async fn frame_received(mut twai: Twai<'static, Async>, sender: CanFrameSender<'static>) {
+async fn tx_scheduler(
+    bitrate: Bitrate,
+    one_shots: OneShotReceiver<'static>,
+    tx: CanTxSender<'static>,
+    car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>,
+) {
+    let mut scheduler = TxScheduler::new(BusBudget::new(bitrate));
+    scheduler.set_listen_only(LISTEN_ONLY);
+    let mut report_start = Instant::now();
     loop {
-        match twai.receive_async().await {
-            Ok(message) =>{
-                use embedded_can::*;
-
-                info!("Received TWAI message with data: {:?}", message);
-                sender.send(message).await},
-            Err(e) => {
-                warn!("Error reading message: {:?}", e);
-            },
+        // Counted in the stats when the queue is full
+        while let Ok(frame) = one_shots.try_receive() {
+            scheduler.send_once(frame);
         }
+        let now_ms = Instant::now().as_millis();
+        let request: Option<EspTwaiFrame> = scheduler.poll(now_ms, |now_ms| {
+            car_state.lock(|state| state.borrow_mut().next_obd_request(now_ms))
+        });
+        if let Some(request) = request {
+            tx.send(request).await;
+        }
+        if report_start.elapsed() >= TX_REPORT_INTERVAL {
+            let stats = scheduler.stats();
+            info!(
+                "CAN TX: {} frames sent, {} polls throttled, {} one-shots dropped",
+                stats.sent, stats.throttled, stats.dropped
+            );
+            report_start = Instant::now();
+        }
+        Timer::after_millis(5).await
     }
 }
 
 #[task]
 async fn voltage_calculator(mut pin: VoltageAdcPin, mut adc: VoltageAdc, car_state: Arc<Mutex<CriticalSectionRawMutex, RefCell<CarState>>>)->! {
-    let mut buffer: RingBuffer<f32,16> = RingBuffer::new();
+    let mut filter = VoltageFilter::new(BATTERY_DIVIDER, full_scale_mv(VOLTAGE_ATTENUATION));
     loop {
-        if let Ok(value) = adc.read_oneshot(&mut pin) {
-            // info!("Raw: {}", value);
-            let converted = 3.3 / ((1<<12) * 3 * value) as f32;
-            // info!("Converted: {}", converted);
-            // info!("Length: {}",buffer.get_size());
-            buffer.push(converted);
-            let sum: f32 = buffer.iter().sum();
-            car_state.lock(|state| {
-                state.borrow_mut().set_voltage(sum / buffer.len() as f32);
-            });
-        } else {
-            // info!("Would block");
+        // Calibrated, so this is millivolts at the pin
+        if let Ok(millivolts) = adc.read_oneshot(&mut pin) {
+            filter.push(millivolts);
+            if let Some(volts) = filter.voltage() {
+                car_state.lock(|state| {
+                    state.borrow_mut().set_voltage(volts);
+                });
+            }
         }
         Timer::after_millis(100).await
     }
@@ -0,0 +1,144 @@
+use dashboard::settings::{self, Settings};
+use dashboard::store::{Flash, KvStore};
+use dashboard::trip::{TripComputer, TripId};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use esp_hal::system::{Cpu, CpuControl};
+use esp_hal::time::{Duration, Instant};
+use esp_storage::{FlashStorage, FlashStorageError};
+use log::{info, warn};
+
+/// The `nvs` partition of the default partition table, nothing else uses it.
+const PARTITION_OFFSET: u32 = 0x9000;
+const PARTITION_SIZE: u32 = 0x6000;
+/// Trip counters are written back this often while they change. With 6 sectors
+/// of 4k that erases each one every few hours of driving.
+const TRIP_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The `nvs` partition, offsets from its start.
+pub(crate) struct NvsPartition {
+    flash: FlashStorage,
+    /// Parked around each flash operation once it runs, see `with_app_core_parked`.
+    app_core: Option<CpuControl<'static>>,
+}
+
+impl NvsPartition {
+    /// esp-storage turns the flash cache off while it talks to the flash, and the app core runs
+    /// its tasks out of that cache: it would fetch garbage and crash. So it is parked, stalled in
+    /// hardware, until the operation is done. That happens inside a critical section, which takes
+    /// a lock shared by both cores, so the app core is never stopped in the middle of one holding
+    /// something the flash code needs. Parked it fetches nothing and carries on where it stopped.
+    /// An erase takes a few tens of ms, long enough for the TWAI FIFO to overflow, one more reason
+    /// the trips are only saved once a minute.
+    fn with_app_core_parked<R>(&mut self, op: impl FnOnce(&mut FlashStorage) -> R) -> R {
+        let NvsPartition { flash, app_core } = self;
+        let Some(cpu_control) = app_core.as_mut() else {
+            // Before the app core is started nothing else runs
+            return op(flash);
+        };
+        critical_section::with(|_| {
+            unsafe { cpu_control.park_core(Cpu::AppCpu) };
+            let result = op(flash);
+            cpu_control.unpark_core(Cpu::AppCpu);
+            result
+        })
+    }
+}
+
+impl Flash for NvsPartition {
+    type Error = FlashStorageError;
+    const SECTOR_SIZE: u32 = <FlashStorage as NorFlash>::ERASE_SIZE as u32;
+
+    fn capacity(&self) -> u32 {
+        PARTITION_SIZE
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), FlashStorageError> {
+        self.with_app_core_parked(|flash| ReadNorFlash::read(flash, PARTITION_OFFSET + offset, bytes))
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), FlashStorageError> {
+        self.with_app_core_parked(|flash| NorFlash::write(flash, PARTITION_OFFSET + offset, bytes))
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), FlashStorageError> {
+        let from = PARTITION_OFFSET + offset;
+        self.with_app_core_parked(|flash| NorFlash::erase(flash, from, from + Self::SECTOR_SIZE))
+    }
+}
+
+/// Settings and counters kept in flash. Flash that fails is logged and otherwise
+/// left alone, the dashboard works without it and starts with the defaults.
+pub(crate) struct Persistence {
+    store: Option<KvStore<NvsPartition>>,
+    pub settings: Settings,
+    saved_settings: Settings,
+    saved_trips: TripComputer,
+    last_trip_save: Instant,
+}
+
+impl Persistence {
+    pub fn open() -> Self {
+        let mut store = KvStore::mount(NvsPartition { flash: FlashStorage::new(), app_core: None })
+            .inspect_err(|e| warn!("Settings storage unusable: {:?}", e))
+            .ok();
+        let settings = store
+            .as_mut()
+            .and_then(|store| Settings::load(store).inspect_err(|e| warn!("Could not read settings: {:?}", e)).ok())
+            .unwrap_or_default();
+        info!("Settings: {:?}", settings);
+        Persistence {
+            store,
+            settings,
+            saved_settings: settings,
+            saved_trips: TripComputer::default(),
+            last_trip_save: Instant::now(),
+        }
+    }
+
+    /// Call once the app core runs, from then on it is parked around every flash access.
+    pub fn share_flash_with(&mut self, cpu_control: CpuControl<'static>) {
+        if let Some(store) = self.store.as_mut() {
+            store.flash_mut().app_core = Some(cpu_control);
+        }
+    }
+
+    /// Puts the odometer and trips from flash into `trips`.
+    pub fn load_trips(&mut self, trips: &mut TripComputer) {
+        let Some(store) = self.store.as_mut() else {
+            return;
+        };
+        if let Err(e) = settings::load_trips(store, trips) {
+            warn!("Could not read trips: {:?}", e);
+        }
+        self.saved_trips = trips.clone();
+        info!("Odometer at {} km", trips.odometer_km());
+    }
+
+    /// Writes `settings` if they changed.
+    pub fn save_settings(&mut self) {
+        if self.settings == self.saved_settings {
+            return;
+        }
+        self.saved_settings = self.settings;
+        if let Some(Err(e)) = self.store.as_mut().map(|store| self.settings.save(store)) {
+            warn!("Could not store settings: {:?}", e);
+        }
+    }
+
+    /// Writes the counters that changed every `TRIP_SAVE_INTERVAL`, or straight away after a reset.
+    pub fn save_trips(&mut self, trips: &TripComputer) {
+        let reset = [TripId::A, TripId::B]
+            .into_iter()
+            .any(|id| trips.trip(id).elapsed_ms() < self.saved_trips.trip(id).elapsed_ms());
+        if !reset && self.last_trip_save.elapsed() < TRIP_SAVE_INTERVAL {
+            return;
+        }
+        self.last_trip_save = Instant::now();
+        let Some(store) = self.store.as_mut() else {
+            return;
+        };
+        if let Err(e) = settings::save_trips(store, trips, &mut self.saved_trips) {
+            warn!("Could not store trips: {:?}", e);
+        }
+    }
+}